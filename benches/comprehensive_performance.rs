@@ -1,14 +1,14 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
 fn bench_regex_optimization(c: &mut Criterion) {
-    let patterns = vec![
+    let patterns = [
         r"\d+",
         r"/api/v[0-9]+/.*",
         r"user_[a-zA-Z0-9]+",
         r"Bearer .*",
         r"application/json",
     ];
-    let test_strings = vec![
+    let test_strings = [
         "12345",
         "/api/v1/users/123",
         "user_admin123",
@@ -64,6 +64,7 @@ fn bench_header_optimization(c: &mut Criterion) {
     group.finish();
 }
 
+#[allow(clippy::slow_vector_initialization)] // deliberately compared against vec![0; n]
 fn bench_memory_optimization(c: &mut Criterion) {
     let mut group = c.benchmark_group("memory_optimization");
 
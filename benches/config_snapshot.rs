@@ -0,0 +1,141 @@
+//! Compares the old config access pattern (a `parking_lot` read lock per rule check, six per
+//! request) with one `ArcSwap` snapshot per request, idle and while a reload loop swaps the
+//! config.
+
+use axum::http::HeaderMap;
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use logprox::config::{Config, ConfigHolder};
+use parking_lot::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Rule evaluations per request in `proxy_handler`.
+const CHECKS_PER_REQUEST: usize = 6;
+const READER_THREADS: usize = 8;
+
+const CONFIG_YAML: &str = r#"
+logging:
+  default: false
+  rules:
+    - name: "API"
+      match_conditions:
+        path: { patterns: ["/api/.*"] }
+        methods: ["POST"]
+      capture: { method: true, path: true }
+drop:
+  default: false
+  rules:
+    - name: "Deprecated"
+      match_conditions:
+        path: { patterns: ["/v1/deprecated"] }
+      response: { status_code: 410 }
+"#;
+
+fn load_config(path: &str) -> Config {
+    Config::from_file(path).unwrap()
+}
+
+fn evaluate(config: &Config, headers: &HeaderMap) -> bool {
+    config.should_drop_request_parts("GET", "/api/users", headers, "").is_some()
+        || config.should_log_request_parts("GET", "/api/users", headers, "").is_some()
+}
+
+/// Per-request work under the old pattern: one read lock per check.
+fn rwlock_request(lock: &RwLock<Config>, headers: &HeaderMap) {
+    for _ in 0..CHECKS_PER_REQUEST {
+        black_box(evaluate(&lock.read(), headers));
+    }
+}
+
+/// Per-request work under the snapshot pattern: one lock-free load, reused for every check.
+fn snapshot_request(holder: &ConfigHolder, headers: &HeaderMap) {
+    let config = holder.get();
+    for _ in 0..CHECKS_PER_REQUEST {
+        black_box(evaluate(&config, headers));
+    }
+}
+
+/// Runs `iters` requests split across reader threads, optionally with a thread reloading the
+/// config in a loop, and returns the wall time.
+fn run_contended<R, W>(iters: u64, reload: bool, request: R, swap: W) -> Duration
+where
+    R: Fn() + Sync,
+    W: Fn() + Sync,
+{
+    let stop = AtomicBool::new(false);
+    let per_thread = iters.div_ceil(READER_THREADS as u64);
+    std::thread::scope(|scope| {
+        if reload {
+            scope.spawn(|| {
+                while !stop.load(Ordering::Relaxed) {
+                    swap();
+                    std::thread::sleep(Duration::from_micros(200));
+                }
+            });
+        }
+        let start = Instant::now();
+        let readers: Vec<_> = (0..READER_THREADS)
+            .map(|_| {
+                scope.spawn(|| {
+                    for _ in 0..per_thread {
+                        request();
+                    }
+                })
+            })
+            .collect();
+        for reader in readers {
+            reader.join().unwrap();
+        }
+        let elapsed = start.elapsed();
+        stop.store(true, Ordering::Relaxed);
+        elapsed
+    })
+}
+
+fn bench_config_access(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.yaml");
+    std::fs::write(&path, CONFIG_YAML).unwrap();
+    let path = path.to_str().unwrap().to_string();
+
+    let lock = RwLock::new(load_config(&path));
+    let holder = ConfigHolder::new(load_config(&path));
+    let headers = HeaderMap::new();
+
+    let mut group = c.benchmark_group("config_access");
+    group.bench_function("rwlock_read_per_check", |b| b.iter(|| rwlock_request(&lock, &headers)));
+    group.bench_function("arc_swap_snapshot_per_request", |b| b.iter(|| snapshot_request(&holder, &headers)));
+
+    for reload in [false, true] {
+        let label = if reload { "with_reloads" } else { "no_reloads" };
+        group.bench_with_input(BenchmarkId::new("rwlock_8_threads", label), &reload, |b, &reload| {
+            b.iter_custom(|iters| {
+                run_contended(iters, reload, || rwlock_request(&lock, &headers), || {
+                    let config = load_config(&path);
+                    *lock.write() = config;
+                })
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("arc_swap_8_threads", label), &reload, |b, &reload| {
+            b.iter_custom(|iters| {
+                run_contended(iters, reload, || snapshot_request(&holder, &headers), || {
+                    holder.reload_from(&path).unwrap();
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_snapshot_load(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.yaml");
+    std::fs::write(&path, CONFIG_YAML).unwrap();
+    let holder = Arc::new(ConfigHolder::new(load_config(path.to_str().unwrap())));
+
+    c.bench_function("config_holder_get", |b| b.iter(|| black_box(holder.get())));
+}
+
+criterion_group!(benches, bench_config_access, bench_snapshot_load);
+criterion_main!(benches);
@@ -1,7 +1,5 @@
 use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
-use logprox::config::{BodyMatch, Config, MatchConditions, PathMatch};
-use std::collections::HashMap;
-use std::time::Duration;
+use logprox::config::Config;
 
 fn bench_regex_compilation(c: &mut Criterion) {
     let mut group = c.benchmark_group("regex_compilation");
@@ -81,8 +79,7 @@ fn bench_string_allocations(c: &mut Criterion) {
             // Optimized code (no allocations)
             let _method_str = method; // &str
             let _path_str = path; // &str
-            let _body_str =
-                std::borrow::Cow::from(std::string::String::from_utf8_lossy(body_bytes));
+            let _body_str = std::string::String::from_utf8_lossy(body_bytes);
 
             black_box(())
         });
@@ -97,7 +94,7 @@ fn bench_config_locking(c: &mut Criterion) {
     let mut group = c.benchmark_group("config_locking");
 
     let config = Arc::new(ConfigHolder::new(Config {
-        server: logprox::config::ServerConfig { port: 3000 },
+        server: logprox::config::ServerConfig { port: 3000, ..Default::default() },
         logging: logprox::config::LoggingConfig {
             default: false,
             rules: vec![],
@@ -110,7 +107,7 @@ fn bench_config_locking(c: &mut Criterion) {
             default: false,
             rules: vec![],
         },
-        upstream: Default::default(),
+        ..Default::default()
     }));
 
     // Current approach: Multiple separate lock acquisitions
@@ -138,7 +135,7 @@ fn bench_config_locking(c: &mut Criterion) {
 }
 
 fn bench_header_processing(c: &mut Criterion) {
-    use axum::http::{HeaderMap, HeaderName, HeaderValue};
+    use axum::http::{HeaderMap, HeaderValue};
 
     let mut group = c.benchmark_group("header_processing");
 
@@ -206,7 +203,7 @@ fn bench_header_processing(c: &mut Criterion) {
 }
 
 // Mock ConfigHolder for testing
-use logprox::config::{ConfigHolder, DropConfig, LoggingConfig};
+use logprox::config::ConfigHolder;
 
 criterion_group!(
     benches,
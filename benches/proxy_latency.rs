@@ -17,11 +17,12 @@ async fn setup_test_servers() -> (String, String) {
     });
 
     let config = Config {
-        server: ServerConfig { port: 0 },
+        server: ServerConfig { port: 0, ..Default::default() },
         logging: LoggingConfig { default: false, rules: vec![] },
         drop: DropConfig { default: false, rules: vec![] },
         response_logging: ResponseLoggingConfig { default: false, rules: vec![] },
         upstream: logprox::config::UpstreamConfig { allow_private_networks: true, ..Default::default() },
+        ..Default::default()
     };
     let config_holder = Arc::new(ConfigHolder::new(config));
 
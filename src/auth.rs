@@ -0,0 +1,282 @@
+//! Caller authentication for the `auth` config section: API keys, HTTP Basic and JWT bearer
+//! tokens.
+
+use crate::config::{AuthConfig, Claims};
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use base64::Engine;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Why a request failed authentication.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthError {
+    /// The request carried no credentials for any configured method.
+    MissingCredentials,
+    /// Credentials were present but did not verify.
+    InvalidCredentials(String),
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::MissingCredentials => write!(f, "missing credentials"),
+            AuthError::InvalidCredentials(reason) => write!(f, "invalid credentials: {}", reason),
+        }
+    }
+}
+
+/// A verified caller.
+#[derive(Debug, Clone)]
+pub struct Identity {
+    /// `"api_key"`, `"basic"` or `"jwt"`.
+    pub method: &'static str,
+    pub claims: Claims,
+}
+
+impl Identity {
+    fn subject(method: &'static str, sub: &str) -> Self {
+        let mut claims = Claims::new();
+        claims.insert("sub".to_string(), sub.into());
+        Self { method, claims }
+    }
+}
+
+/// Verifiers prepared from an [`AuthConfig`], with key files already read.
+pub struct Authenticator {
+    api_key_header: Option<HeaderName>,
+    api_keys: Vec<(String, [u8; 32])>,
+    htpasswd: Option<HashMap<String, String>>,
+    jwt: Option<JwtVerifier>,
+}
+
+struct JwtVerifier {
+    keys: Vec<JwtKey>,
+    issuers: Vec<String>,
+    audiences: Vec<String>,
+    algorithms: Vec<Algorithm>,
+    leeway_secs: u64,
+}
+
+struct JwtKey {
+    kid: Option<String>,
+    alg: Option<Algorithm>,
+    key: DecodingKey,
+}
+
+impl std::fmt::Debug for Authenticator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Authenticator")
+            .field("api_keys", &self.api_keys.len())
+            .field("htpasswd_users", &self.htpasswd.as_ref().map(HashMap::len))
+            .field("jwt_keys", &self.jwt.as_ref().map(|jwt| jwt.keys.len()))
+            .finish()
+    }
+}
+
+impl Authenticator {
+    pub fn new(config: &AuthConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        let mut api_key_header = None;
+        let mut api_keys = Vec::new();
+        if let Some(cfg) = &config.api_keys {
+            api_key_header = Some(
+                HeaderName::from_bytes(cfg.header.to_ascii_lowercase().as_bytes())
+                    .map_err(|_| format!("Invalid API key header '{}'", cfg.header))?,
+            );
+            for key in &cfg.keys {
+                let hash: [u8; 32] = hex::decode(&key.sha256)
+                    .ok()
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .ok_or_else(|| format!("API key '{}' is not a hex SHA-256 hash", key.name))?;
+                api_keys.push((key.name.clone(), hash));
+            }
+        }
+
+        let htpasswd = match &config.basic {
+            Some(cfg) => Some(load_htpasswd(&cfg.htpasswd_file)?),
+            None => None,
+        };
+
+        let jwt = match &config.jwt {
+            Some(cfg) => {
+                let algorithms = cfg.algorithms.iter()
+                    .map(|a| Algorithm::from_str(a).map_err(|_| format!("Unknown JWT algorithm '{}'", a)))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Some(JwtVerifier {
+                    keys: load_jwks(&cfg.jwks_file)?,
+                    issuers: cfg.issuers.clone(),
+                    audiences: cfg.audiences.clone(),
+                    algorithms,
+                    leeway_secs: cfg.parse_leeway().as_secs(),
+                })
+            }
+            None => None,
+        };
+
+        Ok(Self { api_key_header, api_keys, htpasswd, jwt })
+    }
+
+    /// Verifies the first credentials found in `headers`: the API key header, then an
+    /// `Authorization: Basic` or `Authorization: Bearer` header.
+    pub async fn authenticate(&self, headers: &HeaderMap) -> Result<Identity, AuthError> {
+        if let Some(value) = self.api_key_header.as_ref().and_then(|h| headers.get(h)) {
+            return self.verify_api_key(value);
+        }
+        let authorization = headers
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split_once(' '));
+        match authorization {
+            Some((scheme, credentials)) if scheme.eq_ignore_ascii_case("basic") && self.htpasswd.is_some() => {
+                self.verify_basic(credentials.trim()).await
+            }
+            Some((scheme, token)) if scheme.eq_ignore_ascii_case("bearer") && self.jwt.is_some() => {
+                self.verify_jwt(token.trim())
+            }
+            _ => Err(AuthError::MissingCredentials),
+        }
+    }
+
+    /// Headers that carry credentials for the configured methods.
+    pub fn credential_headers(&self) -> Vec<HeaderName> {
+        let mut names: Vec<HeaderName> = self.api_key_header.iter().cloned().collect();
+        if self.htpasswd.is_some() || self.jwt.is_some() {
+            names.push(axum::http::header::AUTHORIZATION);
+        }
+        names
+    }
+
+    /// `WWW-Authenticate` challenges advertised on 401 responses.
+    pub fn challenges(&self) -> Vec<String> {
+        let mut challenges = Vec::new();
+        if self.htpasswd.is_some() {
+            challenges.push("Basic realm=\"logprox\"".to_string());
+        }
+        if self.jwt.is_some() {
+            challenges.push("Bearer realm=\"logprox\"".to_string());
+        }
+        challenges
+    }
+
+    fn verify_api_key(&self, value: &HeaderValue) -> Result<Identity, AuthError> {
+        let presented: [u8; 32] = Sha256::digest(value.as_bytes()).into();
+        self.api_keys.iter()
+            .find(|(_, hash)| constant_time_eq(hash, &presented))
+            .map(|(name, _)| Identity::subject("api_key", name))
+            .ok_or_else(|| AuthError::InvalidCredentials("unknown API key".to_string()))
+    }
+
+    async fn verify_basic(&self, credentials: &str) -> Result<Identity, AuthError> {
+        let invalid = || AuthError::InvalidCredentials("bad username or password".to_string());
+        let decoded = base64::engine::general_purpose::STANDARD
+            .decode(credentials)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok())
+            .ok_or_else(invalid)?;
+        let (user, password) = decoded.split_once(':').ok_or_else(invalid)?;
+        let hash = self.htpasswd.as_ref().and_then(|users| users.get(user)).ok_or_else(invalid)?;
+        if verify_htpasswd_hash(password, hash).await {
+            Ok(Identity::subject("basic", user))
+        } else {
+            Err(invalid())
+        }
+    }
+
+    fn verify_jwt(&self, token: &str) -> Result<Identity, AuthError> {
+        let jwt = self.jwt.as_ref().ok_or(AuthError::MissingCredentials)?;
+        let invalid = |reason: String| AuthError::InvalidCredentials(reason);
+        let header = jsonwebtoken::decode_header(token).map_err(|e| invalid(e.to_string()))?;
+        if !jwt.algorithms.is_empty() && !jwt.algorithms.contains(&header.alg) {
+            return Err(invalid(format!("algorithm {:?} not allowed", header.alg)));
+        }
+
+        let mut validation = Validation::new(header.alg);
+        validation.leeway = jwt.leeway_secs;
+        validation.set_required_spec_claims(&["exp"]);
+        if !jwt.issuers.is_empty() {
+            validation.set_issuer(&jwt.issuers);
+        }
+        if jwt.audiences.is_empty() {
+            validation.validate_aud = false;
+        } else {
+            validation.set_audience(&jwt.audiences);
+        }
+
+        let candidates = jwt.keys.iter().filter(|k| {
+            (header.kid.is_none() || k.kid.is_none() || k.kid == header.kid)
+                && k.alg.map_or(true, |alg| alg == header.alg)
+        });
+        let mut reason = "no matching key".to_string();
+        for candidate in candidates {
+            match jsonwebtoken::decode::<Claims>(token, &candidate.key, &validation) {
+                Ok(data) => return Ok(Identity { method: "jwt", claims: data.claims }),
+                Err(e) => reason = e.to_string(),
+            }
+        }
+        Err(invalid(reason))
+    }
+}
+
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn load_htpasswd(path: &str) -> Result<HashMap<String, String>, Box<dyn std::error::Error>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read htpasswd file '{}': {}", path, e))?;
+    let mut users = HashMap::new();
+    for line in content.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (user, hash) = line
+            .split_once(':')
+            .ok_or_else(|| format!("Malformed htpasswd line in '{}'", path))?;
+        if !(hash.starts_with("$2") || hash.starts_with("{SHA}")) {
+            return Err(format!(
+                "Unsupported htpasswd hash for user '{}' in '{}' (use bcrypt or {{SHA}})",
+                user, path
+            )
+            .into());
+        }
+        users.insert(user.to_string(), hash.to_string());
+    }
+    Ok(users)
+}
+
+async fn verify_htpasswd_hash(password: &str, hash: &str) -> bool {
+    match hash.strip_prefix("{SHA}") {
+        Some(expected) => {
+            let digest = sha1::Sha1::digest(password.as_bytes());
+            let expected = base64::engine::general_purpose::STANDARD.decode(expected).unwrap_or_default();
+            constant_time_eq(&digest, &expected)
+        }
+        // bcrypt is deliberately slow: keep it off the async workers
+        None => {
+            let (password, hash) = (password.to_string(), hash.to_string());
+            tokio::task::spawn_blocking(move || bcrypt::verify(password, &hash).unwrap_or(false))
+                .await
+                .unwrap_or(false)
+        }
+    }
+}
+
+fn load_jwks(path: &str) -> Result<Vec<JwtKey>, Box<dyn std::error::Error>> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read JWKS file '{}': {}", path, e))?;
+    let jwks: JwkSet = serde_json::from_str(&content)
+        .map_err(|e| format!("Invalid JWKS file '{}': {}", path, e))?;
+    jwks.keys.iter()
+        .map(|jwk| {
+            let key = DecodingKey::from_jwk(jwk)
+                .map_err(|e| format!("Unusable key in JWKS file '{}': {}", path, e))?;
+            Ok(JwtKey {
+                kid: jwk.common.key_id.clone(),
+                alg: jwk.common.key_algorithm.and_then(|a| Algorithm::from_str(&a.to_string()).ok()),
+                key,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,171 @@
+//! Command-line interface. Without a subcommand the binary runs the proxy.
+
+use clap::{Args, Parser, Subcommand};
+use logprox::config::schema;
+use logprox::config::{config_file_path, Claims, Config, ConfigSources, ValidationIssue};
+use logprox::handlers::dry_run::{dry_run, DryRunInput, SyntheticRequest, SyntheticResponse};
+use std::collections::BTreeMap;
+use std::process::ExitCode;
+
+#[derive(Debug, Parser)]
+#[command(name = "logprox", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Check a config file and report every problem found. Exits non-zero if there are any.
+    Validate {
+        /// Config file or directory to check, with its includes and the `LOGPROX_ENV` overlay.
+        file: String,
+        /// Only fail on errors; report warnings without failing.
+        #[arg(long)]
+        allow_warnings: bool,
+        /// Print the issues as a JSON array.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show which rules a synthetic request (and optional response) matches and what would be
+    /// logged, as JSON.
+    TestRule(Box<TestRuleArgs>),
+    /// Print the JSON Schema config files are checked against.
+    Schema,
+    /// Print `config_docs.md` with its field reference regenerated from the schema.
+    Docs,
+}
+
+#[derive(Debug, Args)]
+pub struct TestRuleArgs {
+    /// Config file to evaluate. Default: `CONFIG_FILE`, or `config.yaml`.
+    #[arg(long)]
+    pub config: Option<String>,
+    #[arg(long, default_value = "GET")]
+    pub method: String,
+    /// Request path, including the embedded upstream URL.
+    #[arg(long)]
+    pub path: String,
+    /// Request header as `name: value`. Repeatable.
+    #[arg(short = 'H', long = "header", value_parser = parse_header)]
+    pub headers: Vec<(String, String)>,
+    #[arg(long, default_value = "")]
+    pub body: String,
+    /// Caller claims as a JSON object, as if the request were authenticated.
+    #[arg(long, value_parser = parse_claims)]
+    pub claims: Option<Claims>,
+    /// Client IP address, for `client_ip` conditions.
+    #[arg(long)]
+    pub client_ip: Option<std::net::IpAddr>,
+    /// Response status code; evaluates response logging rules against a synthetic response.
+    #[arg(long)]
+    pub status: Option<u16>,
+    /// Response header as `name: value`. Repeatable.
+    #[arg(long = "response-header", value_parser = parse_header)]
+    pub response_headers: Vec<(String, String)>,
+    #[arg(long, default_value = "")]
+    pub response_body: String,
+    /// Response latency in milliseconds, for `min_latency` and `max_latency` conditions.
+    #[arg(long)]
+    pub latency_ms: Option<u64>,
+}
+
+fn parse_header(s: &str) -> Result<(String, String), String> {
+    let (name, value) = s.split_once(':').ok_or_else(|| format!("expected 'name: value', got '{}'", s))?;
+    Ok((name.trim().to_string(), value.trim().to_string()))
+}
+
+fn parse_claims(s: &str) -> Result<Claims, String> {
+    serde_json::from_str(s).map_err(|e| format!("claims must be a JSON object: {}", e))
+}
+
+pub fn validate(file: &str, allow_warnings: bool, json: bool) -> ExitCode {
+    if let Err(e) = std::fs::metadata(file) {
+        eprintln!("{}: cannot read config: {}", file, e);
+        return ExitCode::FAILURE;
+    }
+    let issues = Config::check_sources(&ConfigSources::load(file)).1;
+    let errors = issues.iter().filter(|issue| issue.is_error()).count();
+    let warnings = issues.len() - errors;
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&issues).unwrap_or_default());
+    } else {
+        for issue in &issues {
+            println!("{}", located(file, issue));
+        }
+        if issues.is_empty() {
+            println!("{}: OK", file);
+        } else {
+            println!("{}: {} error(s), {} warning(s)", file, errors, warnings);
+        }
+    }
+
+    if errors > 0 || (warnings > 0 && !allow_warnings) {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Formats an issue compiler-style: `file:line:column: severity: path: message`.
+fn located(file: &str, issue: &ValidationIssue) -> String {
+    if issue.file.is_some() {
+        issue.to_string()
+    } else if issue.line.is_some() {
+        format!("{}:{}", file, issue)
+    } else {
+        format!("{}: {}", file, issue)
+    }
+}
+
+pub fn test_rule(args: TestRuleArgs) -> ExitCode {
+    let file = args.config.unwrap_or_else(config_file_path);
+    let config = match Config::from_file(&file) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{}: {}", file, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let has_response = args.status.is_some()
+        || !args.response_headers.is_empty()
+        || !args.response_body.is_empty()
+        || args.latency_ms.is_some();
+    let input = DryRunInput {
+        request: SyntheticRequest {
+            method: args.method,
+            path: args.path,
+            headers: args.headers.into_iter().collect(),
+            body: args.body,
+            claims: args.claims,
+            client_ip: args.client_ip,
+        },
+        response: has_response.then(|| SyntheticResponse {
+            status_code: args.status.unwrap_or(200),
+            headers: args.response_headers.into_iter().collect::<BTreeMap<_, _>>(),
+            body: args.response_body,
+            latency_ms: args.latency_ms,
+        }),
+    };
+    match dry_run(&config, &input) {
+        Ok(report) => {
+            println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+pub fn schema() -> ExitCode {
+    println!("{}", serde_json::to_string_pretty(&schema::schema()).unwrap_or_default());
+    ExitCode::SUCCESS
+}
+
+pub fn docs() -> ExitCode {
+    print!("{}", schema::docs());
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,130 @@
+//! Global and per-upstream-host concurrency limits with a bounded wait queue.
+
+use crate::config::ConcurrencyConfig;
+use crate::metrics::Metrics;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Number of tracked hosts above which idle host semaphores are pruned.
+const PRUNE_THRESHOLD: usize = 1_000;
+
+/// Why a request was shed instead of served.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShedReason {
+    /// The wait queue already held `max_queue` requests.
+    QueueFull,
+    /// No slot freed up within `max_wait`.
+    WaitTimeout,
+}
+
+/// Slots held by one request. Released when dropped.
+#[derive(Debug)]
+pub struct ConcurrencyPermit {
+    _host: Option<OwnedSemaphorePermit>,
+    _global: Option<OwnedSemaphorePermit>,
+    metrics: Arc<Metrics>,
+}
+
+impl Drop for ConcurrencyPermit {
+    fn drop(&mut self) {
+        self.metrics.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Semaphores enforcing a [`ConcurrencyConfig`]. Rebuilt when the config changes; requests
+/// admitted under the old limits finish against the old semaphores.
+#[derive(Debug)]
+pub struct ConcurrencyLimiter {
+    config: ConcurrencyConfig,
+    global: Option<Arc<Semaphore>>,
+    hosts: Mutex<HashMap<String, (Arc<Semaphore>, usize)>>,
+}
+
+impl ConcurrencyLimiter {
+    pub fn new(config: ConcurrencyConfig) -> Self {
+        Self {
+            global: config.max_in_flight.map(|n| Arc::new(Semaphore::new(n))),
+            config,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn config(&self) -> &ConcurrencyConfig {
+        &self.config
+    }
+
+    fn host_semaphore(&self, host: &str) -> Option<Arc<Semaphore>> {
+        let cap = self.config.host_limit(host)?;
+        let mut hosts = self.hosts.lock();
+        if hosts.len() > PRUNE_THRESHOLD {
+            hosts.retain(|_, (sem, cap)| sem.available_permits() < *cap);
+        }
+        let (sem, _) = hosts
+            .entry(host.to_string())
+            .or_insert_with(|| (Arc::new(Semaphore::new(cap)), cap));
+        Some(sem.clone())
+    }
+
+    /// Waits for a global slot and, when `host` is given, a slot for that upstream host.
+    /// Sheds the request if the queue is full or no slot frees up within `max_wait`.
+    pub async fn acquire(
+        &self,
+        host: Option<&str>,
+        metrics: &Arc<Metrics>,
+    ) -> Result<ConcurrencyPermit, ShedReason> {
+        let host_sem = host.and_then(|h| self.host_semaphore(h));
+        let global_sem = self.global.clone();
+        let permit = |host, global| {
+            metrics.in_flight.fetch_add(1, Ordering::Relaxed);
+            ConcurrencyPermit { _host: host, _global: global, metrics: metrics.clone() }
+        };
+
+        // Fast path: both slots free. The host slot is taken first so a request waiting on a
+        // saturated host never holds a global slot.
+        let host_permit = match &host_sem {
+            Some(sem) => sem.clone().try_acquire_owned().ok().map(Some),
+            None => Some(None),
+        };
+        if let Some(host_permit) = host_permit {
+            match &global_sem {
+                Some(sem) => {
+                    if let Ok(global_permit) = sem.clone().try_acquire_owned() {
+                        return Ok(permit(host_permit, Some(global_permit)));
+                    }
+                }
+                None => return Ok(permit(host_permit, None)),
+            }
+        }
+
+        // Slow path: join the bounded queue.
+        if metrics.queue_depth.fetch_add(1, Ordering::Relaxed) >= self.config.max_queue as i64 {
+            metrics.queue_depth.fetch_sub(1, Ordering::Relaxed);
+            metrics.shed_queue_full.fetch_add(1, Ordering::Relaxed);
+            return Err(ShedReason::QueueFull);
+        }
+        let waited = tokio::time::timeout(self.config.parse_max_wait(), async {
+            let host_permit = match host_sem {
+                Some(sem) => sem.acquire_owned().await.ok(),
+                None => None,
+            };
+            let global_permit = match global_sem {
+                Some(sem) => sem.acquire_owned().await.ok(),
+                None => None,
+            };
+            (host_permit, global_permit)
+        })
+        .await;
+        metrics.queue_depth.fetch_sub(1, Ordering::Relaxed);
+
+        match waited {
+            Ok((host_permit, global_permit)) => Ok(permit(host_permit, global_permit)),
+            Err(_) => {
+                metrics.shed_wait_timeout.fetch_add(1, Ordering::Relaxed);
+                Err(ShedReason::WaitTimeout)
+            }
+        }
+    }
+}
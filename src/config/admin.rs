@@ -0,0 +1,61 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// The admin listener serving `/config`, `/config/docs`, `/config/reload` and `/metrics`,
+/// kept apart from proxied traffic. `listen` and `tls` are read at startup; the token is
+/// re-read on every request, so a reload rotates it.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct AdminConfig {
+    /// Address the admin listener binds. Default: `127.0.0.1:9090` (localhost only).
+    #[serde(default = "default_admin_listen")]
+    pub listen: String,
+    /// Hex-encoded SHA-256 of the bearer token admin requests must present in
+    /// `Authorization: Bearer <token>`. No token required if absent.
+    #[serde(default)]
+    pub bearer_token_sha256: Option<String>,
+    /// Serve the admin listener over TLS, optionally requiring client certificates.
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+}
+
+fn default_admin_listen() -> String {
+    "127.0.0.1:9090".to_string()
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            listen: default_admin_listen(),
+            bearer_token_sha256: None,
+            tls: None,
+        }
+    }
+}
+
+/// TLS settings for a listener. Setting `client_ca_file` enables mutual TLS: clients must
+/// present a certificate signed by one of its CAs.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct TlsConfig {
+    /// PEM certificate chain presented by the listener.
+    pub cert_file: String,
+    /// PEM private key for `cert_file`.
+    pub key_file: String,
+    /// PEM CA bundle used to verify client certificates.
+    #[serde(default)]
+    pub client_ca_file: Option<String>,
+}
+
+impl AdminConfig {
+    /// Decodes `bearer_token_sha256`. Returns `Ok(None)` when no token is configured.
+    pub fn token_hash(&self) -> Result<Option<[u8; 32]>, String> {
+        self.bearer_token_sha256
+            .as_deref()
+            .map(|hash| {
+                hex::decode(hash)
+                    .ok()
+                    .and_then(|bytes| bytes.try_into().ok())
+                    .ok_or_else(|| "admin.bearer_token_sha256 is not a hex SHA-256 hash".to_string())
+            })
+            .transpose()
+    }
+}
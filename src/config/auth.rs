@@ -0,0 +1,120 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use super::request::{parse_duration_str, MatchConditions};
+use crate::auth::Authenticator;
+
+/// Verified claims of an authenticated caller. JWTs contribute their payload; API keys and
+/// Basic credentials contribute `{"sub": <key name or username>}`.
+pub type Claims = serde_json::Map<String, serde_json::Value>;
+
+/// Authenticates callers before their requests are proxied. Configure any combination of
+/// `api_keys`, `basic` and `jwt`; a request is accepted if any configured method verifies it.
+/// Authentication is off when no method is configured.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
+pub struct AuthConfig {
+    /// Requests that must authenticate. Empty = every request. Other requests are still
+    /// authenticated opportunistically when a rule matches on or captures claims.
+    #[serde(default)]
+    pub match_conditions: MatchConditions,
+    /// Static API keys, stored as SHA-256 hashes.
+    #[serde(default)]
+    pub api_keys: Option<ApiKeyAuth>,
+    /// HTTP Basic credentials checked against an htpasswd file.
+    #[serde(default)]
+    pub basic: Option<BasicAuth>,
+    /// Bearer JWTs verified with keys from a local JWKS file.
+    #[serde(default)]
+    pub jwt: Option<JwtAuth>,
+    /// Remove the `authorization` and API key headers before forwarding upstream.
+    #[serde(default)]
+    pub strip_credentials: bool,
+    /// Key material loaded from the files above by [`AuthConfig::load`].
+    #[serde(skip)]
+    authenticator: Option<Arc<Authenticator>>,
+}
+
+/// API keys presented in a request header.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ApiKeyAuth {
+    /// Header carrying the key. Default: `x-api-key`.
+    #[serde(default = "default_api_key_header")]
+    pub header: String,
+    /// Accepted keys.
+    pub keys: Vec<ApiKey>,
+}
+
+fn default_api_key_header() -> String {
+    "x-api-key".to_string()
+}
+
+/// A named API key. Generate the hash with `printf '%s' "$KEY" | sha256sum`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ApiKey {
+    /// Reported as the `sub` claim of callers using this key.
+    pub name: String,
+    /// Hex-encoded SHA-256 of the key.
+    pub sha256: String,
+}
+
+/// HTTP Basic authentication against an Apache htpasswd file.
+/// Supports bcrypt (`$2y$`, `$2b$`, `$2a$`) and `{SHA}` entries.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct BasicAuth {
+    /// htpasswd file with bcrypt or `{SHA}` password hashes.
+    pub htpasswd_file: String,
+}
+
+/// JWT bearer token verification. Tokens must carry an unexpired `exp` claim.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct JwtAuth {
+    /// JWKS file holding the HMAC (`oct`), RSA or EC verification keys.
+    pub jwks_file: String,
+    /// Accepted `iss` values. Empty = issuer not checked.
+    #[serde(default)]
+    pub issuers: Vec<String>,
+    /// Accepted `aud` values; the token must name at least one. Empty = audience not checked.
+    #[serde(default)]
+    pub audiences: Vec<String>,
+    /// Accepted signing algorithms (e.g. `RS256`, `ES256`, `HS256`). Empty = any algorithm
+    /// matching the key type.
+    #[serde(default)]
+    pub algorithms: Vec<String>,
+    /// Clock skew tolerated when checking `exp` and `nbf` (e.g. `"30s"`). Default: 60s.
+    #[serde(default)]
+    pub leeway: Option<String>,
+}
+
+impl JwtAuth {
+    /// Parses `leeway`, falling back to 60 seconds.
+    pub fn parse_leeway(&self) -> std::time::Duration {
+        self.leeway
+            .as_deref()
+            .and_then(parse_duration_str)
+            .unwrap_or(std::time::Duration::from_secs(60))
+    }
+}
+
+impl AuthConfig {
+    /// True when at least one authentication method is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.api_keys.is_some() || self.basic.is_some() || self.jwt.is_some()
+    }
+
+    /// Reads the htpasswd and JWKS files and prepares the configured verifiers.
+    /// Called by [`Config::from_file`](super::Config::from_file).
+    pub fn load(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.authenticator = if self.is_enabled() {
+            Some(Arc::new(Authenticator::new(self)?))
+        } else {
+            None
+        };
+        Ok(())
+    }
+
+    /// The verifier built by [`load`](Self::load), if authentication is enabled and loaded.
+    pub fn authenticator(&self) -> Option<&Arc<Authenticator>> {
+        self.authenticator.as_ref()
+    }
+}
@@ -0,0 +1,90 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::request::MatchConditions;
+
+/// Controls body transformation. Like header rules, every matching rule applies, in config
+/// order, so later rules see the changes made by earlier ones.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
+pub struct BodyConfig {
+    /// Body rules. Every matching rule applies.
+    #[serde(default)]
+    pub rules: Vec<BodyRule>,
+}
+
+/// Body changes for requests matching `match_conditions`: `request` on the way upstream,
+/// `response` on the way back to the client.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct BodyRule {
+    /// Rule name, reported in logs and metrics. Rules from included files are merged by name.
+    pub name: String,
+    /// The file this rule was loaded from, when the config was assembled from files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub match_conditions: MatchConditions,
+    /// Changes to the request body forwarded upstream.
+    #[serde(default)]
+    pub request: BodyTransform,
+    /// Changes to the upstream (or replayed) response body returned to the client.
+    #[serde(default)]
+    pub response: BodyTransform,
+}
+
+/// Body changes, applied in the order `template`, `json_patch`, `replace`. `Content-Length` is
+/// recomputed whenever the body changes.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct BodyTransform {
+    /// Replaces the whole body. A template with the same `{{...}}` placeholders as header rule
+    /// values, read from the request.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub template: Option<String>,
+    /// JSON Patch operations, applied in order when the body is JSON. An operation whose path
+    /// does not resolve is skipped.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub json_patch: Vec<JsonPatchOperation>,
+    /// Regex find/replace operations on the body as text, applied in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub replace: Vec<TextReplacement>,
+}
+
+impl BodyTransform {
+    pub fn is_empty(&self) -> bool {
+        self.template.is_none() && self.json_patch.is_empty() && self.replace.is_empty()
+    }
+}
+
+/// One JSON Patch (RFC 6902) operation.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct JsonPatchOperation {
+    pub op: PatchOp,
+    /// JSON Pointer (RFC 6901) to the target, e.g. `/user/name` or `/items/0`. `-` as the last
+    /// token of an `add` appends to an array; `""` is the whole document.
+    pub path: String,
+    /// The value to add or replace with. String values inside it are `{{...}}` templates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+}
+
+/// A JSON Patch operation kind.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PatchOp {
+    /// Insert `value`, replacing an existing object member or shifting array elements right.
+    Add,
+    /// Remove the target, which must exist.
+    Remove,
+    /// Replace the target, which must exist, with `value`.
+    Replace,
+}
+
+/// A regex find/replace on the body.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct TextReplacement {
+    /// Regex matched against the body. Every match is replaced.
+    pub pattern: String,
+    /// Replacement text; `$1` and `$name` insert capture groups (write `${name}` as
+    /// `$${name}`).
+    #[serde(default)]
+    pub replacement: String,
+}
@@ -0,0 +1,697 @@
+//! Rules compiled once per config. Every pattern is built into a [`Regex`] or [`RegexSet`]
+//! when the config loads and is dropped along with it, so matching never consults a shared
+//! cache.
+
+use ipnet::IpNet;
+use regex::{Regex, RegexSet, SetMatches};
+use std::cell::OnceCell;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::ops::{Range, RangeInclusive};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use super::request::parse_duration_str;
+use super::{claim_values, BodyFields, BodyRule, BodyTransform, Config, GraphqlMatch, GraphqlOperationType, MatchConditions, PartMatch, RequestContext, ResponseContext, ResponseMatchConditions, RewriteRule, RewriteTarget, SampleKey, Sampling};
+
+/// Compiles `pattern`. Strict compilation reports an invalid pattern as an error; lenient
+/// compilation returns `None`, and the pattern never matches.
+pub(super) fn compile_pattern(kind: &str, pattern: &str, strict: bool) -> Result<Option<Regex>, String> {
+    match Regex::new(pattern) {
+        Ok(re) => Ok(Some(re)),
+        Err(e) if strict => Err(format!("Invalid {} pattern '{}': {}", kind, pattern, e)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Parses an address range: CIDR notation, or a single address.
+pub(super) fn parse_network(network: &str) -> Result<IpNet, String> {
+    let network = network.trim();
+    network.parse::<IpNet>()
+        .or_else(|_| network.parse::<IpAddr>().map(IpNet::from))
+        .map_err(|_| format!("Invalid address range '{}': expected CIDR notation (e.g. 10.0.0.0/8) or an IP address", network))
+}
+
+/// Parses address ranges. Invalid ranges are an error when strict, and drop out otherwise.
+fn compile_networks(networks: &[String], strict: bool) -> Result<Vec<IpNet>, String> {
+    let mut compiled = Vec::with_capacity(networks.len());
+    for network in networks {
+        match parse_network(network) {
+            Ok(net) => compiled.push(net),
+            Err(e) if strict => return Err(e),
+            Err(_) => {}
+        }
+    }
+    Ok(compiled)
+}
+
+/// Whether `ip` falls in any of `networks`. IPv4-mapped IPv6 addresses match IPv4 ranges.
+pub fn in_networks(networks: &[IpNet], ip: IpAddr) -> bool {
+    let ip = ip.to_canonical();
+    networks.iter().any(|net| net.contains(&ip))
+}
+
+/// Parses a status class (`4xx`), an inclusive range (`500-599`) or a single status (`404`).
+pub(super) fn parse_status_range(range: &str) -> Result<RangeInclusive<u16>, String> {
+    let invalid = || format!("Invalid status range '{}': expected a class like 4xx or a range like 500-599", range);
+    let status = |s: &str| s.trim().parse::<u16>().ok().filter(|code| (100..=999).contains(code));
+    let range = range.trim();
+    let parsed = match range.to_ascii_lowercase().strip_suffix("xx") {
+        Some(class) => class.parse::<u16>().ok().filter(|c| (1..=9).contains(c)).map(|c| c * 100..=c * 100 + 99),
+        None => match range.split_once('-') {
+            Some((from, to)) => status(from).zip(status(to)).map(|(from, to)| from..=to),
+            None => status(range).map(|code| code..=code),
+        },
+    };
+    parsed.filter(|r| !r.is_empty()).ok_or_else(invalid)
+}
+
+/// Compiles alternative patterns (any one may match). Invalid patterns drop out leniently;
+/// a rule left with none of its patterns can never match.
+fn compile_any(kind: &str, patterns: &[String], strict: bool) -> Result<(Vec<Regex>, bool), String> {
+    let mut compiled = Vec::with_capacity(patterns.len());
+    for pattern in patterns {
+        compiled.extend(compile_pattern(kind, pattern, strict)?);
+    }
+    let unmatchable = !patterns.is_empty() && compiled.is_empty();
+    Ok((compiled, unmatchable))
+}
+
+/// Compiles named patterns that must all match. One invalid pattern makes the rule unmatchable.
+fn compile_all(
+    kind: &str,
+    patterns: &HashMap<String, String>,
+    strict: bool,
+) -> Result<(Vec<(String, Regex)>, bool), String> {
+    let mut compiled = Vec::with_capacity(patterns.len());
+    let mut unmatchable = false;
+    for (name, pattern) in patterns {
+        match compile_pattern(kind, pattern, strict)? {
+            Some(re) => compiled.push((name.clone(), re)),
+            None => unmatchable = true,
+        }
+    }
+    Ok((compiled, unmatchable))
+}
+
+fn body_set(patterns: &[Regex]) -> Option<RegexSet> {
+    if patterns.is_empty() {
+        return None;
+    }
+    RegexSet::new(patterns.iter().map(Regex::as_str)).ok()
+}
+
+fn headers_match(patterns: &[(String, Regex)], headers: &axum::http::HeaderMap) -> bool {
+    patterns.iter().all(|(name, re)| match headers.get(name) {
+        Some(value) => value.to_str().map_or(true, |s| re.is_match(s)),
+        None => false,
+    })
+}
+
+fn body_matches(set: &Option<RegexSet>, patterns: &[Regex], body: &str) -> bool {
+    match set {
+        Some(set) => set.is_match(body),
+        None => patterns.is_empty() || patterns.iter().any(|re| re.is_match(body)),
+    }
+}
+
+/// A compiled [`PartMatch`].
+#[derive(Debug)]
+struct CompiledPart {
+    name: Option<Regex>,
+    filename: Option<Regex>,
+    content_type: Option<Regex>,
+}
+
+/// A compiled [`GraphqlMatch`].
+#[derive(Debug)]
+struct CompiledGraphql {
+    operation_name: Option<Regex>,
+    operation_types: Vec<GraphqlOperationType>,
+}
+
+/// Compiles an optional pattern. The flag is set when a lenient compile dropped it.
+fn compile_optional(kind: &str, pattern: Option<&String>, strict: bool) -> Result<(Option<Regex>, bool), String> {
+    match pattern {
+        Some(pattern) => {
+            let compiled = compile_pattern(kind, pattern, strict)?;
+            let invalid = compiled.is_none();
+            Ok((compiled, invalid))
+        }
+        None => Ok((None, false)),
+    }
+}
+
+fn compile_parts(parts: &[PartMatch], strict: bool) -> Result<(Vec<CompiledPart>, bool), String> {
+    let mut compiled = Vec::with_capacity(parts.len());
+    let mut invalid = false;
+    for part in parts {
+        let (name, bad_name) = compile_optional("multipart name", part.name.as_ref(), strict)?;
+        let (filename, bad_filename) = compile_optional("multipart filename", part.filename.as_ref(), strict)?;
+        let (content_type, bad_type) = compile_optional("multipart content type", part.content_type.as_ref(), strict)?;
+        invalid |= bad_name || bad_filename || bad_type;
+        compiled.push(CompiledPart { name, filename, content_type });
+    }
+    Ok((compiled, invalid))
+}
+
+fn compile_graphql(graphql: Option<&GraphqlMatch>, strict: bool) -> Result<(Option<CompiledGraphql>, bool), String> {
+    let Some(graphql) = graphql else {
+        return Ok((None, false));
+    };
+    let (operation_name, invalid) = compile_optional("GraphQL operation name", graphql.operation_name.as_ref(), strict)?;
+    Ok((Some(CompiledGraphql { operation_name, operation_types: graphql.operation_types.clone() }), invalid))
+}
+
+/// Whether an optional pattern is absent, or present and matched by `value`.
+fn optional_matches(pattern: &Option<Regex>, value: Option<&str>) -> bool {
+    match pattern {
+        Some(re) => value.is_some_and(|v| re.is_match(v)),
+        None => true,
+    }
+}
+
+/// Whether the form, multipart and GraphQL conditions hold for `fields`.
+fn fields_match(form: &[(String, Regex)], parts: &[CompiledPart], graphql: &Option<CompiledGraphql>, fields: &BodyFields) -> bool {
+    let form_matches = form.iter().all(|(name, re)| {
+        fields.form.iter().any(|(field, value)| field == name && re.is_match(value))
+    });
+    let parts_match = parts.iter().all(|condition| {
+        fields.parts.iter().any(|part| {
+            optional_matches(&condition.name, part.name.as_deref())
+                && optional_matches(&condition.filename, part.filename.as_deref())
+                && optional_matches(&condition.content_type, part.content_type.as_deref())
+        })
+    });
+    let graphql_matches = match (graphql, &fields.graphql) {
+        (None, _) => true,
+        (Some(_), None) => false,
+        (Some(condition), Some(operation)) => {
+            optional_matches(&condition.operation_name, operation.operation_name.as_deref())
+                && (condition.operation_types.is_empty()
+                    || operation.operation_type.is_some_and(|t| condition.operation_types.contains(&t)))
+        }
+    };
+    form_matches && parts_match && graphql_matches
+}
+
+/// A compiled [`MatchConditions`].
+#[derive(Debug, Default)]
+pub struct CompiledRule {
+    /// Set when an invalid pattern (lenient compilation only) means the rule can never match.
+    unmatchable: bool,
+    methods: Vec<String>,
+    path: Vec<Regex>,
+    /// Positions of `path` in the owning [`RuleSet`]'s path set.
+    path_ids: Range<usize>,
+    headers: Vec<(String, Regex)>,
+    body: Vec<Regex>,
+    body_set: Option<RegexSet>,
+    claims: Vec<(String, Regex)>,
+    form: Vec<(String, Regex)>,
+    parts: Vec<CompiledPart>,
+    graphql: Option<CompiledGraphql>,
+    client_ip: Vec<IpNet>,
+    all: Vec<CompiledRule>,
+    any: Vec<CompiledRule>,
+    not: Option<Box<CompiledRule>>,
+}
+
+impl CompiledRule {
+    /// Compiles `conditions`, failing on the first invalid pattern.
+    pub fn new(conditions: &MatchConditions) -> Result<Self, String> {
+        Self::compile(conditions, true)
+    }
+
+    /// Compiles `conditions`; invalid patterns never match.
+    pub fn lenient(conditions: &MatchConditions) -> Self {
+        Self::compile(conditions, false).unwrap_or_default()
+    }
+
+    fn compile(conditions: &MatchConditions, strict: bool) -> Result<Self, String> {
+        let (path, no_path) = compile_any("path", &conditions.path.patterns, strict)?;
+        let (headers, bad_header) = compile_all("header", &conditions.headers, strict)?;
+        let (body, no_body) = compile_any("body", &conditions.body.patterns, strict)?;
+        let (claims, bad_claim) = compile_all("claim", &conditions.claims, strict)?;
+        let (form, bad_field) = compile_all("form field", &conditions.form, strict)?;
+        let (parts, bad_part) = compile_parts(&conditions.multipart, strict)?;
+        let (graphql, bad_graphql) = compile_graphql(conditions.graphql.as_ref(), strict)?;
+        let client_ip = compile_networks(&conditions.client_ip, strict)?;
+        let no_client = !conditions.client_ip.is_empty() && client_ip.is_empty();
+        let all = conditions.all.iter().map(|c| Self::compile(c, strict)).collect::<Result<Vec<_>, _>>()?;
+        let any = conditions.any.iter().map(|c| Self::compile(c, strict)).collect::<Result<Vec<_>, _>>()?;
+        let not = conditions.not.as_deref().map(|c| Self::compile(c, strict)).transpose()?.map(Box::new);
+        // An invalid pattern under `not` would otherwise make the negation match everything
+        let bad_not = not.as_ref().is_some_and(|rule| rule.unmatchable);
+        Ok(Self {
+            unmatchable: no_path || bad_header || no_body || bad_claim || bad_field || bad_part || bad_graphql || no_client || bad_not,
+            methods: conditions.methods.clone(),
+            path_ids: 0..path.len(),
+            path,
+            headers,
+            body_set: body_set(&body),
+            body,
+            claims,
+            form,
+            parts,
+            graphql,
+            client_ip,
+            all,
+            any,
+            not,
+        })
+    }
+
+    /// Whether any condition of this rule, nested ones included, is on the caller's claims.
+    pub fn uses_claims(&self) -> bool {
+        !self.claims.is_empty()
+            || self.all.iter().chain(&self.any).any(CompiledRule::uses_claims)
+            || self.not.as_ref().is_some_and(|rule| rule.uses_claims())
+    }
+
+    /// Whether the request satisfies every condition of this rule.
+    pub fn matches(&self, ctx: &RequestContext) -> bool {
+        self.matches_with(ctx, || self.path.iter().any(|re| re.is_match(ctx.path)))
+    }
+
+    /// Like [`matches`](Self::matches), with the path check delegated to `path_matches`
+    /// (only called when the rule has path patterns).
+    fn matches_with(&self, ctx: &RequestContext, path_matches: impl FnOnce() -> bool) -> bool {
+        if self.unmatchable {
+            return false;
+        }
+        if !self.methods.is_empty() && !self.methods.iter().any(|m| m.eq_ignore_ascii_case(ctx.method)) {
+            return false;
+        }
+        if !self.client_ip.is_empty() && !ctx.client_ip.is_some_and(|ip| in_networks(&self.client_ip, ip)) {
+            return false;
+        }
+        if !self.path.is_empty() && !path_matches() {
+            return false;
+        }
+        if !headers_match(&self.headers, ctx.headers) {
+            return false;
+        }
+        if !body_matches(&self.body_set, &self.body, ctx.body) {
+            return false;
+        }
+        let needs_fields = !self.form.is_empty() || !self.parts.is_empty() || self.graphql.is_some();
+        if needs_fields && !fields_match(&self.form, &self.parts, &self.graphql, ctx.fields()) {
+            return false;
+        }
+        if !self.claims.is_empty() {
+            let Some(claims) = ctx.claims else {
+                return false;
+            };
+            let claims_match = self.claims.iter().all(|(claim, re)| {
+                claim_values(claims, claim).iter().any(|v| re.is_match(v))
+            });
+            if !claims_match {
+                return false;
+            }
+        }
+        self.all.iter().all(|rule| rule.matches(ctx))
+            && (self.any.is_empty() || self.any.iter().any(|rule| rule.matches(ctx)))
+            && !self.not.as_ref().is_some_and(|rule| rule.matches(ctx))
+    }
+
+    /// The capture groups of the first path pattern that matches `path`, keyed by group name
+    /// and by index (`"0"` is the whole match).
+    pub fn path_captures(&self, path: &str) -> HashMap<String, String> {
+        let mut result = HashMap::new();
+        let Some((re, caps)) = self.path.iter().find_map(|re| re.captures(path).map(|caps| (re, caps))) else {
+            return result;
+        };
+        for (i, name) in re.capture_names().enumerate() {
+            if let Some(m) = caps.get(i) {
+                result.insert(i.to_string(), m.as_str().to_string());
+                if let Some(name) = name {
+                    result.insert(name.to_string(), m.as_str().to_string());
+                }
+            }
+        }
+        result
+    }
+}
+
+/// The compiled rules of one config section, in config order. Path patterns of all rules
+/// share one [`RegexSet`], so the path is scanned once however many rules are checked.
+#[derive(Debug, Default)]
+pub struct RuleSet {
+    rules: Vec<CompiledRule>,
+    /// `None` if the combined set could not be built; rules then match paths one by one.
+    paths: Option<RegexSet>,
+}
+
+impl RuleSet {
+    fn compile<'a>(conditions: impl Iterator<Item = &'a MatchConditions>, strict: bool) -> Result<Self, String> {
+        let mut rules = Vec::new();
+        let mut patterns = Vec::new();
+        for conditions in conditions {
+            let mut rule = CompiledRule::compile(conditions, strict)?;
+            rule.path_ids = patterns.len()..patterns.len() + rule.path.len();
+            patterns.extend(rule.path.iter().map(|re| re.as_str().to_string()));
+            rules.push(rule);
+        }
+        let paths = match RegexSet::new(&patterns) {
+            Ok(set) => Some(set),
+            Err(e) if strict => return Err(format!("Path patterns could not be combined: {}", e)),
+            Err(_) => None,
+        };
+        Ok(Self { rules, paths })
+    }
+
+    pub fn get(&self, index: usize) -> Option<&CompiledRule> {
+        self.rules.get(index)
+    }
+
+    /// Whether any rule matches on the caller's claims.
+    pub fn uses_claims(&self) -> bool {
+        self.rules.iter().any(CompiledRule::uses_claims)
+    }
+
+    /// Indexes of the rules matching the request, in config order.
+    pub fn matching<'s>(&'s self, ctx: &'s RequestContext<'s>) -> impl Iterator<Item = usize> + 's {
+        let hits: OnceCell<Option<SetMatches>> = OnceCell::new();
+        self.rules.iter().enumerate().filter_map(move |(index, rule)| {
+            let path_matches = || match hits.get_or_init(|| self.paths.as_ref().map(|set| set.matches(ctx.path))) {
+                Some(hits) => rule.path_ids.clone().any(|id| hits.matched(id)),
+                None => rule.path.iter().any(|re| re.is_match(ctx.path)),
+            };
+            rule.matches_with(ctx, path_matches).then_some(index)
+        })
+    }
+
+    /// Index of the first rule matching the request.
+    pub fn first_match(&self, ctx: &RequestContext) -> Option<usize> {
+        self.matching(ctx).next()
+    }
+}
+
+/// A compiled [`ResponseMatchConditions`].
+#[derive(Debug, Default)]
+pub struct CompiledResponseRule {
+    unmatchable: bool,
+    status_codes: Vec<u16>,
+    status_ranges: Vec<RangeInclusive<u16>>,
+    min_latency: Option<Duration>,
+    max_latency: Option<Duration>,
+    min_body_bytes: Option<usize>,
+    max_body_bytes: Option<usize>,
+    request: Option<Box<CompiledRule>>,
+    headers: Vec<(String, Regex)>,
+    body: Vec<Regex>,
+    body_set: Option<RegexSet>,
+    all: Vec<CompiledResponseRule>,
+    any: Vec<CompiledResponseRule>,
+    not: Option<Box<CompiledResponseRule>>,
+}
+
+impl CompiledResponseRule {
+    /// Compiles `conditions`, failing on the first invalid pattern.
+    pub fn new(conditions: &ResponseMatchConditions) -> Result<Self, String> {
+        Self::compile(conditions, true)
+    }
+
+    /// Compiles `conditions`; invalid patterns never match.
+    pub fn lenient(conditions: &ResponseMatchConditions) -> Self {
+        Self::compile(conditions, false).unwrap_or_default()
+    }
+
+    fn compile(conditions: &ResponseMatchConditions, strict: bool) -> Result<Self, String> {
+        let (headers, bad_header) = compile_all("header", &conditions.headers, strict)?;
+        let (body, no_body) = compile_any("body", &conditions.body.patterns, strict)?;
+        let mut status_ranges = Vec::with_capacity(conditions.status_ranges.len());
+        for range in &conditions.status_ranges {
+            match parse_status_range(range) {
+                Ok(range) => status_ranges.push(range),
+                Err(e) if strict => return Err(e),
+                Err(_) => {}
+            }
+        }
+        let no_status = !conditions.status_ranges.is_empty() && status_ranges.is_empty() && conditions.status_codes.is_empty();
+        let request = conditions.request.as_ref().map(|c| CompiledRule::compile(c, strict)).transpose()?.map(Box::new);
+        let bad_request = request.as_ref().is_some_and(|rule| rule.unmatchable);
+        let all = conditions.all.iter().map(|c| Self::compile(c, strict)).collect::<Result<Vec<_>, _>>()?;
+        let any = conditions.any.iter().map(|c| Self::compile(c, strict)).collect::<Result<Vec<_>, _>>()?;
+        let not = conditions.not.as_deref().map(|c| Self::compile(c, strict)).transpose()?.map(Box::new);
+        let bad_not = not.as_ref().is_some_and(|rule| rule.unmatchable);
+        Ok(Self {
+            unmatchable: bad_header || no_body || no_status || bad_request || bad_not,
+            status_codes: conditions.status_codes.clone(),
+            status_ranges,
+            // Durations that do not parse are ignored, as elsewhere
+            min_latency: conditions.min_latency.as_deref().and_then(parse_duration_str),
+            max_latency: conditions.max_latency.as_deref().and_then(parse_duration_str),
+            min_body_bytes: conditions.min_body_bytes,
+            max_body_bytes: conditions.max_body_bytes,
+            request,
+            headers,
+            body_set: body_set(&body),
+            body,
+            all,
+            any,
+            not,
+        })
+    }
+
+    pub fn matches(&self, status_code: u16, headers: &axum::http::HeaderMap, body: &str) -> bool {
+        self.matches_ctx(&ResponseContext::new(status_code, headers, body))
+    }
+
+    /// Whether any request condition of this rule, nested ones included, is on the caller's
+    /// claims.
+    pub fn uses_claims(&self) -> bool {
+        self.request.as_ref().is_some_and(|rule| rule.uses_claims())
+            || self.all.iter().chain(&self.any).any(CompiledResponseRule::uses_claims)
+            || self.not.as_ref().is_some_and(|rule| rule.uses_claims())
+    }
+
+    /// Whether the response satisfies every condition of this rule.
+    pub fn matches_ctx(&self, ctx: &ResponseContext) -> bool {
+        let status_matches = (self.status_codes.is_empty() && self.status_ranges.is_empty())
+            || self.status_codes.contains(&ctx.status_code)
+            || self.status_ranges.iter().any(|range| range.contains(&ctx.status_code));
+        let latency_matches = match (self.min_latency, self.max_latency) {
+            (None, None) => true,
+            (min, max) => ctx.latency.is_some_and(|latency| {
+                min.map_or(true, |min| latency >= min) && max.map_or(true, |max| latency <= max)
+            }),
+        };
+        !self.unmatchable
+            && status_matches
+            && latency_matches
+            && self.min_body_bytes.map_or(true, |min| ctx.body_size >= min)
+            && self.max_body_bytes.map_or(true, |max| ctx.body_size <= max)
+            && headers_match(&self.headers, ctx.headers)
+            && body_matches(&self.body_set, &self.body, ctx.body)
+            && self.request.as_ref().map_or(true, |rule| ctx.request.is_some_and(|request| rule.matches(request)))
+            && self.all.iter().all(|rule| rule.matches_ctx(ctx))
+            && (self.any.is_empty() || self.any.iter().any(|rule| rule.matches_ctx(ctx)))
+            && !self.not.as_ref().is_some_and(|rule| rule.matches_ctx(ctx))
+    }
+}
+
+/// A compiled rewrite rule `pattern`.
+#[derive(Debug, Default)]
+pub struct CompiledRewrite {
+    /// `None` matches any target.
+    pattern: Option<Regex>,
+    /// Set when the pattern is invalid (lenient compilation only); the rule never applies.
+    unmatchable: bool,
+}
+
+impl CompiledRewrite {
+    fn compile(rule: &RewriteRule, strict: bool) -> Result<Self, String> {
+        let Some(pattern) = &rule.pattern else {
+            return Ok(Self::default());
+        };
+        let pattern = compile_pattern("rewrite", pattern, strict)?;
+        Ok(Self { unmatchable: pattern.is_none(), pattern })
+    }
+
+    /// `url` rewritten by `rule`, or `None` if the rule's pattern does not match it.
+    pub fn rewrite(&self, rule: &RewriteRule, url: &str) -> Option<String> {
+        if self.unmatchable {
+            return None;
+        }
+        let mut parsed = url.parse::<reqwest::Url>().ok()?;
+        let target = match rule.target {
+            RewriteTarget::Url => url.to_string(),
+            RewriteTarget::Path => parsed.path().to_string(),
+        };
+        let replaced = match &self.pattern {
+            Some(re) if !re.is_match(&target) => return None,
+            Some(re) => re.replace(&target, rule.replacement.as_deref().unwrap_or("$0")).into_owned(),
+            None => target,
+        };
+        match rule.target {
+            RewriteTarget::Url => parsed = replaced.parse().ok()?,
+            RewriteTarget::Path => parsed.set_path(&replaced),
+        }
+        if let Some(host) = &rule.host {
+            let authority = format!("http://{}", host).parse::<reqwest::Url>().ok()?;
+            parsed.set_host(authority.host_str()).ok()?;
+            parsed.set_port(authority.port()).ok()?;
+        }
+        Some(parsed.to_string())
+    }
+}
+
+/// A logging rule's [`Sampling`], with the count of matches `sample_every` needs.
+#[derive(Debug, Default)]
+pub struct Sampler {
+    rate: Option<f64>,
+    every: Option<u64>,
+    key: Option<SampleKey>,
+    always_log_errors: bool,
+    seen: AtomicU64,
+}
+
+impl Sampler {
+    fn compile(sampling: &Sampling) -> Self {
+        Self {
+            // Out-of-range settings are validation errors; leniently they are clamped
+            rate: sampling.sample_rate.map(|rate| if rate.is_nan() { 1.0 } else { rate.clamp(0.0, 1.0) }),
+            every: sampling.sample_every.map(|every| every.max(1)),
+            key: sampling.sample_key.clone(),
+            always_log_errors: sampling.always_log_errors,
+            seen: AtomicU64::new(0),
+        }
+    }
+
+    /// Whether a match of the rule is logged. Keyed sampling hashes the request's
+    /// `request_id` or header, so the decision for a key is the same on every call.
+    pub fn sample(&self, request_id: &str, headers: &axum::http::HeaderMap) -> bool {
+        let key = match &self.key {
+            Some(SampleKey::RequestId) => Some(request_id),
+            Some(SampleKey::Header(name)) => headers.get(name).and_then(|v| v.to_str().ok()),
+            None => None,
+        };
+        let hash = key.map(fnv1a);
+        match (self.every, self.rate) {
+            (Some(every), _) => match hash {
+                Some(hash) => hash % every == 0,
+                None => self.seen.fetch_add(1, Ordering::Relaxed) % every == 0,
+            },
+            (None, Some(rate)) => {
+                // The top 53 bits of the hash as a fraction in [0, 1)
+                let roll = hash.map_or_else(rand::random::<f64>, |hash| (hash >> 11) as f64 / (1u64 << 53) as f64);
+                roll < rate
+            }
+            (None, None) => true,
+        }
+    }
+
+    /// Whether a response matching the rule is logged: responses of 500 or above always are
+    /// under `always_log_errors`. Header keys are read from the request's headers.
+    pub fn sample_response(&self, request_id: &str, ctx: &ResponseContext) -> bool {
+        if self.always_log_errors && ctx.status_code >= 500 {
+            return true;
+        }
+        let no_headers = axum::http::HeaderMap::new();
+        let headers = ctx.request.map_or(&no_headers, |request| request.headers);
+        self.sample(request_id, headers)
+    }
+}
+
+/// 64-bit FNV-1a: stable across processes and releases, unlike the std hasher.
+fn fnv1a(key: &str) -> u64 {
+    key.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3))
+}
+
+/// The compiled `replace` patterns of a body rule, each with its replacement text.
+#[derive(Debug, Default)]
+pub struct CompiledBodyRule {
+    pub request: Vec<(Regex, String)>,
+    pub response: Vec<(Regex, String)>,
+}
+
+impl CompiledBodyRule {
+    fn compile(rule: &BodyRule, strict: bool) -> Result<Self, String> {
+        // Leniently, an invalid pattern drops out and the rest still apply
+        let compile = |transform: &BodyTransform| -> Result<Vec<(Regex, String)>, String> {
+            let mut compiled = Vec::with_capacity(transform.replace.len());
+            for replace in &transform.replace {
+                if let Some(re) = compile_pattern("body replace", &replace.pattern, strict)? {
+                    compiled.push((re, replace.replacement.clone()));
+                }
+            }
+            Ok(compiled)
+        };
+        Ok(Self { request: compile(&rule.request)?, response: compile(&rule.response)? })
+    }
+}
+
+/// Every rule of a [`Config`], compiled.
+#[derive(Debug, Default)]
+pub struct CompiledRules {
+    pub logging: RuleSet,
+    pub drop: RuleSet,
+    pub mock: RuleSet,
+    pub faults: RuleSet,
+    pub rate_limits: RuleSet,
+    pub headers: RuleSet,
+    pub rewrites: RuleSet,
+    /// The `pattern` of each rewrite rule, in config order.
+    pub rewrite_patterns: Vec<CompiledRewrite>,
+    pub bodies: RuleSet,
+    /// The `replace` patterns of each body rule, in config order.
+    pub body_replacements: Vec<CompiledBodyRule>,
+    pub auth: CompiledRule,
+    pub response_logging: Vec<CompiledResponseRule>,
+    /// The sampling of each logging rule, in config order.
+    pub logging_samplers: Vec<Sampler>,
+    /// The sampling of each response logging rule, in config order.
+    pub response_logging_samplers: Vec<Sampler>,
+    /// `server.trusted_proxies`, parsed.
+    pub trusted_proxies: Vec<IpNet>,
+    /// Whether any rule matches on or captures the caller's claims.
+    pub uses_claims: bool,
+}
+
+impl CompiledRules {
+    /// Compiles every rule in `config`, failing on the first invalid pattern.
+    pub fn compile(config: &Config) -> Result<Self, String> {
+        Self::compile_with(config, true)
+    }
+
+    /// Compiles every rule in `config`. Invalid patterns never match instead of failing.
+    pub fn compile_lenient(config: &Config) -> Self {
+        Self::compile_with(config, false).unwrap_or_default()
+    }
+
+    fn compile_with(config: &Config, strict: bool) -> Result<Self, String> {
+        let mut rules = Self {
+            logging: RuleSet::compile(config.logging.rules.iter().map(|r| &r.match_conditions), strict)?,
+            drop: RuleSet::compile(config.drop.rules.iter().map(|r| &r.match_conditions), strict)?,
+            mock: RuleSet::compile(config.mock.rules.iter().map(|r| &r.match_conditions), strict)?,
+            faults: RuleSet::compile(config.faults.rules.iter().map(|r| &r.match_conditions), strict)?,
+            rate_limits: RuleSet::compile(config.rate_limits.rules.iter().map(|r| &r.match_conditions), strict)?,
+            headers: RuleSet::compile(config.headers.rules.iter().map(|r| &r.match_conditions), strict)?,
+            rewrites: RuleSet::compile(config.rewrites.rules.iter().map(|r| &r.match_conditions), strict)?,
+            rewrite_patterns: config.rewrites.rules.iter()
+                .map(|r| CompiledRewrite::compile(r, strict))
+                .collect::<Result<_, _>>()?,
+            bodies: RuleSet::compile(config.bodies.rules.iter().map(|r| &r.match_conditions), strict)?,
+            body_replacements: config.bodies.rules.iter()
+                .map(|r| CompiledBodyRule::compile(r, strict))
+                .collect::<Result<_, _>>()?,
+            auth: CompiledRule::compile(&config.auth.match_conditions, strict)?,
+            response_logging: config.response_logging.rules.iter()
+                .map(|r| CompiledResponseRule::compile(&r.match_conditions, strict))
+                .collect::<Result<_, _>>()?,
+            logging_samplers: config.logging.rules.iter().map(|r| Sampler::compile(&r.sampling)).collect(),
+            response_logging_samplers: config.response_logging.rules.iter().map(|r| Sampler::compile(&r.sampling)).collect(),
+            trusted_proxies: compile_networks(&config.server.trusted_proxies, strict)?,
+            uses_claims: false,
+        };
+        rules.uses_claims = [&rules.logging, &rules.drop, &rules.mock, &rules.faults, &rules.rate_limits, &rules.headers, &rules.rewrites, &rules.bodies]
+            .iter()
+            .any(|set| set.uses_claims())
+            || rules.response_logging.iter().any(CompiledResponseRule::uses_claims)
+            || config.logging.rules.iter().any(|r| !r.capture.claims.is_empty())
+            || config.response_logging.rules.iter().any(|r| !r.capture.claims.is_empty());
+        Ok(rules)
+    }
+}
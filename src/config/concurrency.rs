@@ -0,0 +1,41 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::request::parse_duration_str;
+
+/// Protects the proxy process from overload. Requests beyond the limits wait in a bounded
+/// queue for up to `max_wait`, then are shed with 503. All limits are off by default.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default, Clone, PartialEq)]
+pub struct ConcurrencyConfig {
+    /// Maximum requests handled at once across the whole proxy.
+    #[serde(default)]
+    pub max_in_flight: Option<usize>,
+    /// Maximum concurrent requests to any single upstream host, after rewrite rules apply.
+    #[serde(default)]
+    pub per_upstream_host: Option<usize>,
+    /// Per-host caps that override `per_upstream_host`, keyed by hostname.
+    #[serde(default)]
+    pub upstream_hosts: HashMap<String, usize>,
+    /// Requests allowed to wait for a free slot. Default: 0 (shed immediately).
+    #[serde(default)]
+    pub max_queue: usize,
+    /// Longest a queued request waits before being shed (e.g. `"2s"`). Default: 1s.
+    #[serde(default)]
+    pub max_wait: Option<String>,
+}
+
+impl ConcurrencyConfig {
+    /// Parses `max_wait`, falling back to one second.
+    pub fn parse_max_wait(&self) -> std::time::Duration {
+        self.max_wait
+            .as_deref()
+            .and_then(parse_duration_str)
+            .unwrap_or(std::time::Duration::from_secs(1))
+    }
+
+    /// The concurrency cap for `host`, if any.
+    pub fn host_limit(&self, host: &str) -> Option<usize> {
+        self.upstream_hosts.get(host).copied().or(self.per_upstream_host)
+    }
+}
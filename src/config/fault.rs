@@ -0,0 +1,92 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::request::{parse_duration_str, MatchConditions};
+
+/// Controls fault injection for chaos testing. Faults apply to proxied requests only
+/// (not to drop, mock or replay responses). First matching rule wins.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
+pub struct FaultConfig {
+    /// Fault rules, evaluated in order.
+    #[serde(default)]
+    pub rules: Vec<FaultRule>,
+}
+
+/// A rule that injects one or more faults into a percentage of matching requests.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct FaultRule {
+    /// Rule name, reported in logs and metrics. Rules from included files are merged by name.
+    pub name: String,
+    /// The file this rule was loaded from, when the config was assembled from files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    pub match_conditions: MatchConditions,
+    /// Percentage (0-100) of matching requests that get the faults. Default: 100.
+    #[serde(default = "default_percentage")]
+    pub percentage: f64,
+    /// Delay added before the request is sent upstream.
+    #[serde(default)]
+    pub latency: Option<LatencyFault>,
+    /// Close the client connection without a complete response. The upstream is not contacted.
+    #[serde(default)]
+    pub abort: bool,
+    /// Respond with this error instead of contacting the upstream.
+    #[serde(default)]
+    pub error: Option<ErrorFault>,
+    /// Cut the connection after sending this many bytes of the upstream response body.
+    #[serde(default)]
+    pub truncate_body_bytes: Option<usize>,
+    /// Throttle the upstream response body to this many bytes per second.
+    #[serde(default)]
+    pub bandwidth_bytes_per_sec: Option<u64>,
+}
+
+fn default_percentage() -> f64 {
+    100.0
+}
+
+/// Added latency: `fixed`, or uniformly distributed between `min` and `max`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct LatencyFault {
+    /// Fixed delay (e.g. `"500ms"`). Takes priority over `min`/`max`.
+    #[serde(default)]
+    pub fixed: Option<String>,
+    /// Lower bound of a uniformly distributed delay. Default: 0.
+    #[serde(default)]
+    pub min: Option<String>,
+    /// Upper bound of a uniformly distributed delay.
+    #[serde(default)]
+    pub max: Option<String>,
+}
+
+impl LatencyFault {
+    /// Picks a delay for one request. Returns `None` if no bound parses.
+    pub fn sample(&self) -> Option<std::time::Duration> {
+        if let Some(fixed) = self.fixed.as_deref().and_then(parse_duration_str) {
+            return Some(fixed);
+        }
+        let max = self.max.as_deref().and_then(parse_duration_str)?;
+        let min = self.min.as_deref().and_then(parse_duration_str).unwrap_or_default();
+        if max <= min {
+            return Some(min);
+        }
+        Some(min + (max - min).mul_f64(rand::random::<f64>()))
+    }
+}
+
+/// An upstream-like error response.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct ErrorFault {
+    /// HTTP status code.
+    pub status_code: u16,
+    /// Response body.
+    #[serde(default)]
+    pub body: Option<String>,
+}
+
+impl FaultRule {
+    /// Rolls against `percentage` to decide whether this request gets the faults.
+    pub fn should_inject(&self) -> bool {
+        self.percentage >= 100.0 || rand::random::<f64>() * 100.0 < self.percentage
+    }
+}
@@ -0,0 +1,187 @@
+//! Structured fields parsed from request bodies for matching and logging: form fields,
+//! multipart part headers and the GraphQL operation.
+
+use axum::http::{header, HeaderMap};
+use serde::Serialize;
+
+use super::request::GraphqlOperationType;
+
+/// The structured fields of one request body. Empty for bodies of other content types.
+#[derive(Debug, Clone, Default)]
+pub struct BodyFields {
+    /// Fields of a `application/x-www-form-urlencoded` body, or the parts of a
+    /// `multipart/form-data` body that are not files, in body order.
+    pub form: Vec<(String, String)>,
+    /// Every part of a `multipart/form-data` body.
+    pub parts: Vec<MultipartPart>,
+    /// The operation of a GraphQL request.
+    pub graphql: Option<GraphqlOperation>,
+}
+
+/// The headers of one multipart part. Its content is never kept.
+#[derive(Debug, Clone, Serialize)]
+pub struct MultipartPart {
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filename: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+}
+
+/// The operation a GraphQL request executes.
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphqlOperation {
+    pub operation_name: Option<String>,
+    /// `None` when the request carries no query document (e.g. a persisted query).
+    pub operation_type: Option<GraphqlOperationType>,
+}
+
+impl BodyFields {
+    /// Parses `body` according to the request's `Content-Type`.
+    pub fn parse(headers: &HeaderMap, body: &str) -> Self {
+        let content_type = headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or_default();
+        let media_type = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+        let mut fields = Self::default();
+        match media_type.as_str() {
+            "application/x-www-form-urlencoded" => {
+                fields.form = form_urlencoded::parse(body.as_bytes()).into_owned().collect();
+            }
+            "multipart/form-data" => {
+                if let Some(boundary) = parameter(content_type, "boundary") {
+                    (fields.form, fields.parts) = multipart(body, &boundary);
+                }
+            }
+            "application/graphql" => fields.graphql = Some(graphql(Some(body), None)),
+            media_type if media_type == "application/json" || media_type.ends_with("+json") => {
+                fields.graphql = graphql_request(body);
+            }
+            _ => {}
+        }
+        fields
+    }
+}
+
+/// The value of parameter `name` in a header value like `form-data; name="file"`.
+fn parameter(value: &str, name: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim().trim_matches('"').to_string())
+    })
+}
+
+/// The non-file fields and the part headers of a multipart body.
+fn multipart(body: &str, boundary: &str) -> (Vec<(String, String)>, Vec<MultipartPart>) {
+    let mut form = Vec::new();
+    let mut parts = Vec::new();
+    let delimiter = format!("--{}", boundary);
+    // The first section is the preamble; a section starting with `--` follows the last part
+    for section in body.split(delimiter.as_str()).skip(1) {
+        if section.starts_with("--") {
+            break;
+        }
+        let section = section.strip_prefix("\r\n").unwrap_or(section);
+        let Some((head, content)) = section.split_once("\r\n\r\n") else {
+            continue;
+        };
+        let mut part = MultipartPart { name: None, filename: None, content_type: None };
+        for line in head.split("\r\n") {
+            let Some((name, value)) = line.split_once(':') else {
+                continue;
+            };
+            if name.trim().eq_ignore_ascii_case("content-disposition") {
+                part.name = parameter(value, "name");
+                part.filename = parameter(value, "filename");
+            } else if name.trim().eq_ignore_ascii_case("content-type") {
+                part.content_type = Some(value.trim().to_string());
+            }
+        }
+        if let (Some(name), None) = (&part.name, &part.filename) {
+            form.push((name.clone(), content.strip_suffix("\r\n").unwrap_or(content).to_string()));
+        }
+        parts.push(part);
+    }
+    (form, parts)
+}
+
+/// The operation of a GraphQL-over-HTTP JSON body (`{"query": ..., "operationName": ...}`).
+fn graphql_request(body: &str) -> Option<GraphqlOperation> {
+    let request: serde_json::Value = serde_json::from_str(body).ok()?;
+    let query = request.get("query").and_then(|q| q.as_str());
+    let operation_name = request.get("operationName").and_then(|n| n.as_str());
+    (query.is_some() || operation_name.is_some()).then(|| graphql(query, operation_name))
+}
+
+/// The operation `query` executes: the one named `operation_name`, or else the first.
+fn graphql(query: Option<&str>, operation_name: Option<&str>) -> GraphqlOperation {
+    let operations = query.map(operations).unwrap_or_default();
+    let executed = match operation_name {
+        Some(name) => operations.iter().find(|(_, n)| n.as_deref() == Some(name)),
+        None => operations.first(),
+    };
+    GraphqlOperation {
+        operation_name: operation_name.map(str::to_string).or_else(|| executed.and_then(|(_, n)| n.clone())),
+        operation_type: executed.map(|(t, _)| *t),
+    }
+}
+
+/// The operations defined in a GraphQL document, with their names. Fragments are skipped.
+fn operations(document: &str) -> Vec<(GraphqlOperationType, Option<String>)> {
+    let mut operations = Vec::new();
+    let (mut braces, mut parens) = (0usize, 0usize);
+    // `Some(Some(type))` after an operation keyword, `Some(None)` after `fragment`
+    let mut pending: Option<Option<GraphqlOperationType>> = None;
+    let mut name: Option<String> = None;
+    let mut skip_word = false;
+    let mut chars = document.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            '#' => {
+                while chars.next_if(|&(_, c)| c != '\n').is_some() {}
+            }
+            '"' => {
+                let mut escaped = false;
+                for (_, c) in chars.by_ref() {
+                    match c {
+                        '\\' if !escaped => escaped = true,
+                        '"' if !escaped => break,
+                        _ => escaped = false,
+                    }
+                }
+            }
+            '(' | '[' => parens += 1,
+            ')' | ']' => parens = parens.saturating_sub(1),
+            '{' => {
+                if braces == 0 && parens == 0 {
+                    match pending.take() {
+                        Some(Some(kind)) => operations.push((kind, name.take())),
+                        Some(None) => {}
+                        None => operations.push((GraphqlOperationType::Query, None)),
+                    }
+                }
+                braces += 1;
+            }
+            '}' => braces = braces.saturating_sub(1),
+            '@' => skip_word = true,
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                let mut end = start + c.len_utf8();
+                while let Some((i, c)) = chars.next_if(|&(_, c)| c.is_ascii_alphanumeric() || c == '_') {
+                    end = i + c.len_utf8();
+                }
+                if braces > 0 || parens > 0 || std::mem::take(&mut skip_word) {
+                    continue;
+                }
+                let word = &document[start..end];
+                match (&pending, word) {
+                    (None, "query") => pending = Some(Some(GraphqlOperationType::Query)),
+                    (None, "mutation") => pending = Some(Some(GraphqlOperationType::Mutation)),
+                    (None, "subscription") => pending = Some(Some(GraphqlOperationType::Subscription)),
+                    (None, "fragment") => pending = Some(None),
+                    (Some(Some(_)), _) if name.is_none() => name = Some(word.to_string()),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    operations
+}
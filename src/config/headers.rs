@@ -0,0 +1,63 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::request::MatchConditions;
+
+/// Controls header transformation. Unlike most rule sections, every matching rule applies, in
+/// config order, so later rules see the changes made by earlier ones.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
+pub struct HeaderConfig {
+    /// Header rules. Every matching rule applies.
+    #[serde(default)]
+    pub rules: Vec<HeaderRule>,
+}
+
+/// Header changes for requests matching `match_conditions`: `request` on the way upstream,
+/// `response` on the way back to the client.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct HeaderRule {
+    /// Rule name, reported in logs and metrics. Rules from included files are merged by name.
+    pub name: String,
+    /// The file this rule was loaded from, when the config was assembled from files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub match_conditions: MatchConditions,
+    /// Changes to the request forwarded upstream.
+    #[serde(default)]
+    pub request: HeaderTransform,
+    /// Changes to the response returned to the client, including drop, mock and replayed
+    /// responses.
+    #[serde(default)]
+    pub response: HeaderTransform,
+}
+
+/// Header changes, applied in the order `remove`, `rename`, `set`, `add`.
+///
+/// Values are templates: `{{client_ip}}`, `{{request_id}}`, `{{method}}`, `{{path}}`,
+/// `{{path.N}}` / `{{path.NAME}}` (path pattern captures), `{{header.NAME}}` (request headers,
+/// as received) and `{{body.a.b}}` (JSON request body fields) are replaced with values from the
+/// request.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct HeaderTransform {
+    /// Headers to remove.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub remove: Vec<String>,
+    /// Headers to rename, old name to new name. Values move to the new name, after any it
+    /// already has.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub rename: HashMap<String, String>,
+    /// Headers to set, replacing any existing values.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub set: HashMap<String, String>,
+    /// Headers to add, keeping any existing values.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub add: HashMap<String, String>,
+}
+
+impl HeaderTransform {
+    pub fn is_empty(&self) -> bool {
+        self.remove.is_empty() && self.rename.is_empty() && self.set.is_empty() && self.add.is_empty()
+    }
+}
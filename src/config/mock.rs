@@ -0,0 +1,222 @@
+use base64::Engine;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::request::{parse_duration_str, MatchConditions};
+
+/// Controls mock/stub responses. Matching `rules` are answered locally without contacting
+/// the upstream; `replay` serves responses from a recorded HAR or NDJSON file.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
+pub struct MockConfig {
+    /// Mock rules, evaluated after drop rules. First matching rule wins.
+    #[serde(default)]
+    pub rules: Vec<MockRule>,
+    /// Serve responses from a recording, keyed by method and upstream URL.
+    #[serde(default)]
+    pub replay: Option<ReplayConfig>,
+    /// Recorded responses loaded from `replay.file`, keyed by `"METHOD URL"`.
+    #[serde(skip)]
+    recordings: HashMap<String, RecordedResponse>,
+}
+
+/// A rule that answers matching requests with a templated response.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct MockRule {
+    /// Rule name, reported in logs and metrics. Rules from included files are merged by name.
+    pub name: String,
+    /// The file this rule was loaded from, when the config was assembled from files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    pub match_conditions: MatchConditions,
+    pub response: MockResponse,
+}
+
+/// The HTTP response returned when a mock rule matches.
+///
+/// `body` and header values are templates: `{{method}}`, `{{path}}`, `{{path.N}}` /
+/// `{{path.NAME}}` (path pattern captures), `{{header.NAME}}` and `{{body.a.b}}` (JSON body
+/// fields) are replaced with values from the request.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct MockResponse {
+    /// HTTP status code. Default: 200.
+    #[serde(default = "default_mock_status")]
+    pub status_code: u16,
+    /// Response headers; values are templates.
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// Inline response body template.
+    #[serde(default)]
+    pub body: Option<String>,
+    /// Read the response body template from this file on each request. Ignored if `body` is set.
+    #[serde(default)]
+    pub body_file: Option<String>,
+    /// Artificial delay before responding (e.g. `"250ms"`, `"2s"`).
+    #[serde(default)]
+    pub delay: Option<String>,
+}
+
+fn default_mock_status() -> u16 {
+    200
+}
+
+impl MockResponse {
+    /// Parses the `delay` string into a [`Duration`](std::time::Duration).
+    /// Returns `None` if `delay` is absent or has an unrecognised format.
+    pub fn parse_delay(&self) -> Option<std::time::Duration> {
+        self.delay.as_deref().and_then(parse_duration_str)
+    }
+}
+
+/// Serves recorded responses instead of proxying.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct ReplayConfig {
+    /// Recording to serve: a HAR file (`.har`) or one JSON object per line (`.ndjson`/`.jsonl`).
+    pub file: String,
+    /// Proxy to the upstream when no recording matches. Default: false (return 404).
+    #[serde(default)]
+    pub fallthrough: bool,
+}
+
+/// A single recorded upstream response.
+#[derive(Debug, Clone)]
+pub struct RecordedResponse {
+    pub status_code: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+/// Headers that describe the original wire encoding. Recordings store decoded bodies,
+/// so replaying these would misdescribe the body we send.
+const SKIPPED_RECORDED_HEADERS: &[&str] = &["content-length", "content-encoding", "transfer-encoding"];
+
+/// One line of an NDJSON recording.
+#[derive(Deserialize)]
+struct NdjsonRecord {
+    method: String,
+    url: String,
+    #[serde(default = "default_mock_status")]
+    status: u16,
+    #[serde(default)]
+    headers: HashMap<String, String>,
+    #[serde(default)]
+    body: Option<String>,
+    #[serde(default)]
+    body_base64: Option<String>,
+}
+
+impl MockConfig {
+    /// Loads `replay.file` into memory. A no-op when replay is not configured.
+    pub fn load_recordings(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(replay) = &self.replay else {
+            return Ok(());
+        };
+        let content = std::fs::read_to_string(&replay.file)
+            .map_err(|e| format!("Failed to read recording '{}': {}", replay.file, e))?;
+        let lower = replay.file.to_ascii_lowercase();
+        let entries = if lower.ends_with(".har") {
+            parse_har(&content)
+        } else if lower.ends_with(".ndjson") || lower.ends_with(".jsonl") {
+            parse_ndjson(&content)
+        } else {
+            Err("unsupported format (expected .har, .ndjson or .jsonl)".into())
+        }
+        .map_err(|e| format!("Invalid recording '{}': {}", replay.file, e))?;
+
+        self.recordings.clear();
+        for (method, url, response) in entries {
+            // First recording wins, matching rule evaluation order elsewhere.
+            self.recordings.entry(recording_key(&method, &url)).or_insert(response);
+        }
+        Ok(())
+    }
+
+    /// Returns the recorded response for `method` and `url`, if any.
+    pub fn recorded_response(&self, method: &str, url: &str) -> Option<&RecordedResponse> {
+        self.recordings.get(&recording_key(method, url))
+    }
+}
+
+fn recording_key(method: &str, url: &str) -> String {
+    format!("{} {}", method.to_ascii_uppercase(), url)
+}
+
+fn keep_recorded_header(name: &str) -> bool {
+    !SKIPPED_RECORDED_HEADERS.iter().any(|h| h.eq_ignore_ascii_case(name))
+}
+
+type RecordingEntry = (String, String, RecordedResponse);
+
+fn parse_har(content: &str) -> Result<Vec<RecordingEntry>, Box<dyn std::error::Error>> {
+    let har: serde_json::Value = serde_json::from_str(content)?;
+    let entries = har["log"]["entries"]
+        .as_array()
+        .ok_or("missing log.entries array")?;
+
+    let mut result = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        let method = entry["request"]["method"]
+            .as_str()
+            .ok_or_else(|| format!("entry {}: missing request.method", i))?;
+        let url = entry["request"]["url"]
+            .as_str()
+            .ok_or_else(|| format!("entry {}: missing request.url", i))?;
+        let response = &entry["response"];
+        let status_code = response["status"]
+            .as_u64()
+            .and_then(|s| u16::try_from(s).ok())
+            .ok_or_else(|| format!("entry {}: missing response.status", i))?;
+
+        let headers = response["headers"]
+            .as_array()
+            .map(|headers| {
+                headers
+                    .iter()
+                    .filter_map(|h| Some((h["name"].as_str()?.to_string(), h["value"].as_str()?.to_string())))
+                    .filter(|(name, _)| keep_recorded_header(name))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let text = response["content"]["text"].as_str().unwrap_or_default();
+        let body = if response["content"]["encoding"].as_str() == Some("base64") {
+            base64::engine::general_purpose::STANDARD
+                .decode(text)
+                .map_err(|e| format!("entry {}: invalid base64 body: {}", i, e))?
+        } else {
+            text.as_bytes().to_vec()
+        };
+
+        result.push((method.to_string(), url.to_string(), RecordedResponse { status_code, headers, body }));
+    }
+    Ok(result)
+}
+
+fn parse_ndjson(content: &str) -> Result<Vec<RecordingEntry>, Box<dyn std::error::Error>> {
+    let mut result = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: NdjsonRecord =
+            serde_json::from_str(line).map_err(|e| format!("line {}: {}", i + 1, e))?;
+        let body = match (record.body_base64, record.body) {
+            (Some(encoded), _) => base64::engine::general_purpose::STANDARD
+                .decode(encoded)
+                .map_err(|e| format!("line {}: invalid body_base64: {}", i + 1, e))?,
+            (None, Some(body)) => body.into_bytes(),
+            (None, None) => Vec::new(),
+        };
+        let headers = record
+            .headers
+            .into_iter()
+            .filter(|(name, _)| keep_recorded_header(name))
+            .collect();
+        result.push((
+            record.method,
+            record.url,
+            RecordedResponse { status_code: record.status, headers, body },
+        ));
+    }
+    Ok(result)
+}
@@ -1,58 +1,91 @@
-use parking_lot::{RwLock, RwLockReadGuard};
-use std::sync::LazyLock;
+use arc_swap::ArcSwap;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::Arc;
-
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, OnceLock};
+
+use crate::auth::{AuthError, Identity};
+use crate::concurrency::ConcurrencyLimiter;
+use crate::history::{ConfigHistory, ConfigVersion, RollbackError};
+use crate::metrics::Metrics;
+use crate::rate_limiter::RateLimiter;
+
+pub mod admin;
+pub mod auth;
+pub mod body;
+pub mod compiled;
+pub mod concurrency;
+pub mod fault;
+pub mod fields;
+pub mod headers;
+pub mod mock;
+pub mod rate_limit;
+pub mod reload;
 pub mod request;
 pub mod response;
-
+pub mod rewrite;
+pub mod sampling;
+pub mod schema;
+pub mod sources;
+pub mod substitute;
+pub mod validate;
+
+pub use admin::*;
+pub use auth::*;
+pub use body::*;
+pub use compiled::*;
+pub use concurrency::*;
+pub use fault::*;
+pub use fields::*;
+pub use headers::*;
+pub use mock::*;
+pub use rate_limit::*;
+pub use reload::*;
 pub use request::*;
 pub use response::*;
-
-// ---------------------------------------------------------------------------
-// Global regex cache — compiled once, reused across all requests and threads.
-// parking_lot::RwLock: many concurrent readers, rare writes (only on first
-// encounter of a new pattern after a config reload).
-// ---------------------------------------------------------------------------
-static REGEX_CACHE: LazyLock<RwLock<HashMap<String, Arc<regex::Regex>>>> =
-    LazyLock::new(|| RwLock::new(HashMap::new()));
-
-/// Returns a cached compiled regex for `pattern`, compiling and caching it on
-/// first call. Returns `None` for invalid patterns (same behaviour as before:
-/// silently non-matching rather than panicking).
-fn get_cached_regex(pattern: &str) -> Option<Arc<regex::Regex>> {
-    // Fast path: pattern already compiled.
-    {
-        let cache = REGEX_CACHE.read();
-        if let Some(re) = cache.get(pattern) {
-            return Some(Arc::clone(re));
-        }
-    }
-    // Slow path: compile and insert.
-    let re = Arc::new(regex::Regex::new(pattern).ok()?);
-    REGEX_CACHE.write().insert(pattern.to_string(), Arc::clone(&re));
-    Some(re)
-}
+pub use rewrite::*;
+pub use sampling::*;
+pub use sources::{ConfigSources, Format, SourceFile};
+pub use validate::*;
 
 // ---------------------------------------------------------------------------
 // Config structs
 // ---------------------------------------------------------------------------
 
-#[derive(Debug, Serialize, Deserialize, Default)]
+/// Proxy listener settings. Read at startup, except `trusted_proxies` and `forwarded_headers`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ServerConfig {
+    /// Port the proxy listens on. Overridden by the `PORT` environment variable.
     #[serde(default = "default_port")]
     pub port: u16,
+    /// Serve the proxy listener over TLS, optionally requiring client certificates.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tls: Option<TlsConfig>,
+    /// Addresses or CIDR ranges of proxies in front of LogProx (e.g. `10.0.0.0/8`). Requests
+    /// from them are attributed to the client named in their `Forwarded` or `X-Forwarded-For`
+    /// header; those headers are ignored on requests from anyone else.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub trusted_proxies: Vec<String>,
+    /// Add `X-Forwarded-For`, `X-Forwarded-Proto`, `X-Forwarded-Host` and `Forwarded` to
+    /// upstream requests. Default: true.
+    #[serde(default = "default_true")]
+    pub forwarded_headers: bool,
 }
 
 fn default_port() -> u16 {
     3000
 }
 
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self { port: default_port(), tls: None, trusted_proxies: Vec::new(), forwarded_headers: true }
+    }
+}
+
 /// Controls which upstream targets the proxy is allowed to reach.
 /// Default: http/https only, private/loopback IPs blocked (secure default).
 /// Set `allow_private_networks: true` when proxying to internal services.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct UpstreamConfig {
     /// Permit requests to private/loopback/link-local IP ranges.
     /// Default: false. Enable when proxying to internal services.
@@ -84,11 +117,46 @@ impl Default for UpstreamConfig {
     }
 }
 
+/// Decoding of compressed (`Content-Encoding`) bodies for rule matching and log capture.
+/// Bodies are always forwarded as received; only the copy that rules and logs see is decoded.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+pub struct DecompressionConfig {
+    /// Decode `gzip`, `deflate`, `br` and `zstd` bodies. When off, rules and logs see the
+    /// compressed bytes.
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Most bytes a body may decode to. Decoding stops there, and rules and logs see the
+    /// first `max_bytes` only, so a small compressed body cannot expand without bound.
+    #[serde(default = "default_max_decompressed_bytes")]
+    pub max_bytes: usize,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_max_decompressed_bytes() -> usize {
+    10 * 1024 * 1024
+}
+
+impl Default for DecompressionConfig {
+    fn default() -> Self {
+        Self { enabled: true, max_bytes: default_max_decompressed_bytes() }
+    }
+}
+
 /// Top-level configuration loaded from a YAML file.
 ///
 /// Load with [`Config::from_file`], then wrap in [`ConfigHolder`] to serve traffic.
 /// All sections except `logging` and `drop` are optional and default to safe values.
-#[derive(Debug, Serialize, Deserialize)]
+///
+/// Rules are compiled into [`CompiledRules`] by `from_file`, or on first use for configs built
+/// another way. Rule fields changed after that are not seen by matching.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
+#[schemars(
+    title = "LogProx configuration",
+    description = "All sections except `logging` and `drop` are optional and default to safe values."
+)]
 pub struct Config {
     #[serde(default)]
     pub server: ServerConfig,
@@ -99,139 +167,219 @@ pub struct Config {
     /// Upstream access controls (SSRF protection).
     #[serde(default)]
     pub upstream: UpstreamConfig,
+    /// Mock rules and recorded-response replay.
+    #[serde(default)]
+    pub mock: MockConfig,
+    /// Fault injection rules for chaos testing.
+    #[serde(default)]
+    pub faults: FaultConfig,
+    /// Per-client/per-key request rate limits.
+    #[serde(default)]
+    pub rate_limits: RateLimitConfig,
+    /// Request and response header transformation.
+    #[serde(default)]
+    pub headers: HeaderConfig,
+    /// Request and response body transformation.
+    #[serde(default)]
+    pub bodies: BodyConfig,
+    /// Upstream URL rewriting and redirects.
+    #[serde(default)]
+    pub rewrites: RewriteConfig,
+    /// Decoding of compressed bodies for matching and logging.
+    #[serde(default)]
+    pub decompression: DecompressionConfig,
+    /// In-flight request limits and load shedding.
+    #[serde(default)]
+    pub concurrency: ConcurrencyConfig,
+    /// Caller authentication (API keys, HTTP Basic, JWT).
+    #[serde(default)]
+    pub auth: AuthConfig,
+    /// Admin listener address and protection.
+    #[serde(default)]
+    pub admin: AdminConfig,
+    /// Automatic reload on config file changes.
+    #[serde(default)]
+    pub reload: ReloadConfig,
+    /// The files this config was assembled from, in merge order. Empty for configs not loaded
+    /// from files.
+    #[serde(skip)]
+    pub source_files: Vec<String>,
+    /// Paths of values read from secret files (`${file:...}`), masked by [`Config::masked_json`].
+    #[serde(skip)]
+    pub secret_paths: BTreeSet<String>,
+    /// The document this config was loaded from, before placeholder substitution. Kept in the
+    /// version history, so rolling back re-resolves variables and secrets.
+    #[serde(skip)]
+    pub document: Option<serde_json::Value>,
+    /// The compiled rules, filled by `from_file` or on first use. Read via [`Config::rules`].
+    #[serde(skip)]
+    pub compiled: OnceLock<CompiledRules>,
 }
 
 /// Thread-safe wrapper around [`Config`] that supports hot reload.
 ///
-/// Uses a `parking_lot::RwLock` internally — many concurrent readers, rare writes
-/// (only during [`reload`](ConfigHolder::reload)). Pass as `Arc<ConfigHolder>` axum state.
+/// Holds the current config as an immutable `Arc<Config>` behind an [`ArcSwap`]: readers take
+/// a lock-free snapshot with [`get`](ConfigHolder::get) and keep it for the whole request, and
+/// [`reload`](ConfigHolder::reload) atomically swaps in a new one. Pass as `Arc<ConfigHolder>`
+/// axum state. Also owns runtime state that must outlive a reload, such as rate limit counters,
+/// metrics and the [`ConfigHistory`] of recently installed configs.
 #[derive(Debug)]
 pub struct ConfigHolder {
-    config: RwLock<Config>,
+    config: ArcSwap<Config>,
+    rate_limiter: RateLimiter,
+    concurrency: ArcSwap<ConcurrencyLimiter>,
+    metrics: Arc<Metrics>,
+    history: parking_lot::Mutex<ConfigHistory>,
 }
 
 impl ConfigHolder {
-    /// Creates a new `ConfigHolder`, pre-warming the regex cache for all patterns.
+    /// Creates a new `ConfigHolder`, compiling the config's rules if that has not happened
+    /// yet and restoring the rate limit snapshot if one is configured.
     pub fn new(config: Config) -> Self {
-        // Pre-warm the global regex cache for all patterns in this config so
-        // that the first live request does not pay compilation cost.
-        prewarm_regex_cache(&config);
+        // Compile up front so the first live request does not pay for it.
+        config.rules();
+        let rate_limiter = RateLimiter::default();
+        if let Some(path) = &config.rate_limits.snapshot_file {
+            if let Err(e) = rate_limiter.load_snapshot(path) {
+                tracing::warn!(file = %path, error = %e, "failed to restore rate limit snapshot");
+            }
+        }
+        let concurrency = ArcSwap::from_pointee(ConcurrencyLimiter::new(config.concurrency.clone()));
+        let mut history = ConfigHistory::default();
+        history.record(config_value(&config), "startup", config.reload.history);
         Self {
-            config: RwLock::new(config),
+            config: ArcSwap::from_pointee(config),
+            rate_limiter,
+            concurrency,
+            metrics: Arc::new(Metrics::default()),
+            history: parking_lot::Mutex::new(history),
         }
     }
 
+    /// Reloads the config from [`config_file_path`].
     pub fn reload(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let config_file =
-            std::env::var("CONFIG_FILE").unwrap_or_else(|_| "config.yaml".to_string());
-        let new_config = Config::from_file(&config_file)?;
-        let mut config = self.config.write();
-        *config = new_config;
-        Ok(())
+        self.reload_from(&config_file_path())
     }
 
-    pub fn get(&self) -> RwLockReadGuard<'_, Config> {
-        self.config.read()
+    /// Loads and validates the config at `path`, then installs it. On error the current
+    /// config stays in place.
+    pub fn reload_from(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let new_config = Config::from_file(path)?;
+        self.install(new_config, "reload");
+        Ok(())
     }
-}
 
-/// Pre-warm the global regex cache with every pattern in the config.
-/// Invalid patterns are silently skipped (they will never match).
-fn prewarm_regex_cache(config: &Config) {
-    let all_patterns = config.logging.rules.iter()
-        .flat_map(|r| {
-            r.match_conditions.path.patterns.iter()
-                .chain(r.match_conditions.body.patterns.iter())
-                .chain(r.match_conditions.headers.values())
-        })
-        .chain(config.drop.rules.iter().flat_map(|r| {
-            r.match_conditions.path.patterns.iter()
-                .chain(r.match_conditions.body.patterns.iter())
-                .chain(r.match_conditions.headers.values())
-        }))
-        .chain(config.response_logging.rules.iter().flat_map(|r| {
-            r.match_conditions.body.patterns.iter()
-                .chain(r.match_conditions.headers.values())
-        }));
-
-    let mut cache = REGEX_CACHE.write();
-    for pattern in all_patterns {
-        if !cache.contains_key(pattern) {
-            if let Ok(re) = regex::Regex::new(pattern) {
-                cache.insert(pattern.clone(), Arc::new(re));
-            }
+    /// Installs `config` as a new version, rebuilding runtime state that depends on it, and
+    /// writes an audit log entry summarising the change. `trigger` records what installed it.
+    /// Returns the new version number.
+    pub fn install(&self, config: Config, trigger: &str) -> u64 {
+        let content = config_value(&config);
+        let keep = config.reload.history;
+        // Held across the swap so versions are numbered in the order they take effect.
+        let mut history = self.history.lock();
+        if config.concurrency != self.get().concurrency {
+            self.concurrency.store(Arc::new(ConcurrencyLimiter::new(config.concurrency.clone())));
         }
+        self.config.store(Arc::new(config));
+        let (version, previous, changes) = history.record(content, trigger, keep);
+        let entry = serde_json::json!({
+            "type": "config_change",
+            "timestamp": chrono::Utc::now().to_rfc3339(),
+            "trigger": trigger,
+            "version": version,
+            "previous_version": previous,
+            "changes": changes,
+        });
+        tracing::info!("{}", entry);
+        version
     }
-}
 
-impl Config {
-    pub fn from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let f = std::fs::File::open(path)?;
-        let mut config: Config = serde_norway::from_reader(f)?;
-        config.substitute_env_vars();
-        // Validate all patterns at startup to surface bad regex before serving traffic.
-        config.validate_patterns()?;
-        // Pre-warm cache so first request pays no compilation cost.
-        prewarm_regex_cache(&config);
-        Ok(config)
-    }
-
-    fn validate_patterns(&self) -> Result<(), Box<dyn std::error::Error>> {
-        for rule in &self.logging.rules {
-            for p in &rule.match_conditions.path.patterns {
-                regex::Regex::new(p).map_err(|e| format!("Invalid path pattern '{}': {}", p, e))?;
-            }
-            for p in &rule.match_conditions.body.patterns {
-                regex::Regex::new(p).map_err(|e| format!("Invalid body pattern '{}': {}", p, e))?;
-            }
-            for p in rule.match_conditions.headers.values() {
-                regex::Regex::new(p).map_err(|e| format!("Invalid header pattern '{}': {}", p, e))?;
-            }
+    /// Re-installs the config recorded as `version`, as a new version. The stored config is
+    /// validated and loaded again, so files it references are re-read.
+    pub fn rollback(&self, version: u64) -> Result<u64, RollbackError> {
+        let content = match self.history.lock().get(version) {
+            Some(entry) => entry.content.to_string(),
+            None => return Err(RollbackError::UnknownVersion(version)),
+        };
+        let (config, issues) = Config::check_source(&content);
+        let errors: Vec<_> = issues.into_iter().filter(ValidationIssue::is_error).collect();
+        match config {
+            Some(config) if errors.is_empty() => Ok(self.install(config, &format!("rollback:{}", version))),
+            _ => Err(RollbackError::Invalid(Box::new(ValidationErrors(errors)))),
         }
-        for rule in &self.drop.rules {
-            for p in &rule.match_conditions.path.patterns {
-                regex::Regex::new(p).map_err(|e| format!("Invalid path pattern '{}': {}", p, e))?;
-            }
-            for p in &rule.match_conditions.body.patterns {
-                regex::Regex::new(p).map_err(|e| format!("Invalid body pattern '{}': {}", p, e))?;
-            }
-            for p in rule.match_conditions.headers.values() {
-                regex::Regex::new(p).map_err(|e| format!("Invalid header pattern '{}': {}", p, e))?;
-            }
-        }
-        for rule in &self.response_logging.rules {
-            for p in &rule.match_conditions.body.patterns {
-                regex::Regex::new(p).map_err(|e| format!("Invalid body pattern '{}': {}", p, e))?;
-            }
-            for p in rule.match_conditions.headers.values() {
-                regex::Regex::new(p).map_err(|e| format!("Invalid header pattern '{}': {}", p, e))?;
-            }
+    }
+
+    /// The active config version.
+    pub fn version(&self) -> u64 {
+        self.history.lock().current()
+    }
+
+    /// The configs kept for rollback, oldest first; the last one is active.
+    pub fn history(&self) -> Vec<ConfigVersion> {
+        self.history.lock().versions().cloned().collect()
+    }
+
+    /// The current config snapshot. It never changes; a reload installs a new snapshot
+    /// instead, so callers see one consistent config for as long as they hold it.
+    pub fn get(&self) -> Arc<Config> {
+        self.config.load_full()
+    }
+
+    pub fn rate_limiter(&self) -> &RateLimiter {
+        &self.rate_limiter
+    }
+
+    pub fn concurrency_limiter(&self) -> Arc<ConcurrencyLimiter> {
+        self.concurrency.load_full()
+    }
+
+    pub fn metrics(&self) -> &Arc<Metrics> {
+        &self.metrics
+    }
+
+    /// Writes the rate limit snapshot, if `rate_limits.snapshot_file` is configured.
+    pub fn save_rate_limit_snapshot(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let snapshot_file = self.get().rate_limits.snapshot_file.clone();
+        match snapshot_file {
+            Some(path) => self.rate_limiter.save_snapshot(&path),
+            None => Ok(()),
         }
-        Ok(())
     }
+}
 
-    /// Match `text` against `pattern` using the global compiled-regex cache.
-    fn match_pattern(pattern: &str, text: &str) -> bool {
-        get_cached_regex(pattern)
-            .map(|re| re.is_match(text))
-            .unwrap_or(false)
+/// `config` as recorded in the version history: its source document, or else the config
+/// serialised as JSON.
+fn config_value(config: &Config) -> serde_json::Value {
+    match &config.document {
+        Some(document) => document.clone(),
+        None => serde_json::to_value(config).unwrap_or_default(),
     }
+}
 
-    fn substitute_env_vars(&mut self) {
-        for rule in &mut self.drop.rules {
-            if let Some(ref mut body) = rule.response.body {
-                *body = Self::substitute_env_in_string(body);
-            }
+/// The config file path: `CONFIG_FILE`, or `config.yaml` if unset.
+pub fn config_file_path() -> String {
+    std::env::var("CONFIG_FILE").unwrap_or_else(|_| "config.yaml".to_string())
+}
+
+impl Config {
+    /// Loads a config file or directory, with its includes and the overlay named by
+    /// `LOGPROX_ENV` (see [`ConfigSources`]). Fails with [`ValidationErrors`] listing every
+    /// error found; warnings are logged and the config is used anyway.
+    pub fn from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        let (config, issues) = Self::check_sources(&ConfigSources::load(path));
+        let (errors, warnings): (Vec<_>, Vec<_>) = issues.into_iter().partition(ValidationIssue::is_error);
+        for warning in &warnings {
+            tracing::warn!(config = %path, "config {}", warning);
+        }
+        match config {
+            Some(config) if errors.is_empty() => Ok(config),
+            _ => Err(Box::new(ValidationErrors(errors))),
         }
     }
 
-    pub fn substitute_env_in_string(s: &str) -> String {
-        let re = regex::Regex::new(r"\$\{([^}]+)\}").unwrap();
-        re.replace_all(s, |caps: &regex::Captures| {
-            let var_name = &caps[1];
-            std::env::var(var_name).unwrap_or_else(|_| format!("${{{}}}", var_name))
-        })
-        .to_string()
+    /// The compiled form of this config's rules.
+    pub fn rules(&self) -> &CompiledRules {
+        self.compiled.get_or_init(|| CompiledRules::compile_lenient(self))
     }
 
     // -----------------------------------------------------------------------
@@ -260,11 +408,11 @@ impl Config {
         conditions: &MatchConditions,
         body_content: &str,
     ) -> bool {
-        self.matches_conditions_parts(req.method().as_str(), req.uri().path(), req.headers(), body_content, conditions)
+        self.matches_rule_parts(req.method().as_str(), req.uri().path(), req.headers(), body_content, conditions)
     }
 
     // -----------------------------------------------------------------------
-    // Part-based variants — used by proxy_handler after the body is consumed
+    // Part-based variants — for callers without an authenticated caller
     // -----------------------------------------------------------------------
 
     pub fn should_log_request_parts(
@@ -274,10 +422,57 @@ impl Config {
         headers: &axum::http::HeaderMap,
         body_content: &str,
     ) -> Option<&CaptureConfig> {
-        for rule in &self.logging.rules {
-            if self.matches_conditions_parts(method, path, headers, body_content, &rule.match_conditions) {
-                return Some(&rule.capture);
-            }
+        self.should_log_request_ctx(&RequestContext::new(method, path, headers, body_content))
+    }
+
+    pub fn should_drop_request_parts(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &axum::http::HeaderMap,
+        body_content: &str,
+    ) -> Option<DropResponse> {
+        self.should_drop_request_ctx(&RequestContext::new(method, path, headers, body_content))
+    }
+
+    pub fn matches_rule_parts(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &axum::http::HeaderMap,
+        body_content: &str,
+        conditions: &MatchConditions,
+    ) -> bool {
+        self.matches_rule_ctx(&RequestContext::new(method, path, headers, body_content), conditions)
+    }
+
+    // -----------------------------------------------------------------------
+    // Context-based variants — used by proxy_handler after the body is consumed
+    // and the caller is authenticated
+    // -----------------------------------------------------------------------
+
+    /// The first logging rule matching the request, with its sampler. The rules are matched
+    /// once; the timeout, capture and sampling decision all come from the result.
+    pub fn logging_match_ctx(&self, ctx: &RequestContext) -> Option<(&LoggingRule, &Sampler)> {
+        let rules = self.rules();
+        let i = rules.logging.first_match(ctx)?;
+        Some((self.logging.rules.get(i)?, rules.logging_samplers.get(i)?))
+    }
+
+    /// The first logging rule matching the request.
+    pub fn logging_rule_ctx(&self, ctx: &RequestContext) -> Option<&LoggingRule> {
+        self.logging_match_ctx(ctx).map(|(rule, _)| rule)
+    }
+
+    pub fn should_log_request_ctx(&self, ctx: &RequestContext) -> Option<&CaptureConfig> {
+        self.request_capture(self.logging_rule_ctx(ctx))
+    }
+
+    /// The capture config of the matched logging `rule`, or the default capture when no rule
+    /// matched and `logging.default` is set.
+    pub fn request_capture<'a>(&'a self, rule: Option<&'a LoggingRule>) -> Option<&'a CaptureConfig> {
+        if let Some(rule) = rule {
+            return Some(&rule.capture);
         }
         if self.logging.default {
             static DEFAULT_CAPTURE: CaptureConfig = CaptureConfig {
@@ -286,6 +481,15 @@ impl Config {
                 method: true,
                 path: true,
                 timing: true,
+                claims: vec![],
+                form: vec![],
+                multipart: false,
+                graphql: false,
+                client_ip: false,
+                client_port: false,
+                tls_version: false,
+                sni: false,
+                body_options: BodyCaptureOptions::NONE,
             };
             Some(&DEFAULT_CAPTURE)
         } else {
@@ -293,17 +497,9 @@ impl Config {
         }
     }
 
-    pub fn should_drop_request_parts(
-        &self,
-        method: &str,
-        path: &str,
-        headers: &axum::http::HeaderMap,
-        body_content: &str,
-    ) -> Option<DropResponse> {
-        for rule in &self.drop.rules {
-            if self.matches_conditions_parts(method, path, headers, body_content, &rule.match_conditions) {
-                return Some(rule.response.clone());
-            }
+    pub fn should_drop_request_ctx(&self, ctx: &RequestContext) -> Option<DropResponse> {
+        if let Some(rule) = self.rules().drop.first_match(ctx).and_then(|i| self.drop.rules.get(i)) {
+            return Some(rule.response.clone());
         }
         if self.drop.default {
             Some(DropResponse {
@@ -315,60 +511,86 @@ impl Config {
         }
     }
 
-    pub fn matches_rule_parts(
-        &self,
-        method: &str,
-        path: &str,
-        headers: &axum::http::HeaderMap,
-        body_content: &str,
-        conditions: &MatchConditions,
-    ) -> bool {
-        self.matches_conditions_parts(method, path, headers, body_content, conditions)
+    /// The first mock rule matching the request, with the capture groups of its matching path
+    /// pattern keyed by group name and by index (`"0"` is the whole match).
+    pub fn should_mock_request_ctx(&self, ctx: &RequestContext) -> Option<(&MockRule, HashMap<String, String>)> {
+        let index = self.rules().mock.first_match(ctx)?;
+        let captures = self.rules().mock.get(index)?.path_captures(ctx.path);
+        Some((self.mock.rules.get(index)?, captures))
     }
 
-    fn matches_conditions_parts(
-        &self,
-        method: &str,
-        path: &str,
-        headers: &axum::http::HeaderMap,
-        body_content: &str,
-        conditions: &MatchConditions,
-    ) -> bool {
-        // Method check
-        if !conditions.methods.is_empty()
-            && !conditions.methods.iter().any(|m| m.eq_ignore_ascii_case(method))
-        {
-            return false;
-        }
+    pub fn should_fault_request_ctx(&self, ctx: &RequestContext) -> Option<&FaultRule> {
+        self.rules().faults.first_match(ctx).and_then(|i| self.faults.rules.get(i))
+    }
 
-        // Path check
-        if !conditions.path.patterns.is_empty()
-            && !conditions.path.patterns.iter().any(|p| Self::match_pattern(p, path))
-        {
-            return false;
-        }
+    /// Returns every rate limit rule matching the request, in config order.
+    pub fn matching_rate_limits_ctx(&self, ctx: &RequestContext) -> Vec<&RateLimitRule> {
+        self.rules().rate_limits.matching(ctx)
+            .filter_map(|i| self.rate_limits.rules.get(i))
+            .collect()
+    }
 
-        // Header check (all specified headers must match)
-        for (header_name, pattern) in &conditions.headers {
-            if let Some(value) = headers.get(header_name) {
-                if let Ok(s) = value.to_str() {
-                    if !Self::match_pattern(pattern, s) {
-                        return false;
-                    }
-                }
-            } else {
-                return false;
-            }
-        }
+    /// The first rewrite rule matching the request and its `upstream_url`, with the rewritten
+    /// URL.
+    pub fn rewrite_ctx(&self, ctx: &RequestContext, upstream_url: &str) -> Option<(&RewriteRule, String)> {
+        let rules = self.rules();
+        rules.rewrites.matching(ctx).find_map(|i| {
+            let rule = self.rewrites.rules.get(i)?;
+            Some((rule, rules.rewrite_patterns.get(i)?.rewrite(rule, upstream_url)?))
+        })
+    }
 
-        // Body check
-        if !conditions.body.patterns.is_empty()
-            && !conditions.body.patterns.iter().any(|p| Self::match_pattern(p, body_content))
-        {
-            return false;
+    /// Returns every header rule matching the request, in config order, with the capture
+    /// groups of its path pattern.
+    pub fn matching_header_rules_ctx(&self, ctx: &RequestContext) -> Vec<(&HeaderRule, HashMap<String, String>)> {
+        let rules = &self.rules().headers;
+        rules.matching(ctx)
+            .filter_map(|i| {
+                let captures = rules.get(i)?.path_captures(ctx.path);
+                Some((self.headers.rules.get(i)?, captures))
+            })
+            .collect()
+    }
+
+    /// Returns every body rule matching the request, in config order, with its compiled
+    /// `replace` patterns and the capture groups of its path pattern.
+    pub fn matching_body_rules_ctx(&self, ctx: &RequestContext) -> Vec<(&BodyRule, &CompiledBodyRule, HashMap<String, String>)> {
+        let rules = self.rules();
+        rules.bodies.matching(ctx)
+            .filter_map(|i| {
+                let captures = rules.bodies.get(i)?.path_captures(ctx.path);
+                Some((self.bodies.rules.get(i)?, rules.body_replacements.get(i)?, captures))
+            })
+            .collect()
+    }
+
+    /// Authenticates the caller. Returns `Ok(None)` when authentication is disabled, or when
+    /// the request is outside `auth.match_conditions` and carries no valid credentials.
+    /// Outside `auth.match_conditions`, credentials are only verified when a rule matches on
+    /// or captures claims.
+    pub async fn authenticate_ctx(&self, ctx: &RequestContext<'_>) -> Result<Option<Identity>, AuthError> {
+        if !self.auth.is_enabled() {
+            return Ok(None);
+        }
+        let required = self.rules().auth.matches(ctx);
+        if !required && !self.rules().uses_claims {
+            return Ok(None);
+        }
+        let result = match self.auth.authenticator() {
+            Some(authenticator) => authenticator.authenticate(ctx.headers).await,
+            None => Err(AuthError::InvalidCredentials("auth config not loaded".to_string())),
+        };
+        match result {
+            Ok(identity) => Ok(Some(identity)),
+            Err(e) if required => Err(e),
+            Err(_) => Ok(None),
         }
+    }
 
-        true
+    /// Evaluates `conditions` that are not part of this config. They are compiled on every
+    /// call (invalid patterns never match); the config's own rules use their compiled form.
+    pub fn matches_rule_ctx(&self, ctx: &RequestContext, conditions: &MatchConditions) -> bool {
+        CompiledRule::lenient(conditions).matches(ctx)
     }
 
     pub fn should_log_response(
@@ -377,10 +599,27 @@ impl Config {
         headers: &axum::http::HeaderMap,
         body_content: &str,
     ) -> Option<&ResponseCaptureConfig> {
-        for rule in &self.response_logging.rules {
-            if self.matches_response_rule(status_code, headers, body_content, &rule.match_conditions) {
-                return Some(&rule.capture);
-            }
+        self.should_log_response_ctx(&ResponseContext::new(status_code, headers, body_content))
+    }
+
+    /// The first response logging rule matching the response, with its sampler. Latency and
+    /// request conditions only match when `ctx` carries the latency and request.
+    pub fn response_logging_match_ctx(&self, ctx: &ResponseContext) -> Option<(&ResponseLoggingRule, &Sampler)> {
+        let rules = self.rules();
+        let i = rules.response_logging.iter().position(|rule| rule.matches_ctx(ctx))?;
+        Some((self.response_logging.rules.get(i)?, rules.response_logging_samplers.get(i)?))
+    }
+
+    /// Returns the capture config of the first response logging rule matching the response.
+    pub fn should_log_response_ctx(&self, ctx: &ResponseContext) -> Option<&ResponseCaptureConfig> {
+        self.response_capture(self.response_logging_match_ctx(ctx).map(|(rule, _)| rule))
+    }
+
+    /// The capture config of the matched response logging `rule`, or the default capture when
+    /// no rule matched and `response_logging.default` is set.
+    pub fn response_capture<'a>(&'a self, rule: Option<&'a ResponseLoggingRule>) -> Option<&'a ResponseCaptureConfig> {
+        if let Some(rule) = rule {
+            return Some(&rule.capture);
         }
         if self.response_logging.default {
             static DEFAULT_RESPONSE_CAPTURE: ResponseCaptureConfig = ResponseCaptureConfig {
@@ -388,6 +627,8 @@ impl Config {
                 body: true,
                 status_code: true,
                 timing: true,
+                claims: vec![],
+                body_options: BodyCaptureOptions::NONE,
             };
             Some(&DEFAULT_RESPONSE_CAPTURE)
         } else {
@@ -395,6 +636,8 @@ impl Config {
         }
     }
 
+    /// Evaluates response `conditions` that are not part of this config, compiling them on
+    /// every call (invalid patterns never match).
     pub fn matches_response_rule(
         &self,
         status_code: u16,
@@ -402,28 +645,26 @@ impl Config {
         body_content: &str,
         conditions: &ResponseMatchConditions,
     ) -> bool {
-        if !conditions.status_codes.is_empty() && !conditions.status_codes.contains(&status_code) {
-            return false;
-        }
-
-        for (header_name, pattern) in &conditions.headers {
-            if let Some(value) = headers.get(header_name) {
-                if let Ok(s) = value.to_str() {
-                    if !Self::match_pattern(pattern, s) {
-                        return false;
-                    }
-                }
-            } else {
-                return false;
-            }
-        }
-
-        if !conditions.body.patterns.is_empty()
-            && !conditions.body.patterns.iter().any(|p| Self::match_pattern(p, body_content))
-        {
-            return false;
-        }
+        CompiledResponseRule::lenient(conditions).matches(status_code, headers, body_content)
+    }
+}
 
-        true
+/// The string values of the claim at dot path `name`. Arrays yield each element; numbers and
+/// booleans are rendered as text.
+fn claim_values(claims: &Claims, name: &str) -> Vec<String> {
+    let mut parts = name.split('.');
+    let mut value = parts.next().and_then(|first| claims.get(first));
+    for part in parts {
+        value = value.and_then(|v| v.get(part));
+    }
+    let scalar = |v: &serde_json::Value| match v {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Number(_) | serde_json::Value::Bool(_) => Some(v.to_string()),
+        _ => None,
+    };
+    match value {
+        Some(serde_json::Value::Array(items)) => items.iter().filter_map(scalar).collect(),
+        Some(v) => scalar(v).into_iter().collect(),
+        None => Vec::new(),
     }
 }
@@ -0,0 +1,111 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::request::{parse_duration_str, MatchConditions};
+
+/// Controls request rate limiting. Unlike other rule sections, every matching rule is
+/// enforced (limits stack); the first one exceeded rejects the request with 429.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
+pub struct RateLimitConfig {
+    /// Rate limit rules. Every matching rule is enforced.
+    #[serde(default)]
+    pub rules: Vec<RateLimitRule>,
+    /// Persist limiter state to this JSON file so limits survive a restart.
+    #[serde(default)]
+    pub snapshot_file: Option<String>,
+    /// How often the snapshot is written (e.g. `"30s"`). Default: 30s.
+    #[serde(default)]
+    pub snapshot_interval: Option<String>,
+}
+
+impl RateLimitConfig {
+    /// Parses `snapshot_interval`, falling back to 30 seconds.
+    pub fn parse_snapshot_interval(&self) -> std::time::Duration {
+        self.snapshot_interval
+            .as_deref()
+            .and_then(parse_duration_str)
+            .unwrap_or(std::time::Duration::from_secs(30))
+    }
+}
+
+/// A limit applied separately to each distinct key among matching requests. Up to 100,000
+/// keys are tracked per rule; requests with further keys share a single limit.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RateLimitRule {
+    /// Rule name, reported in logs and metrics. Rules from included files are merged by name.
+    pub name: String,
+    /// The file this rule was loaded from, when the config was assembled from files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub match_conditions: MatchConditions,
+    /// What requests are counted against: `client_ip`, `upstream_host` (after rewrite rules)
+    /// or `header:NAME`.
+    pub key: RateLimitKey,
+    #[serde(default)]
+    pub algorithm: RateLimitAlgorithm,
+    /// Requests allowed per `window` (also the token bucket's burst capacity).
+    pub limit: u64,
+    /// Window length (e.g. `"1s"`, `"60s"`).
+    pub window: String,
+}
+
+impl RateLimitRule {
+    /// Parses `window`. Returns `None` for an unrecognised format.
+    pub fn parse_window(&self) -> Option<std::time::Duration> {
+        parse_duration_str(&self.window)
+    }
+}
+
+/// The request attribute a rate limit is keyed on. Written as `client_ip`, `upstream_host`
+/// or `header:NAME` in config.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+#[schemars(with = "String")]
+pub enum RateLimitKey {
+    /// The client's IP address.
+    ClientIp,
+    /// The host of the upstream URL.
+    UpstreamHost,
+    /// The value of a request header (e.g. an API key). Requests without it share one bucket.
+    Header(String),
+}
+
+impl TryFrom<String> for RateLimitKey {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "client_ip" => Ok(Self::ClientIp),
+            "upstream_host" => Ok(Self::UpstreamHost),
+            _ => match s.strip_prefix("header:") {
+                Some(name) if !name.trim().is_empty() => Ok(Self::Header(name.trim().to_ascii_lowercase())),
+                _ => Err(format!(
+                    "invalid rate limit key '{}' (expected client_ip, upstream_host or header:NAME)",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+impl From<RateLimitKey> for String {
+    fn from(key: RateLimitKey) -> Self {
+        match key {
+            RateLimitKey::ClientIp => "client_ip".to_string(),
+            RateLimitKey::UpstreamHost => "upstream_host".to_string(),
+            RateLimitKey::Header(name) => format!("header:{}", name),
+        }
+    }
+}
+
+/// How requests are counted within a window.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitAlgorithm {
+    /// Bucket of `limit` tokens refilled continuously over `window`; allows bursts.
+    #[default]
+    TokenBucket,
+    /// Weighted count over the current and previous window; smooths window edges.
+    SlidingWindow,
+}
@@ -0,0 +1,43 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::request::parse_duration_str;
+
+/// Automatic config reloads and version history. On Unix, SIGHUP always triggers a reload.
+/// `watch` and `debounce` are read at startup.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+pub struct ReloadConfig {
+    /// Reload when the config file changes on disk. Default: true.
+    #[serde(default = "default_watch")]
+    pub watch: bool,
+    /// Quiet period after the last change before reloading (e.g. `"500ms"`). Default: 500ms.
+    #[serde(default)]
+    pub debounce: Option<String>,
+    /// How many installed configs to keep for rollback, including the active one. Default: 10.
+    #[serde(default = "default_history")]
+    pub history: usize,
+}
+
+fn default_watch() -> bool {
+    true
+}
+
+fn default_history() -> usize {
+    10
+}
+
+impl Default for ReloadConfig {
+    fn default() -> Self {
+        Self { watch: default_watch(), debounce: None, history: default_history() }
+    }
+}
+
+impl ReloadConfig {
+    /// Parses `debounce`, falling back to 500 milliseconds.
+    pub fn parse_debounce(&self) -> std::time::Duration {
+        self.debounce
+            .as_deref()
+            .and_then(parse_duration_str)
+            .unwrap_or(std::time::Duration::from_millis(500))
+    }
+}
@@ -1,35 +1,50 @@
+use base64::Engine;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::OnceLock;
+
+use super::auth::Claims;
+use super::fields::BodyFields;
+use super::sampling::Sampling;
 
 /// Controls request logging. Set `default: true` to log all requests, or define `rules`
 /// to log only matching ones. First matching rule wins.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
 pub struct LoggingConfig {
     /// Log all requests when no rule matches.
     pub default: bool,
+    /// Logging rules, evaluated in order.
     pub rules: Vec<LoggingRule>,
 }
 
 /// Controls request dropping. Set `default: true` to drop all requests, or define `rules`
 /// to drop only matching ones. First matching rule wins.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
 pub struct DropConfig {
     /// Drop all requests when no rule matches (returns 403).
     pub default: bool,
+    /// Drop rules, evaluated in order.
     pub rules: Vec<DropRule>,
 }
 
 /// A rule that drops matching requests and returns a fixed response.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct DropRule {
+    /// Rule name, reported in logs and metrics. Rules from included files are merged by name.
     pub name: String,
+    /// The file this rule was loaded from, when the config was assembled from files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
     pub match_conditions: MatchConditions,
     pub response: DropResponse,
 }
 
 /// The HTTP response returned when a drop rule matches.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct DropResponse {
+    /// HTTP status code.
     pub status_code: u16,
     /// Response body. Supports `${ENV_VAR}` substitution.
     #[serde(default)]
@@ -37,15 +52,22 @@ pub struct DropResponse {
 }
 
 /// A rule that logs matching requests. Optionally applies a per-request upstream timeout.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct LoggingRule {
+    /// Rule name, reported in logs and metrics. Rules from included files are merged by name.
     pub name: String,
+    /// The file this rule was loaded from, when the config was assembled from files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
     pub match_conditions: MatchConditions,
     pub capture: CaptureConfig,
     /// Upstream timeout for requests matching this rule (e.g. `"30s"`, `"500ms"`).
     /// No timeout applied if absent.
     #[serde(default)]
     pub timeout: Option<String>,
+    /// Logs only a sample of the matching requests.
+    #[serde(flatten)]
+    pub sampling: Sampling,
 }
 
 impl LoggingRule {
@@ -56,7 +78,7 @@ impl LoggingRule {
     }
 }
 
-fn parse_duration_str(s: &str) -> Option<std::time::Duration> {
+pub(crate) fn parse_duration_str(s: &str) -> Option<std::time::Duration> {
     let s = s.trim();
     if s.is_empty() {
         return None;
@@ -73,7 +95,8 @@ fn parse_duration_str(s: &str) -> Option<std::time::Duration> {
 /// Conditions that must all be satisfied for a rule to match a request.
 /// Empty collections mean "match anything" for that condition.
 /// Different condition types are ANDed; within path/body pattern lists, any one match suffices (OR).
-#[derive(Debug, Serialize, Deserialize)]
+/// `all`, `any` and `not` nest further condition blocks for OR and negation across types.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
 pub struct MatchConditions {
     /// Path regex patterns — at least one must match (OR). Empty = match any path.
     #[serde(default)]
@@ -87,33 +110,255 @@ pub struct MatchConditions {
     /// Body regex patterns — at least one must match (OR). Empty = match any body.
     #[serde(default)]
     pub body: BodyMatch,
+    /// Claim conditions — all specified claims of the authenticated caller must match their
+    /// regex pattern (AND). Nested claims use dot paths (`realm.roles`); array claims match if
+    /// any element does. Never matches unauthenticated requests.
+    #[serde(default)]
+    pub claims: HashMap<String, String>,
+    /// Form field conditions — all specified fields of a urlencoded or multipart form body
+    /// must be present and match their regex pattern (AND). A repeated field matches if any
+    /// of its values does.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub form: HashMap<String, String>,
+    /// Multipart part conditions — each must be met by at least one part of a
+    /// `multipart/form-data` body (AND). Only part headers are matched, never file contents.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub multipart: Vec<PartMatch>,
+    /// GraphQL operation conditions. Never matches requests that are not GraphQL.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub graphql: Option<GraphqlMatch>,
+    /// Client address ranges — the client IP must fall in at least one (OR). Entries are CIDR
+    /// ranges (`10.0.0.0/8`, `2001:db8::/32`) or single addresses. Empty = any client.
+    /// Requests from `server.trusted_proxies` are matched on the client they forward for.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub client_ip: Vec<String>,
+    /// Nested condition blocks that must all match (AND).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub all: Vec<MatchConditions>,
+    /// Nested condition blocks of which at least one must match (OR). Empty = no condition.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub any: Vec<MatchConditions>,
+    /// A nested condition block that must not match, e.g. `not: { methods: [GET] }`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not: Option<Box<MatchConditions>>,
+}
+
+/// Conditions one multipart part must meet. Absent conditions match any part.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
+pub struct PartMatch {
+    /// Regex matched against the part's field name.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Regex matched against the part's filename. Parts without a filename never match.
+    #[serde(default)]
+    pub filename: Option<String>,
+    /// Regex matched against the part's `Content-Type`. Parts without one never match.
+    #[serde(default)]
+    pub content_type: Option<String>,
+}
+
+/// Conditions on the GraphQL operation a request executes.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
+pub struct GraphqlMatch {
+    /// Regex matched against the executed operation's name. Anonymous operations never match.
+    #[serde(default)]
+    pub operation_name: Option<String>,
+    /// Operation types to match. Empty = any type.
+    #[serde(default)]
+    pub operation_types: Vec<GraphqlOperationType>,
+}
+
+/// A GraphQL operation type.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GraphqlOperationType {
+    Query,
+    Mutation,
+    Subscription,
+}
+
+/// The request attributes that [`MatchConditions`] are evaluated against.
+#[derive(Debug, Clone)]
+pub struct RequestContext<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    pub headers: &'a axum::http::HeaderMap,
+    pub body: &'a str,
+    /// Verified claims of the caller, if the request was authenticated.
+    pub claims: Option<&'a Claims>,
+    /// Address of the client, when known.
+    pub client_ip: Option<IpAddr>,
+    /// Form fields, multipart parts and GraphQL operation, parsed from `body` on first use.
+    fields: OnceLock<BodyFields>,
+}
+
+impl<'a> RequestContext<'a> {
+    pub fn new(method: &'a str, path: &'a str, headers: &'a axum::http::HeaderMap, body: &'a str) -> Self {
+        Self { method, path, headers, body, claims: None, client_ip: None, fields: OnceLock::new() }
+    }
+
+    pub fn with_claims(self, claims: Option<&'a Claims>) -> Self {
+        Self { claims, ..self }
+    }
+
+    pub fn with_client_ip(self, client_ip: Option<IpAddr>) -> Self {
+        Self { client_ip, ..self }
+    }
+
+    /// The structured fields of the body.
+    pub fn fields(&self) -> &BodyFields {
+        self.fields.get_or_init(|| BodyFields::parse(self.headers, self.body))
+    }
 }
 
 /// Regex patterns matched against the request path.
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
 pub struct PathMatch {
+    /// Regex patterns matched against the request path.
     pub patterns: Vec<String>,
 }
 
 /// Regex patterns matched against the request body.
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
 pub struct BodyMatch {
+    /// Regex patterns matched against the request body.
     pub patterns: Vec<String>,
 }
 
 /// Specifies what request data to include in log output.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
 pub struct CaptureConfig {
     /// Header names to capture.
     #[serde(default)]
     pub headers: Vec<String>,
+    /// Log the request body.
     #[serde(default)]
     pub body: bool,
+    /// Log the request method.
     #[serde(default)]
     pub method: bool,
+    /// Log the request path.
     #[serde(default)]
     pub path: bool,
     /// Log elapsed time from request receipt to upstream response.
     #[serde(default)]
     pub timing: bool,
+    /// Names of verified caller claims to capture (e.g. `sub`, `email`).
+    #[serde(default)]
+    pub claims: Vec<String>,
+    /// Names of urlencoded or multipart form fields to capture. A repeated field is logged as
+    /// a list of its values.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub form: Vec<String>,
+    /// Log the name, filename and content type of every multipart part.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub multipart: bool,
+    /// Log the GraphQL operation name and type.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub graphql: bool,
+    /// Log the client's IP address (as resolved through `server.trusted_proxies`).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub client_ip: bool,
+    /// Log the client's port, when known.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub client_port: bool,
+    /// Log the TLS version of the connection, for requests received over TLS.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub tls_version: bool,
+    /// Log the server name (SNI) the client asked for, for requests received over TLS.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub sni: bool,
+    /// How the body is captured, when `body` is set.
+    #[serde(flatten)]
+    pub body_options: BodyCaptureOptions,
+}
+
+/// How a captured body is written to the log. Bodies that are not valid UTF-8 are logged
+/// base64-encoded, with `"body_encoding": "base64"`.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct BodyCaptureOptions {
+    /// Log at most this many bytes of the body. Longer bodies are cut, and the entry gets
+    /// `"body_truncated": true` and the full `body_size`. Absent = no limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_body_bytes: Option<usize>,
+    /// Only log bodies with one of these content types (`application/json`, `text/*`).
+    /// Empty = any content type.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub body_content_types: Vec<String>,
+    /// Never log bodies with one of these content types (e.g. `image/*`). The entry gets the
+    /// `body_size` instead.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skip_body_content_types: Vec<String>,
+    /// Log JSON bodies as structured JSON instead of an escaped string.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub json_body: bool,
+}
+
+impl BodyCaptureOptions {
+    /// Captures bodies whole, as text.
+    pub const NONE: Self = Self {
+        max_body_bytes: None,
+        body_content_types: Vec::new(),
+        skip_body_content_types: Vec::new(),
+        json_body: false,
+    };
+
+    /// Whether a body of `content_type` (a `Content-Type` value, if any) is logged.
+    pub fn logs_content_type(&self, content_type: Option<&str>) -> bool {
+        let media_type = content_type
+            .and_then(|v| v.split(';').next())
+            .map(|v| v.trim().to_ascii_lowercase())
+            .unwrap_or_default();
+        let matches = |pattern: &String| media_type_matches(pattern, &media_type);
+        (self.body_content_types.is_empty() || self.body_content_types.iter().any(matches))
+            && !self.skip_body_content_types.iter().any(matches)
+    }
+
+    /// The log entry fields for a body of `content_type`: `body` as text, structured JSON or
+    /// base64 (with `body_encoding`), cut at `max_body_bytes` (with `body_truncated` and
+    /// `body_size`), or only `body_size` for a skipped content type.
+    pub fn capture(&self, content_type: Option<&str>, body: &[u8]) -> serde_json::Map<String, serde_json::Value> {
+        let mut fields = serde_json::Map::new();
+        if !self.logs_content_type(content_type) {
+            fields.insert("body_size".into(), body.len().into());
+            return fields;
+        }
+        let limit = self.max_body_bytes.unwrap_or(usize::MAX).min(body.len());
+        let truncated = limit < body.len();
+        if truncated {
+            fields.insert("body_truncated".into(), true.into());
+            fields.insert("body_size".into(), body.len().into());
+        }
+        let captured = &body[..limit];
+        let text = match std::str::from_utf8(captured) {
+            Ok(text) => Some(text),
+            // A cut through a multi-byte character drops the partial character
+            Err(e) if truncated && e.error_len().is_none() => std::str::from_utf8(&captured[..e.valid_up_to()]).ok(),
+            Err(_) => None,
+        };
+        let value = match text {
+            Some(text) if self.json_body => match serde_json::from_str::<serde_json::Value>(text) {
+                Ok(json) if json.is_object() || json.is_array() => json,
+                _ => text.into(),
+            },
+            Some(text) => text.into(),
+            None => {
+                fields.insert("body_encoding".into(), "base64".into());
+                base64::engine::general_purpose::STANDARD.encode(captured).into()
+            }
+        };
+        fields.insert("body".into(), value);
+        fields
+    }
+}
+
+/// Whether `media_type` (lowercase, without parameters) matches `pattern`: an exact media
+/// type, `type/*` or `*/*`.
+fn media_type_matches(pattern: &str, media_type: &str) -> bool {
+    let pattern = pattern.trim().to_ascii_lowercase();
+    match pattern.strip_suffix("/*") {
+        Some("*") => true,
+        Some(kind) => media_type.split('/').next() == Some(kind) && media_type.contains('/'),
+        None => pattern == media_type,
+    }
 }
@@ -1,52 +1,135 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::Duration;
 
-use super::request::BodyMatch;
+use super::request::{BodyCaptureOptions, BodyMatch, MatchConditions, RequestContext};
+use super::sampling::Sampling;
 
 /// Controls response logging. Set `default: true` to log all responses, or define `rules`
 /// to log only matching ones. First matching rule wins.
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
 pub struct ResponseLoggingConfig {
     /// Log all responses when no rule matches.
     #[serde(default)]
     pub default: bool,
+    /// Response logging rules, evaluated in order.
     #[serde(default)]
     pub rules: Vec<ResponseLoggingRule>,
 }
 
 /// A rule that logs matching upstream responses.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct ResponseLoggingRule {
+    /// Rule name, reported in logs and metrics. Rules from included files are merged by name.
     pub name: String,
+    /// The file this rule was loaded from, when the config was assembled from files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
     pub match_conditions: ResponseMatchConditions,
     pub capture: ResponseCaptureConfig,
+    /// Logs only a sample of the matching responses.
+    #[serde(flatten)]
+    pub sampling: Sampling,
 }
 
 /// Conditions that must all be satisfied for a response logging rule to match.
-#[derive(Debug, Serialize, Deserialize)]
+/// `all`, `any` and `not` nest further condition blocks for OR and negation across types.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
 pub struct ResponseMatchConditions {
-    /// HTTP status codes — response status must appear in the list. Empty = match any status.
+    /// HTTP status codes — response status must appear in the list. Empty (and no
+    /// `status_ranges`) = match any status.
     #[serde(default)]
     pub status_codes: Vec<u16>,
+    /// Status classes (`4xx`) and inclusive ranges (`500-599`). With `status_codes`, the status
+    /// must be one of the codes or fall in one of the ranges (OR).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub status_ranges: Vec<String>,
+    /// Match responses that took at least this long from request receipt (e.g. `2s`, `500ms`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_latency: Option<String>,
+    /// Match responses that took at most this long from request receipt.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_latency: Option<String>,
+    /// Match response bodies of at least this many bytes, as received (before decompression).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_body_bytes: Option<usize>,
+    /// Match response bodies of at most this many bytes, as received (before decompression).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_body_bytes: Option<usize>,
+    /// Conditions on the request the response answers (`path`, `methods`, `headers`, ...),
+    /// as in request rules.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request: Option<MatchConditions>,
     /// Header conditions — all specified headers must match their regex pattern (AND).
     #[serde(default)]
     pub headers: HashMap<String, String>,
     /// Body regex patterns — at least one must match (OR). Empty = match any body.
     #[serde(default)]
     pub body: BodyMatch,
+    /// Nested condition blocks that must all match (AND).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub all: Vec<ResponseMatchConditions>,
+    /// Nested condition blocks of which at least one must match (OR). Empty = no condition.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub any: Vec<ResponseMatchConditions>,
+    /// A nested condition block that must not match, e.g. `not: { status_codes: [200] }`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub not: Option<Box<ResponseMatchConditions>>,
+}
+
+/// The response attributes that [`ResponseMatchConditions`] are evaluated against.
+#[derive(Debug, Clone)]
+pub struct ResponseContext<'a> {
+    pub status_code: u16,
+    pub headers: &'a axum::http::HeaderMap,
+    /// The body as text, decoded per its `Content-Encoding`.
+    pub body: &'a str,
+    /// Size of the body as received, in bytes.
+    pub body_size: usize,
+    /// Time from request receipt to the response. Latency conditions never match without it.
+    pub latency: Option<Duration>,
+    /// The request the response answers. Request conditions never match without it.
+    pub request: Option<&'a RequestContext<'a>>,
+}
+
+impl<'a> ResponseContext<'a> {
+    pub fn new(status_code: u16, headers: &'a axum::http::HeaderMap, body: &'a str) -> Self {
+        Self { status_code, headers, body, body_size: body.len(), latency: None, request: None }
+    }
+
+    pub fn with_body_size(self, body_size: usize) -> Self {
+        Self { body_size, ..self }
+    }
+
+    pub fn with_latency(self, latency: Duration) -> Self {
+        Self { latency: Some(latency), ..self }
+    }
+
+    pub fn with_request(self, request: &'a RequestContext<'a>) -> Self {
+        Self { request: Some(request), ..self }
+    }
 }
 
 /// Specifies what response data to include in log output.
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
 pub struct ResponseCaptureConfig {
     /// Response header names to capture.
     #[serde(default)]
     pub headers: Vec<String>,
+    /// Log the response body.
     #[serde(default)]
     pub body: bool,
+    /// Log the response status code.
     #[serde(default)]
     pub status_code: bool,
     /// Log elapsed time from request receipt to response completion.
     #[serde(default)]
     pub timing: bool,
+    /// Names of verified caller claims to capture (e.g. `sub`, `email`).
+    #[serde(default)]
+    pub claims: Vec<String>,
+    /// How the body is captured, when `body` is set.
+    #[serde(flatten)]
+    pub body_options: BodyCaptureOptions,
 }
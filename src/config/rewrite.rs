@@ -0,0 +1,60 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::request::MatchConditions;
+
+/// Redirect statuses a rewrite rule may respond with.
+pub const REDIRECT_STATUSES: [u16; 4] = [301, 302, 307, 308];
+
+/// Controls upstream URL rewriting. A rule applies when the request satisfies
+/// `match_conditions` and the upstream URL (or its path) matches `pattern`. First matching
+/// rule wins.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
+pub struct RewriteConfig {
+    /// Rewrite rules, evaluated in order.
+    #[serde(default)]
+    pub rules: Vec<RewriteRule>,
+}
+
+/// Rewrites the upstream URL of matching requests, or redirects the client to the rewritten
+/// URL.
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct RewriteRule {
+    /// Rule name, reported in logs and metrics. Rules from included files are merged by name.
+    pub name: String,
+    /// The file this rule was loaded from, when the config was assembled from files.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub match_conditions: MatchConditions,
+    /// What `pattern` is matched against: the upstream URL without its query (`url`), or the
+    /// URL's path (`path`).
+    #[serde(default)]
+    pub target: RewriteTarget,
+    /// Regex matched against the target. The rule only applies when it matches. Absent = any.
+    #[serde(default)]
+    pub pattern: Option<String>,
+    /// Replaces the part of the target matched by `pattern`; `$1` and `$name` insert capture
+    /// groups (e.g. `/v1/(.*)` with `/v2/$1`). Write `${name}` as `$${name}`, since `${...}`
+    /// is a variable placeholder. Requires `pattern`.
+    #[serde(default)]
+    pub replacement: Option<String>,
+    /// Replace the upstream host, and the port if given (`host` or `host:port`).
+    #[serde(default)]
+    pub host: Option<String>,
+    /// Respond with this redirect status (301, 302, 307 or 308), pointing the client at the
+    /// rewritten URL through the proxy, instead of proxying.
+    #[serde(default)]
+    pub redirect: Option<u16>,
+}
+
+/// The part of the upstream URL a rewrite rule matches and replaces.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum RewriteTarget {
+    /// The whole upstream URL, e.g. `https://api.example.com/v1/users`.
+    #[default]
+    Url,
+    /// The upstream URL's path, e.g. `/v1/users`.
+    Path,
+}
@@ -0,0 +1,65 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Sampling of a logging rule's entries. Without `sample_rate` or `sample_every`, every match
+/// is logged.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Default)]
+pub struct Sampling {
+    /// Fraction (0.0-1.0) of matches to log, e.g. `0.01` for 1%.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<f64>,
+    /// Log one in every N matches. Takes priority over `sample_rate`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_every: Option<u64>,
+    /// Sample on `request_id` or `header:NAME` instead of at random: the same key is always
+    /// sampled the same way, so a request and its response are logged together when both
+    /// rules sample on it at the same rate. Requests without the header are sampled at random.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sample_key: Option<SampleKey>,
+    /// Log matches whose response status is 500 or above even when they are sampled out.
+    /// A sampled-out request entry is held back until the response status is known.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub always_log_errors: bool,
+}
+
+impl Sampling {
+    /// Whether any matches are sampled out.
+    pub fn is_enabled(&self) -> bool {
+        self.sample_rate.is_some() || self.sample_every.is_some()
+    }
+}
+
+/// The request attribute sampling is keyed on. Written as `request_id` or `header:NAME` in
+/// config.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+#[schemars(with = "String")]
+pub enum SampleKey {
+    /// The request ID (`x-request-id`, or the ID generated for the request).
+    RequestId,
+    /// The value of a request header (e.g. a trace ID).
+    Header(String),
+}
+
+impl TryFrom<String> for SampleKey {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        match s.as_str() {
+            "request_id" => Ok(Self::RequestId),
+            _ => match s.strip_prefix("header:") {
+                Some(name) if !name.trim().is_empty() => Ok(Self::Header(name.trim().to_ascii_lowercase())),
+                _ => Err(format!("invalid sample key '{}' (expected request_id or header:NAME)", s)),
+            },
+        }
+    }
+}
+
+impl From<SampleKey> for String {
+    fn from(key: SampleKey) -> Self {
+        match key {
+            SampleKey::RequestId => "request_id".to_string(),
+            SampleKey::Header(name) => format!("header:{}", name),
+        }
+    }
+}
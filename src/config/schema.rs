@@ -0,0 +1,171 @@
+//! The JSON Schema of the config file, derived from the [`Config`] structs, and the field
+//! reference in `config_docs.md` that is generated from it.
+
+use serde_json::{Map, Value};
+
+use super::Config;
+
+/// The hand-written part of the docs, with the generated field reference between
+/// [`REFERENCE_BEGIN`] and [`REFERENCE_END`].
+pub const DOCS: &str = include_str!("../../config_docs.md");
+
+pub const REFERENCE_BEGIN: &str = "<!-- BEGIN GENERATED FIELD REFERENCE: run `logprox docs` to update -->";
+pub const REFERENCE_END: &str = "<!-- END GENERATED FIELD REFERENCE -->";
+
+/// The JSON Schema (draft 2020-12) every config file is checked against, as served by
+/// `GET /config/schema` and printed by `logprox schema`.
+pub fn schema() -> Value {
+    serde_json::to_value(schemars::schema_for!(Config)).unwrap_or_default()
+}
+
+/// The config docs with the field reference rendered from the current [`schema`].
+pub fn docs() -> String {
+    let (Some(begin), Some(end)) = (DOCS.find(REFERENCE_BEGIN), DOCS.find(REFERENCE_END)) else {
+        return DOCS.to_string();
+    };
+    format!("{}\n\n{}\n{}", &DOCS[..begin + REFERENCE_BEGIN.len()], reference(), &DOCS[end..])
+}
+
+/// A Markdown table per config section listing every field with its type, default and
+/// description (the field's doc comment).
+pub fn reference() -> String {
+    let schema = schema();
+    let defs = schema.get("$defs").and_then(Value::as_object).cloned().unwrap_or_default();
+    let renderer = Renderer { defs: &defs };
+    let mut out = String::new();
+    let Some(sections) = schema.get("properties").and_then(Value::as_object) else {
+        return out;
+    };
+    for (section, property) in sections {
+        let description = renderer.description(property);
+        out.push_str(&format!("### `{}`\n\n", section));
+        if !description.is_empty() {
+            out.push_str(&format!("{}\n\n", description));
+        }
+        out.push_str("| Field | Type | Default | Description |\n|---|---|---|---|\n");
+        let mut rows = Vec::new();
+        renderer.fields(property, section, &mut Vec::new(), &mut rows);
+        for row in rows {
+            out.push_str(&format!("| {} |\n", row.join(" | ")));
+        }
+        out.push('\n');
+    }
+    out.truncate(out.trim_end().len());
+    out.push('\n');
+    out
+}
+
+struct Renderer<'a> {
+    defs: &'a Map<String, Value>,
+}
+
+impl Renderer<'_> {
+    /// Follows `$ref`s (including a lone ref inside `allOf`) to the definition they name.
+    fn resolve<'s>(&'s self, schema: &'s Value) -> &'s Value {
+        if let Some(name) = schema.get("$ref").and_then(Value::as_str).and_then(|r| r.strip_prefix("#/$defs/")) {
+            return self.defs.get(name).map_or(schema, |def| self.resolve(def));
+        }
+        match schema.get("allOf").and_then(Value::as_array) {
+            Some(all) if all.len() == 1 => self.resolve(&all[0]),
+            _ => schema,
+        }
+    }
+
+    /// `Option<T>` is `T` or null: returns `T`'s schema.
+    fn non_null<'s>(&'s self, schema: &'s Value) -> &'s Value {
+        if let Some(any) = schema.get("anyOf").and_then(Value::as_array) {
+            let types: Vec<&Value> = any.iter().filter(|s| s.get("type") != Some(&Value::from("null"))).collect();
+            if types.len() == 1 {
+                return self.non_null(types[0]);
+            }
+        }
+        schema
+    }
+
+    fn description(&self, schema: &Value) -> String {
+        schema
+            .get("description")
+            .or_else(|| self.resolve(self.non_null(schema)).get("description"))
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .split("\n\n")
+            .map(|paragraph| paragraph.split_whitespace().collect::<Vec<_>>().join(" "))
+            .collect::<Vec<_>>()
+            .join("<br>")
+            .replace('|', "\\|")
+    }
+
+    /// The fields of an object schema, recursing into nested objects and list items.
+    /// Recursive definitions (nested `match_conditions`) are listed once, at their outermost use.
+    fn fields<'s>(&'s self, schema: &'s Value, path: &str, seen: &mut Vec<&'s Value>, rows: &mut Vec<[String; 4]>) {
+        let target = self.resolve(self.non_null(schema));
+        let (target, path) = match target.get("items") {
+            Some(items) => (self.resolve(self.non_null(items)), format!("{}[]", path)),
+            None => (target, path.to_string()),
+        };
+        let Some(properties) = target.get("properties").and_then(Value::as_object) else {
+            return;
+        };
+        if seen.iter().any(|ancestor| std::ptr::eq(*ancestor, target)) {
+            return;
+        }
+        seen.push(target);
+        for (name, property) in properties {
+            let field = format!("{}.{}", path, name);
+            // Object defaults are spelled out by the rows of their fields.
+            let default = property.get("default").filter(|d| !d.is_null() && !d.is_object()).map(|d| format!("`{}`", d));
+            let required = target
+                .get("required")
+                .and_then(Value::as_array)
+                .is_some_and(|required| required.iter().any(|r| r == name));
+            rows.push([
+                format!("`{}`", field),
+                self.type_name(property),
+                default.unwrap_or_else(|| if required { "required".to_string() } else { String::new() }),
+                self.description(property),
+            ]);
+            self.fields(property, &field, seen, rows);
+        }
+        seen.pop();
+    }
+
+    fn type_name(&self, schema: &Value) -> String {
+        let schema = self.non_null(schema);
+        let resolved = self.resolve(schema);
+        if let Some(values) = resolved.get("enum").and_then(Value::as_array) {
+            return values.iter().map(|v| format!("`{}`", v.as_str().unwrap_or_default())).collect::<Vec<_>>().join(" \\| ");
+        }
+        if let Some(variants) = resolved.get("oneOf").or_else(|| resolved.get("anyOf")).and_then(Value::as_array) {
+            let names: Vec<String> = variants
+                .iter()
+                .map(|variant| match variant.get("const").and_then(Value::as_str) {
+                    Some(value) => format!("`{}`", value),
+                    None => self.type_name(variant),
+                })
+                .collect();
+            return names.join(" \\| ");
+        }
+        if let Some(value) = resolved.get("const").and_then(Value::as_str) {
+            return format!("`{}`", value);
+        }
+        let types: Vec<&str> = match resolved.get("type") {
+            Some(Value::String(t)) => vec![t.as_str()],
+            Some(Value::Array(types)) => types.iter().filter_map(Value::as_str).filter(|t| *t != "null").collect(),
+            _ => Vec::new(),
+        };
+        match types.as_slice() {
+            ["array"] => match resolved.get("items") {
+                Some(items) => format!("list of {}", self.type_name(items)),
+                None => "list".to_string(),
+            },
+            ["object"] if resolved.get("properties").is_none() => match resolved.get("additionalProperties") {
+                Some(values) if values.is_object() => format!("map of {}", self.type_name(values)),
+                _ => "map".to_string(),
+            },
+            ["object"] => "object".to_string(),
+            ["integer"] => "integer".to_string(),
+            [t] => t.to_string(),
+            _ => "any".to_string(),
+        }
+    }
+}
@@ -0,0 +1,418 @@
+//! Assembling one config from several files: `include:` lists, per-environment overlays and
+//! config directories. Each file is YAML, JSON or TOML, chosen by its extension.
+//!
+//! Files are merged in order, later files winning: a file, then the files it includes (in
+//! listed order, glob matches sorted by path), then the environment overlay. Mappings merge
+//! key by key and other values are replaced, except `<section>.rules` lists, which merge by
+//! rule name.
+
+use serde_norway::{Mapping, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::validate::{PositionIndex, Severity, ValidationIssue};
+
+/// Environment variable naming the overlay to apply, e.g. `prod` for `config.prod.yaml`.
+pub const ENV_VAR: &str = "LOGPROX_ENV";
+
+/// The syntax of a config file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl Format {
+    /// `.json` and `.toml` files are JSON and TOML; anything else is YAML.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Format::Json,
+            Some("toml") => Format::Toml,
+            _ => Format::Yaml,
+        }
+    }
+
+    fn parse(self, text: &str) -> Result<Value, ValidationIssue> {
+        let error = |message: String, position: Option<(usize, usize)>| {
+            let mut issue = ValidationIssue::new(Severity::Error, "", message);
+            issue.line = position.map(|(line, _)| line);
+            issue.column = position.map(|(_, column)| column);
+            issue
+        };
+        match self {
+            Format::Yaml => serde_norway::from_str(text)
+                .map_err(|e| error(e.to_string(), e.location().map(|l| (l.line(), l.column())))),
+            Format::Json => serde_json::from_str::<serde_json::Value>(text)
+                .map_err(|e| error(e.to_string(), Some((e.line(), e.column()))))
+                .and_then(|value| serde_norway::to_value(value).map_err(|e| error(e.to_string(), None))),
+            Format::Toml => toml::from_str::<toml::Value>(text)
+                .map_err(|e| error(e.message().to_string(), e.span().map(|span| line_column(text, span.start))))
+                .and_then(|value| serde_norway::to_value(value).map_err(|e| error(e.to_string(), None))),
+        }
+    }
+
+    /// Positions of values in `text`, for the formats that have them.
+    fn positions(self, text: &str) -> Option<PositionIndex> {
+        match self {
+            // JSON documents are also YAML, with the same positions.
+            Format::Yaml | Format::Json => PositionIndex::parse(text),
+            Format::Toml => None,
+        }
+    }
+}
+
+/// A document that contributed to a config.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    /// Path as resolved, or `None` for a config passed as a string.
+    pub path: Option<String>,
+    pub format: Format,
+    pub text: String,
+}
+
+/// The files making up a config, merged into one YAML document. Problems reading, parsing or
+/// including files are collected in [`issues`](Self::issues) rather than stopping the load.
+#[derive(Debug, Default)]
+pub struct ConfigSources {
+    /// Every document read, in merge order.
+    pub files: Vec<SourceFile>,
+    pub issues: Vec<ValidationIssue>,
+    merged: Mapping,
+    /// Merged config path -> index of the file that last set it. Paths within the file are the
+    /// same, except for rules.
+    origins: HashMap<String, usize>,
+    /// `<section>.rules` -> file index and path within that file of each merged rule.
+    rule_origins: HashMap<String, Vec<(usize, String)>>,
+}
+
+impl ConfigSources {
+    /// Loads the config at `path`, a file or a directory, with the overlay named by
+    /// [`ENV_VAR`].
+    pub fn load(path: &str) -> Self {
+        let env = std::env::var(ENV_VAR).ok().filter(|env| !env.is_empty());
+        Self::load_for_env(path, env.as_deref())
+    }
+
+    /// Loads the config at `path` with the overlay for `env`, if any.
+    ///
+    /// A file `config.yaml` is followed by `config.<env>.yaml` from the same directory, if that
+    /// exists. A directory contributes its config files (`*.yaml`, `*.yml`, `*.json` and
+    /// `*.toml`) in name order, skipping overlays (`name.<env>.yaml`), then the overlays for
+    /// `env` in name order.
+    pub fn load_for_env(path: &str, env: Option<&str>) -> Self {
+        let mut sources = Self::default();
+        let path = Path::new(path);
+        if path.is_dir() {
+            match config_files(path) {
+                Ok(files) => {
+                    let (overlays, base): (Vec<_>, Vec<_>) = files.into_iter().partition(|file| overlay_env(file).is_some());
+                    if base.is_empty() {
+                        sources.load_error(Some(path), "no config files in directory".to_string());
+                    }
+                    for file in base {
+                        sources.add_file(&file, &mut Vec::new());
+                    }
+                    for file in overlays.iter().filter(|file| env.is_some() && overlay_env(file) == env) {
+                        sources.add_file(file, &mut Vec::new());
+                    }
+                }
+                Err(e) => sources.load_error(Some(path), format!("cannot read config directory: {}", e)),
+            }
+        } else {
+            sources.add_file(path, &mut Vec::new());
+            if let Some(overlay) = env.and_then(|env| overlay_path(path, env)).filter(|overlay| overlay.is_file()) {
+                sources.add_file(&overlay, &mut Vec::new());
+            }
+        }
+        sources
+    }
+
+    /// A config given as YAML (or JSON) text. Includes resolve relative to the working
+    /// directory.
+    pub fn from_text(text: &str) -> Self {
+        Self::from_text_as(text, Format::Yaml)
+    }
+
+    /// A config given as text in `format`.
+    pub fn from_text_as(text: &str, format: Format) -> Self {
+        let mut sources = Self::default();
+        sources.add_text(None, format, text.to_string(), Path::new(""), &mut Vec::new());
+        sources
+    }
+
+    /// The paths of the files read, in merge order.
+    pub fn paths(&self) -> Vec<String> {
+        self.files.iter().filter_map(|file| file.path.clone()).collect()
+    }
+
+    /// The merged document.
+    pub fn merged(&self) -> Value {
+        Value::Mapping(self.merged.clone())
+    }
+
+    /// Records a problem found at `path` in file `index`.
+    fn issue(&mut self, severity: Severity, index: usize, path: &str, message: String) {
+        let file = &self.files[index];
+        let mut issue = ValidationIssue::new(severity, path, message);
+        issue.file = file.path.clone();
+        if let Some((line, column)) = file.format.positions(&file.text).and_then(|positions| positions.find(path)) {
+            issue.line = Some(line);
+            issue.column = Some(column);
+        }
+        self.issues.push(issue);
+    }
+
+    /// Records a problem not tied to a file read so far.
+    fn load_error(&mut self, file: Option<&Path>, message: String) {
+        let mut issue = ValidationIssue::new(Severity::Error, "", message);
+        issue.file = file.map(|file| file.display().to_string());
+        self.issues.push(issue);
+    }
+
+    /// Reads and merges the file at `path`. `chain` holds the files including it.
+    fn add_file(&mut self, path: &Path, chain: &mut Vec<PathBuf>) {
+        match std::fs::read_to_string(path) {
+            Ok(text) => {
+                chain.push(path.canonicalize().unwrap_or_else(|_| path.to_path_buf()));
+                let dir = path.parent().unwrap_or(Path::new(""));
+                self.add_text(Some(path.display().to_string()), Format::from_path(path), text, dir, chain);
+                chain.pop();
+            }
+            Err(e) => self.load_error(Some(path), format!("cannot read config: {}", e)),
+        }
+    }
+
+    fn add_text(&mut self, path: Option<String>, format: Format, text: String, dir: &Path, chain: &mut Vec<PathBuf>) {
+        let parsed = format.parse(&text);
+        let index = self.files.len();
+        self.files.push(SourceFile { path: path.clone(), format, text });
+        let mut document = match parsed {
+            Ok(Value::Mapping(document)) => document,
+            Ok(Value::Null) => Mapping::new(),
+            Ok(_) => return self.issue(Severity::Error, index, "", "config must be a mapping".to_string()),
+            Err(mut issue) => {
+                issue.file = path;
+                return self.issues.push(issue);
+            }
+        };
+        let includes = document.remove("include");
+        self.merge_mapping(index, &mut document, "");
+        let merged = std::mem::take(&mut self.merged);
+        self.merged = merge_into(merged, document);
+
+        let patterns = match includes {
+            None | Some(Value::Null) => Vec::new(),
+            Some(Value::String(pattern)) => vec![pattern],
+            Some(Value::Sequence(items)) if items.iter().all(Value::is_string) => {
+                items.into_iter().filter_map(|item| item.as_str().map(str::to_string)).collect()
+            }
+            Some(_) => {
+                let message = "include must be a path or a list of paths".to_string();
+                return self.issue(Severity::Error, index, "include", message);
+            }
+        };
+        for (i, pattern) in patterns.iter().enumerate() {
+            let issue_path = format!("include[{}]", i);
+            let full = dir.join(pattern);
+            let matches = match glob::glob(&full.to_string_lossy()) {
+                Ok(paths) => paths.filter_map(Result::ok).collect::<Vec<_>>(),
+                Err(e) => {
+                    self.issue(Severity::Error, index, &issue_path, format!("invalid include pattern '{}': {}", pattern, e));
+                    continue;
+                }
+            };
+            if matches.is_empty() {
+                let (severity, message) = if is_glob(pattern) {
+                    (Severity::Warning, format!("include pattern '{}' matches no files", pattern))
+                } else {
+                    (Severity::Error, format!("included file '{}' not found", full.display()))
+                };
+                self.issue(severity, index, &issue_path, message);
+            }
+            for file in matches {
+                if chain.contains(&file.canonicalize().unwrap_or_else(|_| file.clone())) {
+                    let message = format!("include cycle: '{}' is already being loaded", file.display());
+                    self.issue(Severity::Error, index, &issue_path, message);
+                    continue;
+                }
+                self.add_file(&file, chain);
+            }
+        }
+    }
+
+    /// Prepares `document` from file `index` for merging at `path`: records where each value
+    /// came from, and merges rule lists by name into the current ones.
+    fn merge_mapping(&mut self, index: usize, document: &mut Mapping, path: &str) {
+        for (key, value) in document.iter_mut() {
+            let Some(key) = key.as_str() else { continue };
+            let child = if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) };
+            if key == "rules" && !path.is_empty() && !path.contains('.') {
+                if let Value::Sequence(rules) = value {
+                    *value = Value::Sequence(self.merge_rules(index, std::mem::take(rules), &child));
+                    continue;
+                }
+            }
+            self.origins.insert(child.clone(), index);
+            if let Value::Mapping(mapping) = value {
+                self.merge_mapping(index, mapping, &child);
+            }
+        }
+    }
+
+    /// Merges `rules` from file `index` into the current rule list at `path`, returning the
+    /// result. A rule replaces, in place, an earlier file's rule of the same name, or is
+    /// appended; `remove: true` deletes the earlier rule instead.
+    fn merge_rules(&mut self, index: usize, rules: Vec<Value>, path: &str) -> Vec<Value> {
+        let mut merged = match self.merged_at(path) {
+            Some(Value::Sequence(current)) => current.clone(),
+            _ => Vec::new(),
+        };
+        let mut origins = self.rule_origins.remove(path).unwrap_or_default();
+        let source = self.files[index].path.clone();
+        for (i, mut rule) in rules.into_iter().enumerate() {
+            let rule_path = format!("{}[{}]", path, i);
+            let mut remove = false;
+            if let Value::Mapping(fields) = &mut rule {
+                remove = fields.remove("remove").and_then(|v| v.as_bool()).unwrap_or(false);
+                if let Some(source) = &source {
+                    fields.insert(Value::from("source"), Value::from(source.as_str()));
+                }
+            }
+            let name = rule.get("name").and_then(Value::as_str).map(str::to_string);
+            // Only rules from earlier files are replaced; duplicates within a file are kept.
+            let existing = name.as_deref().and_then(|name| {
+                (0..merged.len()).find(|&j| {
+                    origins.get(j).map_or(true, |origin| origin.0 != index)
+                        && merged[j].get("name").and_then(Value::as_str) == Some(name)
+                })
+            });
+            match (existing, remove) {
+                (Some(j), true) => {
+                    merged.remove(j);
+                    if j < origins.len() {
+                        origins.remove(j);
+                    }
+                }
+                (None, true) => {
+                    let message = format!("no earlier rule named '{}' to remove", name.unwrap_or_default());
+                    self.issue(Severity::Warning, index, &rule_path, message);
+                }
+                (Some(j), false) => {
+                    merged[j] = rule;
+                    if let Some(origin) = origins.get_mut(j) {
+                        *origin = (index, rule_path);
+                    }
+                }
+                (None, false) => {
+                    merged.push(rule);
+                    origins.push((index, rule_path));
+                }
+            }
+        }
+        self.rule_origins.insert(path.to_string(), origins);
+        merged
+    }
+
+    fn merged_at(&self, path: &str) -> Option<&Value> {
+        let mut parts = path.split('.');
+        let mut value = self.merged.get(parts.next()?)?;
+        for part in parts {
+            value = value.get(part)?;
+        }
+        Some(value)
+    }
+
+    /// Fills in the file, line and column of each issue from its merged config path, falling
+    /// back to the nearest enclosing value found in a file.
+    pub(super) fn locate(&self, issues: &mut [ValidationIssue]) {
+        let indexes: Vec<Option<PositionIndex>> = self.files.iter().map(|file| file.format.positions(&file.text)).collect();
+        for issue in issues.iter_mut().filter(|issue| issue.line.is_none() && !issue.path.is_empty()) {
+            let Some((file, path)) = self.origin(&issue.path) else { continue };
+            if issue.file.is_none() {
+                issue.file = self.files[file].path.clone();
+            }
+            if let Some((line, column)) = indexes[file].as_ref().and_then(|index| index.find(&path)) {
+                issue.line = Some(line);
+                issue.column = Some(column);
+            }
+        }
+    }
+
+    /// The file that set the value at merged `path`, and the value's path in that file.
+    fn origin(&self, path: &str) -> Option<(usize, String)> {
+        let mut prefix = path;
+        loop {
+            if let Some(origin) = self.rule_origin(prefix) {
+                return Some((origin.0, format!("{}{}", origin.1, &path[prefix.len()..])));
+            }
+            if let Some(&file) = self.origins.get(prefix) {
+                return Some((file, path.to_string()));
+            }
+            prefix = &prefix[..prefix.rfind(['.', '['])?];
+        }
+    }
+
+    fn rule_origin(&self, path: &str) -> Option<&(usize, String)> {
+        let (list, index) = path.strip_suffix(']')?.rsplit_once('[')?;
+        self.rule_origins.get(list)?.get(index.parse::<usize>().ok()?)
+    }
+}
+
+/// Merges `overlay` into `base`: mappings key by key, anything else replaced.
+fn merge_into(mut base: Mapping, overlay: Mapping) -> Mapping {
+    for (key, value) in overlay {
+        let merged = match (base.remove(&key), value) {
+            (Some(Value::Mapping(current)), Value::Mapping(value)) => Value::Mapping(merge_into(current, value)),
+            (_, value) => value,
+        };
+        base.insert(key, merged);
+    }
+    base
+}
+
+/// Whether `path` names a config file: `*.yaml`, `*.yml`, `*.json` or `*.toml`.
+pub fn is_config_file(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "yaml" || ext == "yml" || ext == "json" || ext == "toml")
+}
+
+/// Config files directly in `dir`, sorted by name.
+fn config_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if is_config_file(&path) && path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The environment of an overlay file such as `config.prod.yaml`, or `None` for a base file.
+fn overlay_env(path: &Path) -> Option<&str> {
+    let stem = path.file_stem()?.to_str()?;
+    stem.rsplit_once('.').map(|(_, env)| env)
+}
+
+/// `dir/name.<env>.ext` for `dir/name.ext`.
+pub fn overlay_path(path: &Path, env: &str) -> Option<PathBuf> {
+    let stem = path.file_stem()?.to_str()?;
+    let name = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => format!("{}.{}.{}", stem, env, ext),
+        None => format!("{}.{}", stem, env),
+    };
+    Some(path.with_file_name(name))
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(['*', '?', '['])
+}
+
+/// The 1-based line and column of byte `offset` in `text`.
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+}
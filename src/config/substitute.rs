@@ -0,0 +1,241 @@
+//! Placeholder substitution in config values: `${VAR}`, `${VAR:-default}` and `${file:/path}`.
+//!
+//! Substitution runs on the merged YAML document before it is deserialized, so it reaches
+//! every string value. Values read with `${file:...}` are secrets and are masked by
+//! [`Config::masked_json`].
+
+use regex::{Captures, Regex};
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::de::{Deserializer, IntoDeserializer, Visitor};
+use serde::forward_to_deserialize_any;
+use serde_norway::Value;
+use std::collections::BTreeSet;
+use std::sync::OnceLock;
+
+use super::validate::{Severity, ValidationIssue};
+use super::Config;
+
+/// Replacement for secret values in [`Config::masked_json`].
+pub const MASK: &str = "********";
+
+fn placeholder() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\$(\$?)\{([^}]*)\}").unwrap())
+}
+
+/// The result of substituting one string.
+struct Substituted {
+    value: String,
+    /// Set when part of the value was read with `${file:...}`.
+    secret: bool,
+}
+
+/// Substitutes the placeholders in `s`. `$${...}` is left as a literal `${...}`.
+///
+/// - `${VAR}`: the environment variable
+/// - `${VAR:-default}`: the variable, or `default` if it is unset or empty
+/// - `${file:/path}`: the file's contents without trailing newlines (a secret)
+///
+/// An unset variable or unreadable file is an error if `strict`, and left as is otherwise.
+fn substitute(s: &str, strict: bool) -> Result<Substituted, String> {
+    if !s.contains('$') {
+        return Ok(Substituted { value: s.to_string(), secret: false });
+    }
+    let mut error = None;
+    let mut secret = false;
+    let value = placeholder().replace_all(s, |caps: &Captures| {
+        let inner = &caps[2];
+        if !caps[1].is_empty() {
+            return format!("${{{}}}", inner);
+        }
+        if let Some(path) = inner.strip_prefix("file:") {
+            secret = true;
+            return match std::fs::read_to_string(path) {
+                Ok(contents) => contents.trim_end_matches(['\r', '\n']).to_string(),
+                Err(_) if !strict => caps[0].to_string(),
+                Err(e) => {
+                    error.get_or_insert(format!("cannot read secret file '{}': {}", path, e));
+                    String::new()
+                }
+            };
+        }
+        let (name, default) = match inner.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (inner, None),
+        };
+        match (std::env::var(name).ok().filter(|v| !v.is_empty() || default.is_none()), default) {
+            (Some(value), _) => value,
+            (None, Some(default)) => default.to_string(),
+            (None, None) if strict => {
+                error.get_or_insert(format!("environment variable '{}' is not set and has no default", name));
+                String::new()
+            }
+            (None, None) => caps[0].to_string(),
+        }
+    });
+    match error {
+        Some(error) => Err(error),
+        None => Ok(Substituted { value: value.into_owned(), secret }),
+    }
+}
+
+impl Config {
+    /// Substitutes `${VAR}`, `${VAR:-default}` and `${file:/path}` placeholders in `s`,
+    /// leaving references to unset variables (and unreadable files) as they are.
+    pub fn substitute_env_in_string(s: &str) -> String {
+        substitute(s, false).map_or_else(|_| s.to_string(), |substituted| substituted.value)
+    }
+
+    /// The config as JSON, as served by `GET /config`, with values read from secret files
+    /// replaced by [`MASK`].
+    pub fn masked_json(&self) -> serde_json::Value {
+        let mut json = serde_json::to_value(self).unwrap_or_default();
+        for path in &self.secret_paths {
+            if let Some(value) = json.pointer_mut(&json_pointer(path)) {
+                *value = serde_json::Value::from(MASK);
+            }
+        }
+        json
+    }
+}
+
+/// Substitutes placeholders in every string value of `document` (mapping keys are left
+/// alone). Returns the paths of values that hold secrets; unresolvable placeholders are
+/// reported as errors.
+pub(super) fn substitute_document(document: &mut Value, issues: &mut Vec<ValidationIssue>) -> BTreeSet<String> {
+    let mut secrets = BTreeSet::new();
+    substitute_value(document, "", &mut secrets, issues);
+    secrets
+}
+
+fn substitute_value(value: &mut Value, path: &str, secrets: &mut BTreeSet<String>, issues: &mut Vec<ValidationIssue>) {
+    match value {
+        Value::String(s) => match substitute(s, true) {
+            Ok(substituted) => {
+                if substituted.secret {
+                    secrets.insert(path.to_string());
+                }
+                *s = substituted.value;
+            }
+            Err(e) => issues.push(ValidationIssue::new(Severity::Error, path, e)),
+        },
+        Value::Sequence(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                substitute_value(item, &format!("{}[{}]", path, i), secrets, issues);
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (key, item) in mapping.iter_mut() {
+                let Some(key) = key.as_str() else { continue };
+                let child = if path.is_empty() { key.to_string() } else { format!("{}.{}", path, key) };
+                substitute_value(item, &child, secrets, issues);
+            }
+        }
+        Value::Tagged(tagged) => substitute_value(&mut tagged.value, path, secrets, issues),
+        _ => {}
+    }
+}
+
+/// `auth.api_keys[0].key` as the JSON pointer `/auth/api_keys/0/key`.
+fn json_pointer(path: &str) -> String {
+    let mut pointer = String::new();
+    for part in path.split('.') {
+        let mut parts = part.split('[');
+        if let Some(key) = parts.next() {
+            pointer.push('/');
+            pointer.push_str(&key.replace('~', "~0").replace('/', "~1"));
+        }
+        for index in parts {
+            pointer.push('/');
+            pointer.push_str(index.trim_end_matches(']'));
+        }
+    }
+    pointer
+}
+
+/// Deserializes a YAML value as [`Value`] does, except that strings also fill numeric and
+/// boolean fields, so a substituted `port: ${PORT}` loads as a number.
+pub(super) struct Lenient(pub Value);
+
+impl<'de> IntoDeserializer<'de, serde_norway::Error> for Lenient {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+macro_rules! parse_strings {
+    ($($method:ident => $visit:ident: $ty:ty),* $(,)?) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+            match self.0 {
+                Value::String(s) => match s.trim().parse::<$ty>() {
+                    Ok(parsed) => visitor.$visit(parsed),
+                    Err(_) => Value::String(s).$method(visitor),
+                },
+                other => other.$method(visitor),
+            }
+        }
+    )*};
+}
+
+impl<'de> Deserializer<'de> for Lenient {
+    type Error = serde_norway::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Sequence(items) => {
+                let mut seq = SeqDeserializer::new(items.into_iter().map(Lenient));
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            Value::Mapping(mapping) => {
+                let mut map = MapDeserializer::new(mapping.into_iter().map(|(k, v)| (Lenient(k), Lenient(v))));
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            other => other.deserialize_any(visitor),
+        }
+    }
+
+    parse_strings! {
+        deserialize_bool => visit_bool: bool,
+        deserialize_i8 => visit_i8: i8,
+        deserialize_i16 => visit_i16: i16,
+        deserialize_i32 => visit_i32: i32,
+        deserialize_i64 => visit_i64: i64,
+        deserialize_u8 => visit_u8: u8,
+        deserialize_u16 => visit_u16: u16,
+        deserialize_u32 => visit_u32: u32,
+        deserialize_u64 => visit_u64: u64,
+        deserialize_f32 => visit_f32: f32,
+        deserialize_f64 => visit_f64: f64,
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            other => visitor.visit_some(Lenient(other)),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0.deserialize_enum(name, variants, visitor)
+    }
+
+    forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
@@ -0,0 +1,693 @@
+//! Config validation. [`Config::check_sources`] reports every problem in a config at once,
+//! each located in the YAML file it came from, instead of stopping at the first.
+
+use serde::Serialize;
+use std::collections::HashMap;
+use yaml_rust2::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust2::scanner::Marker;
+
+use super::compiled::{compile_pattern, parse_network, parse_status_range, CompiledRules};
+use super::request::parse_duration_str;
+use super::sources::ConfigSources;
+use super::substitute::{substitute_document, Lenient};
+use super::{BodyCaptureOptions, BodyTransform, Config, HeaderTransform, MatchConditions, PatchOp, ResponseMatchConditions, RewriteRule, Sampling, REDIRECT_STATUSES};
+
+const KNOWN_METHODS: [&str; 9] = ["GET", "HEAD", "POST", "PUT", "DELETE", "CONNECT", "OPTIONS", "TRACE", "PATCH"];
+
+/// How serious a [`ValidationIssue`] is. Errors stop a config from loading; warnings are
+/// logged and the config is used anyway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A single problem found in a config.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    /// Location of the offending value in the config, e.g. `drop.rules[1].response.status_code`.
+    /// Empty when the problem is not tied to one value (such as a YAML syntax error).
+    pub path: String,
+    pub message: String,
+    /// The file the offending value came from, when the config was loaded from files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// 1-based line in the YAML source, when known.
+    pub line: Option<usize>,
+    /// 1-based column in the YAML source, when known.
+    pub column: Option<usize>,
+}
+
+impl ValidationIssue {
+    pub(super) fn new(severity: Severity, path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self { severity, path: path.into(), message: message.into(), file: None, line: None, column: None }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl std::fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file)?;
+            if self.line.is_none() {
+                write!(f, " ")?;
+            }
+        }
+        if let (Some(line), Some(column)) = (self.line, self.column) {
+            write!(f, "{}:{}: ", line, column)?;
+        }
+        write!(f, "{}: ", self.severity)?;
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// The errors that stopped a config from loading.
+#[derive(Debug)]
+pub struct ValidationErrors(pub Vec<ValidationIssue>);
+
+impl std::fmt::Display for ValidationErrors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0.as_slice() {
+            [issue] => write!(f, "{}", issue),
+            issues => {
+                write!(f, "{} config errors:", issues.len())?;
+                for issue in issues {
+                    write!(f, "\n  {}", issue)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+impl Config {
+    /// Parses, validates and loads a config from YAML `source`, collecting every problem
+    /// instead of stopping at the first. The config is returned if it parsed, even when it
+    /// has errors. File paths in the config, including `include:`, resolve relative to the
+    /// working directory.
+    pub fn check_source(source: &str) -> (Option<Config>, Vec<ValidationIssue>) {
+        Self::check_sources(&ConfigSources::from_text(source))
+    }
+
+    /// Like [`check_source`](Self::check_source), for a config assembled from files. Issues
+    /// name the file they were found in.
+    pub fn check_sources(sources: &ConfigSources) -> (Option<Config>, Vec<ValidationIssue>) {
+        let mut issues = sources.issues.clone();
+        if issues.iter().any(ValidationIssue::is_error) {
+            sort(&mut issues);
+            return (None, issues);
+        }
+        let mut document = sources.merged();
+        let raw = serde_json::to_value(&document).ok();
+        let secret_paths = substitute_document(&mut document, &mut issues);
+        if issues.iter().any(ValidationIssue::is_error) {
+            sources.locate(&mut issues);
+            sort(&mut issues);
+            return (None, issues);
+        }
+        let mut config: Config = match serde_path_to_error::deserialize(Lenient(document)) {
+            Ok(config) => config,
+            Err(e) => {
+                let path = match e.path().to_string() {
+                    path if path == "." => String::new(),
+                    path => path,
+                };
+                issues.push(ValidationIssue::new(Severity::Error, path, e.into_inner().to_string()));
+                sources.locate(&mut issues);
+                sort(&mut issues);
+                return (None, issues);
+            }
+        };
+        config.source_files = sources.paths();
+        config.secret_paths = secret_paths;
+        config.document = raw;
+        issues.extend(config.validate());
+        if !issues.iter().any(ValidationIssue::is_error) {
+            match CompiledRules::compile(&config) {
+                Ok(rules) => config.compiled = std::sync::OnceLock::from(rules),
+                Err(e) => issues.push(ValidationIssue::new(Severity::Error, "", e)),
+            }
+        }
+        if let Err(e) = config.mock.load_recordings() {
+            issues.push(ValidationIssue::new(Severity::Error, "mock.replay.file", e.to_string()));
+        }
+        if let Err(e) = config.auth.load() {
+            issues.push(ValidationIssue::new(Severity::Error, "auth", e.to_string()));
+        }
+        if let Err(e) = config.admin.token_hash() {
+            issues.push(ValidationIssue::new(Severity::Error, "admin.bearer_token_sha256", e));
+        }
+        sources.locate(&mut issues);
+        sort(&mut issues);
+        (Some(config), issues)
+    }
+
+    /// Checks rule patterns, status codes, durations, methods, header names, rule names and
+    /// rule order.
+    /// Issues carry their config path but no source location.
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut v = Validator::default();
+
+        let logging: Vec<_> = self.logging.rules.iter().map(|r| (r.name.as_str(), &r.match_conditions)).collect();
+        v.rule_section("logging.rules", &logging, true);
+        for (i, rule) in self.logging.rules.iter().enumerate() {
+            v.duration(&format!("logging.rules[{}].timeout", i), rule.timeout.as_deref());
+            v.body_capture(&format!("logging.rules[{}].capture", i), &rule.capture.body_options);
+            v.sampling(&format!("logging.rules[{}]", i), &rule.sampling);
+        }
+
+        let drop: Vec<_> = self.drop.rules.iter().map(|r| (r.name.as_str(), &r.match_conditions)).collect();
+        v.rule_section("drop.rules", &drop, true);
+        for (i, rule) in self.drop.rules.iter().enumerate() {
+            v.status_code(&format!("drop.rules[{}].response.status_code", i), rule.response.status_code);
+        }
+
+        let mock: Vec<_> = self.mock.rules.iter().map(|r| (r.name.as_str(), &r.match_conditions)).collect();
+        v.rule_section("mock.rules", &mock, true);
+        for (i, rule) in self.mock.rules.iter().enumerate() {
+            v.status_code(&format!("mock.rules[{}].response.status_code", i), rule.response.status_code);
+            v.duration(&format!("mock.rules[{}].response.delay", i), rule.response.delay.as_deref());
+        }
+
+        let faults: Vec<_> = self.faults.rules.iter().map(|r| (r.name.as_str(), &r.match_conditions)).collect();
+        v.rule_section("faults.rules", &faults, true);
+        for (i, rule) in self.faults.rules.iter().enumerate() {
+            if !(0.0..=100.0).contains(&rule.percentage) {
+                v.error(format!("faults.rules[{}].percentage", i), format!("Invalid percentage {}: must be between 0 and 100", rule.percentage));
+            }
+            if let Some(error) = &rule.error {
+                v.status_code(&format!("faults.rules[{}].error.status_code", i), error.status_code);
+            }
+            if let Some(latency) = &rule.latency {
+                v.duration(&format!("faults.rules[{}].latency.fixed", i), latency.fixed.as_deref());
+                v.duration(&format!("faults.rules[{}].latency.min", i), latency.min.as_deref());
+                v.duration(&format!("faults.rules[{}].latency.max", i), latency.max.as_deref());
+            }
+        }
+
+        // Every matching rate limit applies, so an earlier catch-all shadows nothing
+        let rate_limits: Vec<_> = self.rate_limits.rules.iter().map(|r| (r.name.as_str(), &r.match_conditions)).collect();
+        v.rule_section("rate_limits.rules", &rate_limits, false);
+        for (i, rule) in self.rate_limits.rules.iter().enumerate() {
+            let path = format!("rate_limits.rules[{}]", i);
+            v.duration(&format!("{}.window", path), Some(&rule.window));
+            if rule.parse_window().is_some_and(|window| window.is_zero()) {
+                v.error(format!("{}.window", path), format!("Invalid window '{}': must be longer than zero", rule.window));
+            }
+            if rule.limit < 1 {
+                v.error(format!("{}.limit", path), "Invalid limit 0: must be at least 1");
+            }
+        }
+        v.duration("rate_limits.snapshot_interval", self.rate_limits.snapshot_interval.as_deref());
+
+        let rewrites: Vec<_> = self.rewrites.rules.iter().map(|r| (r.name.as_str(), &r.match_conditions)).collect();
+        v.rule_section("rewrites.rules", &rewrites, false);
+        for (i, rule) in self.rewrites.rules.iter().enumerate() {
+            v.rewrite(&format!("rewrites.rules[{}]", i), rule);
+        }
+
+        // Every matching header rule applies, too
+        let headers: Vec<_> = self.headers.rules.iter().map(|r| (r.name.as_str(), &r.match_conditions)).collect();
+        v.rule_section("headers.rules", &headers, false);
+        for (i, rule) in self.headers.rules.iter().enumerate() {
+            v.header_transform(&format!("headers.rules[{}].request", i), &rule.request);
+            v.header_transform(&format!("headers.rules[{}].response", i), &rule.response);
+        }
+
+        let bodies: Vec<_> = self.bodies.rules.iter().map(|r| (r.name.as_str(), &r.match_conditions)).collect();
+        v.rule_section("bodies.rules", &bodies, false);
+        for (i, rule) in self.bodies.rules.iter().enumerate() {
+            v.body_transform(&format!("bodies.rules[{}].request", i), &rule.request);
+            v.body_transform(&format!("bodies.rules[{}].response", i), &rule.response);
+        }
+
+        v.conditions("auth.match_conditions", &self.auth.match_conditions);
+        v.networks("server.trusted_proxies", &self.server.trusted_proxies);
+        if let Some(jwt) = &self.auth.jwt {
+            v.duration("auth.jwt.leeway", jwt.leeway.as_deref());
+        }
+        v.duration("concurrency.max_wait", self.concurrency.max_wait.as_deref());
+        // A zero cap never frees a slot, so every request it covers is shed
+        v.concurrency_cap("concurrency.max_in_flight", self.concurrency.max_in_flight);
+        v.concurrency_cap("concurrency.per_upstream_host", self.concurrency.per_upstream_host);
+        let mut hosts: Vec<_> = self.concurrency.upstream_hosts.iter().collect();
+        hosts.sort();
+        for (host, &cap) in hosts {
+            v.concurrency_cap(&format!("concurrency.upstream_hosts.{}", host), Some(cap));
+        }
+        v.duration("reload.debounce", self.reload.debounce.as_deref());
+
+        let response: Vec<_> = self.response_logging.rules.iter().map(|r| r.name.as_str()).collect();
+        v.names("response_logging.rules", &response);
+        let mut catch_all: Option<(usize, &str)> = None;
+        for (i, rule) in self.response_logging.rules.iter().enumerate() {
+            let path = format!("response_logging.rules[{}]", i);
+            v.response_conditions(&format!("{}.match_conditions", path), &rule.match_conditions);
+            v.body_capture(&format!("{}.capture", path), &rule.capture.body_options);
+            v.sampling(&path, &rule.sampling);
+            v.shadowed(&path, &rule.name, "response_logging.rules", catch_all);
+            if catch_all.is_none() && response_matches_everything(&rule.match_conditions) {
+                catch_all = Some((i, &rule.name));
+            }
+        }
+
+        v.issues
+    }
+}
+
+#[derive(Default)]
+struct Validator {
+    issues: Vec<ValidationIssue>,
+}
+
+impl Validator {
+    fn error(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.issues.push(ValidationIssue::new(Severity::Error, path, message));
+    }
+
+    fn warning(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.issues.push(ValidationIssue::new(Severity::Warning, path, message));
+    }
+
+    fn pattern(&mut self, path: String, kind: &str, pattern: &str) {
+        if let Err(e) = compile_pattern(kind, pattern, true) {
+            self.error(path, e);
+        }
+    }
+
+    fn networks(&mut self, path: &str, networks: &[String]) {
+        for (i, network) in networks.iter().enumerate() {
+            if let Err(e) = parse_network(network) {
+                self.error(format!("{}[{}]", path, i), e);
+            }
+        }
+    }
+
+    fn duration(&mut self, path: &str, value: Option<&str>) {
+        if let Some(value) = value {
+            if parse_duration_str(value).is_none() {
+                self.error(path, format!("Invalid duration '{}': expected a whole number followed by 'ms' or 's'", value));
+            }
+        }
+    }
+
+    fn concurrency_cap(&mut self, path: &str, cap: Option<usize>) {
+        if cap == Some(0) {
+            self.error(path, "Invalid limit 0: must be at least 1");
+        }
+    }
+
+    fn status_code(&mut self, path: &str, code: u16) {
+        if axum::http::StatusCode::from_u16(code).is_err() {
+            self.error(path, format!("Invalid HTTP status code {}: must be between 100 and 999", code));
+        }
+    }
+
+    fn rewrite(&mut self, path: &str, rule: &RewriteRule) {
+        match &rule.pattern {
+            Some(pattern) => self.pattern(format!("{}.pattern", path), "rewrite", pattern),
+            None if rule.replacement.is_some() => {
+                self.error(format!("{}.replacement", path), "replacement requires a pattern");
+            }
+            None => {}
+        }
+        if let Some(host) = &rule.host {
+            let valid = format!("http://{}", host)
+                .parse::<reqwest::Url>()
+                .is_ok_and(|url| url.host_str().is_some() && url.path() == "/" && url.query().is_none());
+            if !valid {
+                self.error(format!("{}.host", path), format!("Invalid host '{}': expected 'host' or 'host:port'", host));
+            }
+        }
+        if let Some(status) = rule.redirect {
+            if !REDIRECT_STATUSES.contains(&status) {
+                self.error(format!("{}.redirect", path), format!("Invalid redirect status {}: must be 301, 302, 307 or 308", status));
+            }
+        }
+        if rule.replacement.is_none() && rule.host.is_none() {
+            self.warning(path, format!("Rewrite rule '{}' does not change the URL: set replacement or host", rule.name));
+        }
+    }
+
+    fn header_name(&mut self, path: String, name: &str) {
+        if axum::http::HeaderName::from_bytes(name.as_bytes()).is_err() {
+            self.error(path, format!("Invalid header name '{}'", name));
+        }
+    }
+
+    fn header_transform(&mut self, path: &str, transform: &HeaderTransform) {
+        for (i, name) in transform.remove.iter().enumerate() {
+            self.header_name(format!("{}.remove[{}]", path, i), name);
+        }
+        for (from, to) in &transform.rename {
+            self.header_name(format!("{}.rename.{}", path, from), from);
+            self.header_name(format!("{}.rename.{}", path, from), to);
+        }
+        for (kind, values) in [("set", &transform.set), ("add", &transform.add)] {
+            for (name, value) in values {
+                let value_path = format!("{}.{}.{}", path, kind, name);
+                self.header_name(value_path.clone(), name);
+                // Templates are checked once rendered, per request
+                if !value.contains("{{") && axum::http::HeaderValue::from_str(value).is_err() {
+                    self.error(value_path, format!("Invalid value for header '{}'", name));
+                }
+            }
+        }
+    }
+
+    fn body_transform(&mut self, path: &str, transform: &BodyTransform) {
+        for (i, operation) in transform.json_patch.iter().enumerate() {
+            let op_path = format!("{}.json_patch[{}]", path, i);
+            if !operation.path.is_empty() && !operation.path.starts_with('/') {
+                self.error(format!("{}.path", op_path), format!("Invalid JSON Pointer '{}': must be empty or start with '/'", operation.path));
+            }
+            match operation.op {
+                PatchOp::Add | PatchOp::Replace if operation.value.is_none() => {
+                    self.error(op_path, "add and replace operations require a value");
+                }
+                PatchOp::Remove if operation.path.is_empty() => {
+                    self.error(format!("{}.path", op_path), "remove cannot target the whole document");
+                }
+                _ => {}
+            }
+        }
+        for (i, replace) in transform.replace.iter().enumerate() {
+            self.pattern(format!("{}.replace[{}].pattern", path, i), "body replace", &replace.pattern);
+        }
+    }
+
+    fn body_capture(&mut self, path: &str, options: &BodyCaptureOptions) {
+        for (field, patterns) in [("body_content_types", &options.body_content_types), ("skip_body_content_types", &options.skip_body_content_types)] {
+            for (i, pattern) in patterns.iter().enumerate() {
+                if !pattern.contains('/') {
+                    self.warning(format!("{}.{}[{}]", path, field, i), format!("Content type '{}' never matches: expected 'type/subtype' or 'type/*'", pattern));
+                }
+            }
+        }
+    }
+
+    fn sampling(&mut self, path: &str, sampling: &Sampling) {
+        if let Some(rate) = sampling.sample_rate {
+            if !(0.0..=1.0).contains(&rate) {
+                self.error(format!("{}.sample_rate", path), format!("Invalid sample rate {}: must be between 0 and 1", rate));
+            }
+        }
+        if sampling.sample_every == Some(0) {
+            self.error(format!("{}.sample_every", path), "Invalid sample_every 0: must be at least 1");
+        }
+        if sampling.sample_rate.is_some() && sampling.sample_every.is_some() {
+            self.warning(format!("{}.sample_rate", path), "`sample_every` takes priority; `sample_rate` is ignored");
+        }
+        if !sampling.is_enabled() {
+            if sampling.sample_key.is_some() {
+                self.warning(format!("{}.sample_key", path), "`sample_key` has no effect without `sample_rate` or `sample_every`");
+            }
+            if sampling.always_log_errors {
+                self.warning(format!("{}.always_log_errors", path), "`always_log_errors` has no effect without `sample_rate` or `sample_every`");
+            }
+        }
+    }
+
+    fn conditions(&mut self, path: &str, conditions: &MatchConditions) {
+        for (i, method) in conditions.methods.iter().enumerate() {
+            if !KNOWN_METHODS.iter().any(|known| known.eq_ignore_ascii_case(method)) {
+                self.warning(format!("{}.methods[{}]", path, i), format!("Unknown HTTP method '{}'", method));
+            }
+        }
+        for (i, pattern) in conditions.path.patterns.iter().enumerate() {
+            self.pattern(format!("{}.path.patterns[{}]", path, i), "path", pattern);
+        }
+        for (name, pattern) in &conditions.headers {
+            self.pattern(format!("{}.headers.{}", path, name), "header", pattern);
+        }
+        for (i, pattern) in conditions.body.patterns.iter().enumerate() {
+            self.pattern(format!("{}.body.patterns[{}]", path, i), "body", pattern);
+        }
+        for (name, pattern) in &conditions.claims {
+            self.pattern(format!("{}.claims.{}", path, name), "claim", pattern);
+        }
+        for (name, pattern) in &conditions.form {
+            self.pattern(format!("{}.form.{}", path, name), "form field", pattern);
+        }
+        for (i, part) in conditions.multipart.iter().enumerate() {
+            for (field, pattern) in [("name", &part.name), ("filename", &part.filename), ("content_type", &part.content_type)] {
+                if let Some(pattern) = pattern {
+                    self.pattern(format!("{}.multipart[{}].{}", path, i, field), "multipart", pattern);
+                }
+            }
+        }
+        if let Some(pattern) = conditions.graphql.as_ref().and_then(|g| g.operation_name.as_ref()) {
+            self.pattern(format!("{}.graphql.operation_name", path), "GraphQL operation name", pattern);
+        }
+        self.networks(&format!("{}.client_ip", path), &conditions.client_ip);
+        for (i, nested) in conditions.all.iter().enumerate() {
+            self.conditions(&format!("{}.all[{}]", path, i), nested);
+        }
+        for (i, nested) in conditions.any.iter().enumerate() {
+            self.conditions(&format!("{}.any[{}]", path, i), nested);
+        }
+        if let Some(nested) = &conditions.not {
+            self.conditions(&format!("{}.not", path), nested);
+            if matches_everything(nested) {
+                self.warning(format!("{}.not", path), "`not` of conditions that match every request never matches");
+            }
+        }
+    }
+
+    fn response_conditions(&mut self, path: &str, conditions: &ResponseMatchConditions) {
+        for (i, code) in conditions.status_codes.iter().enumerate() {
+            self.status_code(&format!("{}.status_codes[{}]", path, i), *code);
+        }
+        for (i, range) in conditions.status_ranges.iter().enumerate() {
+            if let Err(e) = parse_status_range(range) {
+                self.error(format!("{}.status_ranges[{}]", path, i), e);
+            }
+        }
+        self.duration(&format!("{}.min_latency", path), conditions.min_latency.as_deref());
+        self.duration(&format!("{}.max_latency", path), conditions.max_latency.as_deref());
+        let latency = |value: &Option<String>| value.as_deref().and_then(parse_duration_str);
+        if let (Some(min), Some(max)) = (latency(&conditions.min_latency), latency(&conditions.max_latency)) {
+            if min > max {
+                self.warning(format!("{}.min_latency", path), "`min_latency` is above `max_latency`; the rule never matches");
+            }
+        }
+        if let (Some(min), Some(max)) = (conditions.min_body_bytes, conditions.max_body_bytes) {
+            if min > max {
+                self.warning(format!("{}.min_body_bytes", path), "`min_body_bytes` is above `max_body_bytes`; the rule never matches");
+            }
+        }
+        for (name, pattern) in &conditions.headers {
+            self.pattern(format!("{}.headers.{}", path, name), "header", pattern);
+        }
+        for (i, pattern) in conditions.body.patterns.iter().enumerate() {
+            self.pattern(format!("{}.body.patterns[{}]", path, i), "body", pattern);
+        }
+        if let Some(request) = &conditions.request {
+            self.conditions(&format!("{}.request", path), request);
+        }
+        for (i, nested) in conditions.all.iter().enumerate() {
+            self.response_conditions(&format!("{}.all[{}]", path, i), nested);
+        }
+        for (i, nested) in conditions.any.iter().enumerate() {
+            self.response_conditions(&format!("{}.any[{}]", path, i), nested);
+        }
+        if let Some(nested) = &conditions.not {
+            self.response_conditions(&format!("{}.not", path), nested);
+            if response_matches_everything(nested) {
+                self.warning(format!("{}.not", path), "`not` of conditions that match every response never matches");
+            }
+        }
+    }
+
+    fn names(&mut self, section: &str, names: &[&str]) {
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        for (i, name) in names.iter().enumerate() {
+            match seen.get(name) {
+                Some(first) => self.warning(
+                    format!("{}[{}].name", section, i),
+                    format!("Duplicate rule name '{}' (first used by {}[{}])", name, section, first),
+                ),
+                None => {
+                    seen.insert(name, i);
+                }
+            }
+        }
+    }
+
+    fn shadowed(&mut self, path: &str, name: &str, section: &str, catch_all: Option<(usize, &str)>) {
+        if let Some((first, first_name)) = catch_all {
+            self.warning(
+                path,
+                format!(
+                    "Rule '{}' can never match: earlier rule '{}' ({}[{}]) matches every request",
+                    name, first_name, section, first
+                ),
+            );
+        }
+    }
+
+    /// Checks a section's rules. With `first_match_wins`, rules after a catch-all are reported
+    /// as unreachable.
+    fn rule_section(&mut self, section: &str, rules: &[(&str, &MatchConditions)], first_match_wins: bool) {
+        let names: Vec<_> = rules.iter().map(|(name, _)| *name).collect();
+        self.names(section, &names);
+        let mut catch_all = None;
+        for (i, (name, conditions)) in rules.iter().enumerate() {
+            let path = format!("{}[{}]", section, i);
+            self.conditions(&format!("{}.match_conditions", path), conditions);
+            if first_match_wins {
+                self.shadowed(&path, name, section, catch_all);
+                if catch_all.is_none() && matches_everything(conditions) {
+                    catch_all = Some((i, *name));
+                }
+            }
+        }
+    }
+}
+
+/// Whether any pattern in the list is one that matches every input (or the list is empty).
+fn patterns_match_everything(patterns: &[String]) -> bool {
+    patterns.is_empty()
+        || patterns.iter().any(|p| matches!(p.as_str(), "" | "^" | ".*" | "^.*" | ".*$" | "^.*$" | "(?s).*"))
+}
+
+fn matches_everything(conditions: &MatchConditions) -> bool {
+    conditions.methods.is_empty()
+        && conditions.headers.is_empty()
+        && conditions.claims.is_empty()
+        && conditions.form.is_empty()
+        && conditions.multipart.is_empty()
+        && conditions.graphql.is_none()
+        && conditions.client_ip.is_empty()
+        && patterns_match_everything(&conditions.path.patterns)
+        && patterns_match_everything(&conditions.body.patterns)
+        && conditions.all.iter().all(matches_everything)
+        && (conditions.any.is_empty() || conditions.any.iter().any(matches_everything))
+        && conditions.not.is_none()
+}
+
+fn response_matches_everything(conditions: &ResponseMatchConditions) -> bool {
+    conditions.status_codes.is_empty()
+        && conditions.status_ranges.is_empty()
+        && conditions.min_latency.is_none()
+        && conditions.max_latency.is_none()
+        && conditions.min_body_bytes.is_none()
+        && conditions.max_body_bytes.is_none()
+        && conditions.request.as_ref().map_or(true, matches_everything)
+        && conditions.headers.is_empty()
+        && patterns_match_everything(&conditions.body.patterns)
+        && conditions.all.iter().all(response_matches_everything)
+        && (conditions.any.is_empty() || conditions.any.iter().any(response_matches_everything))
+        && conditions.not.is_none()
+}
+
+/// Orders issues by file (in merge order) and position; unlocated issues last.
+fn sort(issues: &mut [ValidationIssue]) {
+    issues.sort_by_key(|issue| (issue.line.is_none(), issue.file.clone(), issue.line, issue.column));
+}
+
+enum Frame {
+    Mapping { path: String, key: Option<String> },
+    Sequence { path: String, next: usize },
+}
+
+/// Records the 1-based line and column of every node in a YAML document by config path.
+#[derive(Default)]
+pub(super) struct PositionIndex {
+    stack: Vec<Frame>,
+    positions: HashMap<String, (usize, usize)>,
+}
+
+impl PositionIndex {
+    /// Indexes `source`, or `None` if it is not valid YAML.
+    pub(super) fn parse(source: &str) -> Option<Self> {
+        let mut index = Self::default();
+        Parser::new_from_str(source).load(&mut index, false).ok()?;
+        Some(index)
+    }
+
+    /// The position of the value at `path`, or of the nearest enclosing value in the document.
+    pub(super) fn find(&self, mut path: &str) -> Option<(usize, usize)> {
+        loop {
+            if let Some(&position) = self.positions.get(path) {
+                return Some(position);
+            }
+            path = &path[..path.rfind(['.', '['])?];
+        }
+    }
+
+    /// The path of the node starting now, or `None` if the node is a mapping key.
+    fn next_path(&mut self) -> Option<String> {
+        match self.stack.last_mut() {
+            None => Some(String::new()),
+            Some(Frame::Mapping { key: None, .. }) => None,
+            Some(Frame::Mapping { path, key }) => {
+                let key = key.take().unwrap_or_default();
+                Some(if path.is_empty() { key } else { format!("{}.{}", path, key) })
+            }
+            Some(Frame::Sequence { path, next }) => {
+                *next += 1;
+                Some(format!("{}[{}]", path, *next - 1))
+            }
+        }
+    }
+
+    fn start(&mut self, scalar: Option<String>, container: Option<bool>, mark: Marker) {
+        let path = match self.next_path() {
+            Some(path) => {
+                self.positions.entry(path.clone()).or_insert((mark.line(), mark.col() + 1));
+                path
+            }
+            None => {
+                // A mapping key: scalars name the value that follows; complex keys are opaque
+                let key = scalar.unwrap_or_else(|| "?".to_string());
+                if let Some(Frame::Mapping { key: slot, .. }) = self.stack.last_mut() {
+                    *slot = Some(key.clone());
+                }
+                if container.is_none() {
+                    return;
+                }
+                format!("?{}", key)
+            }
+        };
+        match container {
+            Some(true) => self.stack.push(Frame::Mapping { path, key: None }),
+            Some(false) => self.stack.push(Frame::Sequence { path, next: 0 }),
+            None => {}
+        }
+    }
+}
+
+impl MarkedEventReceiver for PositionIndex {
+    fn on_event(&mut self, event: Event, mark: Marker) {
+        match event {
+            Event::Scalar(value, ..) => self.start(Some(value), None, mark),
+            Event::Alias(_) => self.start(None, None, mark),
+            Event::MappingStart(..) => self.start(None, Some(true), mark),
+            Event::SequenceStart(..) => self.start(None, Some(false), mark),
+            Event::MappingEnd | Event::SequenceEnd => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+    }
+}
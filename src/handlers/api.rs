@@ -1,13 +1,67 @@
 use axum::{
     body::Body,
-    extract::State,
-    http::StatusCode,
+    extract::{Path, Query, Request, State},
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        HeaderMap, StatusCode,
+    },
+    middleware::{self, Next},
     response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
 };
-use crate::config::ConfigHolder;
+use crate::config::{config_file_path, schema, Config, ConfigHolder, ConfigSources, Format};
+use crate::history::RollbackError;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
 use serde_json;
 use std::sync::Arc;
 
+/// Routes served on the admin listener, all behind [`require_admin_token`].
+pub fn admin_router(state: Arc<ConfigHolder>) -> Router {
+    Router::new()
+        .route("/health", get(get_health_check))
+        .route("/config", get(get_config).put(put_config))
+        .route("/config/history", get(get_config_history))
+        .route("/config/rollback/:version", post(rollback_config))
+        .route("/config/docs", get(get_config_docs))
+        .route("/config/schema", get(get_config_schema))
+        .route("/config/reload", post(reload_config))
+        .route("/config/test", post(super::test_config))
+        .route("/metrics", get(get_metrics))
+        .route_layer(middleware::from_fn_with_state(state.clone(), require_admin_token))
+        .with_state(state)
+}
+
+/// Rejects admin requests without the bearer token configured in `admin.bearer_token_sha256`.
+pub async fn require_admin_token(
+    State(config): State<Arc<ConfigHolder>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let expected = config.get().admin.token_hash();
+    let authorized = match expected {
+        Ok(None) => true,
+        Ok(Some(expected)) => req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(|token| crate::auth::constant_time_eq(&Sha256::digest(token.trim()), &expected))
+            .unwrap_or(false),
+        Err(_) => false,
+    };
+    if authorized {
+        return next.run(req).await;
+    }
+    (
+        StatusCode::UNAUTHORIZED,
+        [("content-type", "application/json"), ("www-authenticate", "Bearer realm=\"logprox-admin\"")],
+        serde_json::json!({"error": "Authentication required"}).to_string(),
+    )
+        .into_response()
+}
+
 pub async fn get_health_check() -> Response {
     Response::builder()
         .status(StatusCode::OK)
@@ -20,12 +74,12 @@ pub async fn get_config(State(config): State<Arc<ConfigHolder>>) -> impl IntoRes
     (
         StatusCode::OK,
         [("content-type", "application/json")],
-        serde_json::to_string_pretty(&*config).unwrap(),
+        serde_json::to_string_pretty(&config.masked_json()).unwrap(),
     )
 }
 
 pub async fn reload_config(State(config): State<Arc<ConfigHolder>>) -> impl IntoResponse {
-    match config.reload() {
+    match crate::reload::reload_and_log(&config, &config_file_path(), "api") {
         Ok(_) => (StatusCode::OK, "Configuration reloaded successfully".to_string()),
         Err(e) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -34,10 +88,83 @@ pub async fn reload_config(State(config): State<Arc<ConfigHolder>>) -> impl Into
     }
 }
 
+#[derive(Debug, Deserialize)]
+pub struct PutConfigParams {
+    /// Validate the config and report issues without installing it.
+    #[serde(default)]
+    pub validate_only: bool,
+}
+
+/// `PUT /config`: validates a YAML, JSON or TOML (`content-type: application/toml`) config
+/// from the request body and, unless `validate_only` is set, installs it as a new version. The
+/// config file is not written, so the next file reload replaces it.
+pub async fn put_config(
+    State(config): State<Arc<ConfigHolder>>,
+    Query(params): Query<PutConfigParams>,
+    headers: HeaderMap,
+    body: String,
+) -> Response {
+    let content_type = headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    // JSON is a subset of YAML, so one parser handles both.
+    let format = if content_type.contains("toml") { Format::Toml } else { Format::Yaml };
+    let (new_config, issues) = Config::check_sources(&ConfigSources::from_text_as(&body, format));
+    let new_config = match new_config {
+        Some(new_config) if !issues.iter().any(|issue| issue.is_error()) => new_config,
+        _ => {
+            let body = serde_json::json!({ "valid": false, "issues": issues });
+            return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+        }
+    };
+    if params.validate_only {
+        return Json(serde_json::json!({ "valid": true, "issues": issues })).into_response();
+    }
+    let version = config.install(new_config, "put");
+    Json(serde_json::json!({ "valid": true, "version": version, "issues": issues })).into_response()
+}
+
+/// `GET /config/history`: the active version and the versions kept for rollback.
+pub async fn get_config_history(State(config): State<Arc<ConfigHolder>>) -> impl IntoResponse {
+    let versions = config.history();
+    let current = versions.last().map_or(0, |v| v.version);
+    Json(serde_json::json!({ "current": current, "versions": versions }))
+}
+
+/// `POST /config/rollback/{version}`: re-installs a kept config as a new version.
+pub async fn rollback_config(State(config): State<Arc<ConfigHolder>>, Path(version): Path<u64>) -> Response {
+    match config.rollback(version) {
+        Ok(new_version) => Json(serde_json::json!({ "version": new_version, "rolled_back_to": version })).into_response(),
+        Err(e) => {
+            let status = match e {
+                RollbackError::UnknownVersion(_) => StatusCode::NOT_FOUND,
+                RollbackError::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            };
+            (status, Json(serde_json::json!({ "error": e.to_string() }))).into_response()
+        }
+    }
+}
+
+pub async fn get_metrics(State(config): State<Arc<ConfigHolder>>) -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        [("content-type", "application/json")],
+        serde_json::to_string_pretty(&config.metrics().snapshot()).unwrap(),
+    )
+}
+
+/// `GET /config/docs`: the config reference, with the field tables rendered from the schema.
 pub async fn get_config_docs() -> impl IntoResponse {
     (
         StatusCode::OK,
         [("content-type", "text/plain; charset=utf-8")],
-        include_str!("../../config_docs.md"),
+        schema::docs(),
+    )
+}
+
+/// `GET /config/schema`: the JSON Schema config files are checked against.
+pub async fn get_config_schema() -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        [("content-type", "application/schema+json")],
+        serde_json::to_string_pretty(&schema::schema()).unwrap_or_default(),
     )
 }
\ No newline at end of file
@@ -0,0 +1,166 @@
+use axum::body::Bytes;
+use axum::http::{header, HeaderMap, HeaderValue};
+use regex::Regex;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use super::template::TemplateContext;
+use crate::config::{BodyRule, BodyTransform, CompiledBodyRule, PatchOp};
+
+/// One rule's changes to one body, with its templates rendered.
+struct Edit {
+    rule: String,
+    template: Option<String>,
+    patch: Vec<(PatchOp, String, Value)>,
+    replace: Vec<(Regex, String)>,
+}
+
+/// The body changes of every rule matching one request, with their templates rendered.
+#[derive(Default)]
+pub struct BodyEdits {
+    request: Vec<Edit>,
+    response: Vec<Edit>,
+}
+
+impl BodyEdits {
+    /// Renders the transforms of `rules` (each with its compiled `replace` patterns and path
+    /// captures) against the request in `ctx`.
+    pub fn new(rules: Vec<(&BodyRule, &CompiledBodyRule, HashMap<String, String>)>, ctx: &mut TemplateContext) -> Self {
+        let mut edits = Self::default();
+        for (rule, compiled, captures) in rules {
+            ctx.captures = captures;
+            edits.request.extend(render(&rule.name, &rule.request, &compiled.request, ctx));
+            edits.response.extend(render(&rule.name, &rule.response, &compiled.response, ctx));
+        }
+        edits
+    }
+
+    /// The request body to forward upstream. `headers` get the new `Content-Length` if it
+    /// changed.
+    pub fn apply_request(&self, body: Bytes, headers: &mut HeaderMap) -> Bytes {
+        apply(&self.request, body, headers)
+    }
+
+    /// The response body to return to the client. `headers` get the new `Content-Length` if
+    /// it changed.
+    pub fn apply_response(&self, body: Bytes, headers: &mut HeaderMap) -> Bytes {
+        apply(&self.response, body, headers)
+    }
+}
+
+fn render(rule: &str, transform: &BodyTransform, replace: &[(Regex, String)], ctx: &TemplateContext) -> Option<Edit> {
+    if transform.is_empty() {
+        return None;
+    }
+    let patch = transform.json_patch.iter()
+        .map(|op| (op.op, op.path.clone(), op.value.as_ref().map(|v| render_value(v, ctx)).unwrap_or_default()))
+        .collect();
+    Some(Edit {
+        rule: rule.to_string(),
+        template: transform.template.as_ref().map(|t| ctx.render(t)),
+        patch,
+        replace: replace.to_vec(),
+    })
+}
+
+/// `value` with every string inside it rendered as a template.
+fn render_value(value: &Value, ctx: &TemplateContext) -> Value {
+    match value {
+        Value::String(s) => Value::String(ctx.render(s)),
+        Value::Array(items) => Value::Array(items.iter().map(|v| render_value(v, ctx)).collect()),
+        Value::Object(map) => Value::Object(map.iter().map(|(k, v)| (k.clone(), render_value(v, ctx))).collect()),
+        other => other.clone(),
+    }
+}
+
+fn apply(edits: &[Edit], body: Bytes, headers: &mut HeaderMap) -> Bytes {
+    if edits.is_empty() {
+        return body;
+    }
+    // Compressed bodies can't be edited as text; they pass through untouched
+    if headers.get(header::CONTENT_ENCODING).is_some_and(|v| v != "identity") {
+        tracing::debug!("body rules skipped for an encoded body");
+        return body;
+    }
+    let original = std::str::from_utf8(&body).ok();
+    // Binary bodies can only be replaced wholesale
+    if original.is_none() && edits.iter().all(|e| e.template.is_none()) {
+        return body;
+    }
+    let mut text = original.unwrap_or_default().to_string();
+    for edit in edits {
+        if let Some(template) = &edit.template {
+            text = template.clone();
+        }
+        if !edit.patch.is_empty() {
+            match serde_json::from_str::<Value>(&text) {
+                Ok(mut doc) => {
+                    for (op, pointer, value) in &edit.patch {
+                        if !patch(&mut doc, *op, pointer, value.clone()) {
+                            tracing::warn!(rule = %edit.rule, path = %pointer, "json_patch operation did not apply; skipped");
+                        }
+                    }
+                    text = doc.to_string();
+                }
+                Err(_) => tracing::debug!(rule = %edit.rule, "json_patch skipped: body is not JSON"),
+            }
+        }
+        for (re, replacement) in &edit.replace {
+            text = re.replace_all(&text, replacement.as_str()).into_owned();
+        }
+    }
+    if original == Some(text.as_str()) {
+        return body;
+    }
+    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(text.len()));
+    Bytes::from(text)
+}
+
+/// Applies one JSON Patch operation to `doc`. Returns `false` if `pointer` does not resolve.
+fn patch(doc: &mut Value, op: PatchOp, pointer: &str, value: Value) -> bool {
+    if pointer.is_empty() {
+        if op == PatchOp::Remove {
+            return false;
+        }
+        *doc = value;
+        return true;
+    }
+    let mut tokens: Vec<String> = pointer.split('/').skip(1).map(|t| t.replace("~1", "/").replace("~0", "~")).collect();
+    let Some(last) = tokens.pop() else {
+        return false;
+    };
+    let parent = tokens.iter().try_fold(doc, |node, token| match node {
+        Value::Object(map) => map.get_mut(token),
+        Value::Array(items) => token.parse::<usize>().ok().and_then(move |i| items.get_mut(i)),
+        _ => None,
+    });
+    match parent {
+        Some(Value::Object(map)) => match op {
+            PatchOp::Add => {
+                map.insert(last, value);
+                true
+            }
+            PatchOp::Replace => map.get_mut(&last).map(|target| *target = value).is_some(),
+            PatchOp::Remove => map.remove(&last).is_some(),
+        },
+        Some(Value::Array(items)) => {
+            let index = match last.as_str() {
+                "-" if op == PatchOp::Add => items.len(),
+                token => match token.parse::<usize>() {
+                    Ok(index) => index,
+                    Err(_) => return false,
+                },
+            };
+            match op {
+                PatchOp::Add if index <= items.len() => items.insert(index, value),
+                PatchOp::Replace if index < items.len() => items[index] = value,
+                PatchOp::Remove if index < items.len() => {
+                    items.remove(index);
+                }
+                _ => return false,
+            }
+            true
+        }
+        _ => false,
+    }
+}
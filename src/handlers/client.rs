@@ -0,0 +1,133 @@
+//! Who a request came from: the client address, resolved through trusted proxies, and the
+//! connection it arrived on.
+
+use axum::http::{header, HeaderMap, HeaderValue};
+use ipnet::IpNet;
+use std::net::{IpAddr, SocketAddr};
+
+use crate::config::in_networks;
+use crate::listener::TlsInfo;
+
+/// The client of one request.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientInfo {
+    /// The client's address: the peer, or the client a trusted proxy forwarded for.
+    pub ip: Option<IpAddr>,
+    /// The client's port, when the peer is the client or a trusted proxy reported it.
+    pub port: Option<u16>,
+    /// The directly connected peer.
+    pub peer: Option<SocketAddr>,
+    /// The TLS session, for requests received over TLS.
+    pub tls: Option<TlsInfo>,
+}
+
+impl ClientInfo {
+    /// The client of a request received from `peer`. While the hop being looked at is a
+    /// trusted proxy, the `Forwarded` header (or else `X-Forwarded-For`) is followed one hop
+    /// back; the first untrusted hop is the client. An unparsable hop (such as
+    /// `for=unknown`) ends the walk at the last known address.
+    pub fn resolve(peer: Option<SocketAddr>, tls: Option<TlsInfo>, headers: &HeaderMap, trusted: &[IpNet]) -> Self {
+        let mut ip = peer.map(|addr| addr.ip().to_canonical());
+        let mut port = peer.map(|addr| addr.port());
+        if let Some(peer_ip) = ip.filter(|ip| in_networks(trusted, *ip)) {
+            let mut hops = forwarded_hops(headers);
+            let mut current = peer_ip;
+            while in_networks(trusted, current) {
+                let Some(Some((hop_ip, hop_port))) = hops.pop() else {
+                    break;
+                };
+                current = hop_ip;
+                ip = Some(hop_ip);
+                port = hop_port;
+            }
+        }
+        Self { ip, port, peer, tls }
+    }
+
+    /// Adds `X-Forwarded-For`, `X-Forwarded-Proto`, `X-Forwarded-Host` and `Forwarded` to
+    /// the headers sent upstream. Chains received from a trusted proxy are extended;
+    /// otherwise they are replaced, so clients cannot spoof them.
+    pub fn add_forwarded_headers(&self, headers: &mut HeaderMap, trusted: &[IpNet]) {
+        let Some(peer) = self.peer else {
+            return;
+        };
+        let peer_ip = peer.ip().to_canonical();
+        let from_trusted = in_networks(trusted, peer_ip);
+        let proto = if self.tls.is_some() { "https" } else { "http" };
+        let host = headers.get(header::HOST).and_then(|v| v.to_str().ok()).map(str::to_string);
+
+        let previous = |headers: &HeaderMap, name: &str| -> Option<String> {
+            let values: Vec<&str> = headers.get_all(name).iter().filter_map(|v| v.to_str().ok()).collect();
+            (from_trusted && !values.is_empty()).then(|| values.join(", "))
+        };
+        let chain = |previous: Option<String>, hop: String| match previous {
+            Some(previous) => format!("{}, {}", previous, hop),
+            None => hop,
+        };
+
+        let for_value = chain(previous(headers, "x-forwarded-for"), peer_ip.to_string());
+        let mut element = format!("for={}", forwarded_node(peer_ip));
+        if let Some(host) = &host {
+            element.push_str(&format!(";host=\"{}\"", host));
+        }
+        element.push_str(&format!(";proto={}", proto));
+        let forwarded = chain(previous(headers, "forwarded"), element);
+        let proto = previous(headers, "x-forwarded-proto").unwrap_or_else(|| proto.to_string());
+        let host = previous(headers, "x-forwarded-host").or(host);
+
+        let values = [
+            ("x-forwarded-for", Some(for_value)),
+            ("x-forwarded-proto", Some(proto)),
+            ("x-forwarded-host", host),
+            ("forwarded", Some(forwarded)),
+        ];
+        for (name, value) in values {
+            headers.remove(name);
+            if let Some(value) = value.and_then(|v| HeaderValue::from_str(&v).ok()) {
+                headers.insert(name, value);
+            }
+        }
+    }
+}
+
+/// The hops a request passed through, client first, from `Forwarded` or else
+/// `X-Forwarded-For`. Hops without a usable address are `None`.
+fn forwarded_hops(headers: &HeaderMap) -> Vec<Option<(IpAddr, Option<u16>)>> {
+    let values = |name| -> Vec<&str> {
+        headers.get_all(name).iter()
+            .filter_map(|v| v.to_str().ok())
+            .flat_map(|v| v.split(','))
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+            .collect()
+    };
+    let forwarded = values(header::FORWARDED);
+    if !forwarded.is_empty() {
+        return forwarded.into_iter()
+            .map(|element| {
+                element.split(';')
+                    .filter_map(|pair| pair.split_once('='))
+                    .find(|(key, _)| key.trim().eq_ignore_ascii_case("for"))
+                    .and_then(|(_, node)| parse_node(node.trim().trim_matches('"')))
+            })
+            .collect();
+    }
+    values(header::HeaderName::from_static("x-forwarded-for")).into_iter().map(parse_node).collect()
+}
+
+/// Parses a node such as `192.0.2.60`, `192.0.2.60:4711`, `[2001:db8::1]:4711` or `2001:db8::1`.
+fn parse_node(node: &str) -> Option<(IpAddr, Option<u16>)> {
+    if let Ok(addr) = node.parse::<SocketAddr>() {
+        return Some((addr.ip().to_canonical(), Some(addr.port())));
+    }
+    let ip = node.strip_prefix('[').and_then(|n| n.strip_suffix(']')).unwrap_or(node);
+    ip.parse::<IpAddr>().ok().map(|ip| (ip.to_canonical(), None))
+}
+
+/// `ip` as a `Forwarded` node: IPv6 addresses are bracketed and quoted.
+fn forwarded_node(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => ip.to_string(),
+        IpAddr::V6(ip) => format!("\"[{}]\"", ip),
+    }
+}
@@ -0,0 +1,61 @@
+use axum::body::Bytes;
+use axum::http::{header, HeaderMap};
+use std::io::Read;
+
+use crate::config::DecompressionConfig;
+
+/// `body` as text for rule matching and log capture, decoded per its `Content-Encoding`.
+/// Bodies with an unsupported encoding, or that fail to decode, are returned as received.
+pub fn decoded_text(headers: &HeaderMap, body: &[u8], config: &DecompressionConfig) -> String {
+    match decoded(headers, body, config) {
+        Some(decoded) => String::from_utf8_lossy(&decoded).into_owned(),
+        None => String::from_utf8_lossy(body).into_owned(),
+    }
+}
+
+/// Like [`decoded_text`], as bytes.
+pub fn decoded_body(headers: &HeaderMap, body: &Bytes, config: &DecompressionConfig) -> Bytes {
+    decoded(headers, body, config).map_or_else(|| body.clone(), Bytes::from)
+}
+
+/// `body` decoded, or `None` if it is not encoded or can't be decoded.
+fn decoded(headers: &HeaderMap, body: &[u8], config: &DecompressionConfig) -> Option<Vec<u8>> {
+    let encodings: Vec<String> = headers.get_all(header::CONTENT_ENCODING).iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|e| e.trim().to_ascii_lowercase())
+        .filter(|e| !e.is_empty() && e != "identity")
+        .collect();
+    if !config.enabled || encodings.is_empty() || body.is_empty() {
+        return None;
+    }
+    match decode(body, &encodings, config.max_bytes) {
+        Ok(decoded) => Some(decoded),
+        Err(reason) => {
+            tracing::debug!(encoding = %encodings.join(", "), reason = %reason, "body not decoded");
+            None
+        }
+    }
+}
+
+/// Decodes `body`, encoded with each of `encodings` in turn, into at most `max_bytes`.
+fn decode(body: &[u8], encodings: &[String], max_bytes: usize) -> Result<Vec<u8>, String> {
+    // The last encoding applied is the first to undo
+    let mut reader: Box<dyn Read + '_> = Box::new(body);
+    for encoding in encodings.iter().rev() {
+        reader = match encoding.as_str() {
+            "gzip" | "x-gzip" => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            "deflate" => Box::new(flate2::read::ZlibDecoder::new(reader)),
+            "br" => Box::new(brotli_decompressor::Decompressor::new(reader, 4096)),
+            "zstd" => Box::new(zstd::Decoder::new(reader).map_err(|e| e.to_string())?),
+            other => return Err(format!("unsupported encoding '{}'", other)),
+        };
+    }
+    let mut decoded = Vec::new();
+    reader.take(max_bytes as u64 + 1).read_to_end(&mut decoded).map_err(|e| e.to_string())?;
+    if decoded.len() > max_bytes {
+        tracing::warn!(max_bytes, "decoded body exceeds decompression.max_bytes; only the start is matched and logged");
+        decoded.truncate(max_bytes);
+    }
+    Ok(decoded)
+}
@@ -0,0 +1,215 @@
+//! Rule dry runs: evaluate a synthetic request (and optionally a response) against the config
+//! without proxying anything, and report which rules match and what would be logged.
+//!
+//! Covers drop, logging and response logging rules. Authentication, rate limits, mocks and
+//! faults are not applied.
+
+use axum::{
+    extract::State,
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::client::ClientInfo;
+use super::proxy::{capture_claims, capture_client, request_log_entry, response_log_entry};
+use crate::config::{Claims, Config, ConfigHolder, DropResponse, RequestContext, ResponseContext};
+
+/// What to evaluate: a request and, optionally, the response it gets.
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct DryRunInput {
+    pub request: SyntheticRequest,
+    /// Upstream response to evaluate response logging rules against. Ignored when the
+    /// request is dropped, since the drop response is logged instead.
+    #[serde(default)]
+    pub response: Option<SyntheticResponse>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SyntheticRequest {
+    #[serde(default = "default_method")]
+    pub method: String,
+    /// Request path, including the embedded upstream URL (e.g. `/https://api.example.com/v1`).
+    pub path: String,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub body: String,
+    /// Verified claims of the caller, to evaluate `claims` conditions as if authenticated.
+    #[serde(default)]
+    pub claims: Option<Claims>,
+    /// Address of the client, to evaluate `client_ip` conditions.
+    #[serde(default)]
+    pub client_ip: Option<IpAddr>,
+}
+
+fn default_method() -> String {
+    "GET".to_string()
+}
+
+impl Default for SyntheticRequest {
+    fn default() -> Self {
+        Self { method: default_method(), path: "/".to_string(), headers: BTreeMap::new(), body: String::new(), claims: None, client_ip: None }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SyntheticResponse {
+    #[serde(default = "default_status")]
+    pub status_code: u16,
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default)]
+    pub body: String,
+    /// Time the response took, to evaluate `min_latency` and `max_latency` conditions.
+    #[serde(default)]
+    pub latency_ms: Option<u64>,
+}
+
+fn default_status() -> u16 {
+    200
+}
+
+impl Default for SyntheticResponse {
+    fn default() -> Self {
+        Self { status_code: default_status(), headers: BTreeMap::new(), body: String::new(), latency_ms: None }
+    }
+}
+
+/// The outcome of a dry run.
+#[derive(Debug, Serialize)]
+pub struct DryRunReport {
+    pub drop: SectionResult,
+    pub logging: SectionResult,
+    /// Present when a response was supplied or the request is dropped.
+    pub response_logging: Option<SectionResult>,
+    /// The response returned instead of proxying, if the request is dropped.
+    pub dropped: Option<DropResponse>,
+    /// Upstream timeout from the winning logging rule.
+    pub timeout_ms: Option<u64>,
+    /// The request log entry, as it would be emitted. Timing fields are zero. Absent when the
+    /// request is dropped or not logged.
+    pub request_log: Option<serde_json::Value>,
+    /// The response log entry, as it would be emitted.
+    pub response_log: Option<serde_json::Value>,
+}
+
+/// How one rule section evaluated.
+#[derive(Debug, Serialize)]
+pub struct SectionResult {
+    /// Every matching rule, in config order.
+    pub matched: Vec<RuleMatch>,
+    /// The rule that takes effect: the first match.
+    pub winner: Option<RuleMatch>,
+    /// True when no rule matched and the section's `default` applies.
+    pub default: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleMatch {
+    pub index: usize,
+    pub name: String,
+}
+
+impl SectionResult {
+    fn new(matched: Vec<RuleMatch>, default: bool) -> Self {
+        let winner = matched.first().cloned();
+        Self { default: default && winner.is_none(), winner, matched }
+    }
+}
+
+fn header_map(headers: &BTreeMap<String, String>) -> Result<HeaderMap, String> {
+    let mut map = HeaderMap::new();
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes()).map_err(|_| format!("Invalid header name '{}'", name))?;
+        let value = HeaderValue::from_str(value).map_err(|_| format!("Invalid value for header '{}'", name))?;
+        map.append(name, value);
+    }
+    Ok(map)
+}
+
+/// Evaluates `input` against `config` the way the proxy handler would.
+pub fn dry_run(config: &Config, input: &DryRunInput) -> Result<DryRunReport, String> {
+    let request = &input.request;
+    let headers = header_map(&request.headers)?;
+    let claims = request.claims.as_ref();
+    let ctx = RequestContext::new(&request.method, &request.path, &headers, &request.body)
+        .with_claims(claims)
+        .with_client_ip(request.client_ip);
+    let rules = config.rules();
+
+    let drop_matches = rules.drop.matching(&ctx)
+        .map(|index| RuleMatch { index, name: config.drop.rules[index].name.clone() })
+        .collect();
+    let logging_matches = rules.logging.matching(&ctx)
+        .map(|index| RuleMatch { index, name: config.logging.rules[index].name.clone() })
+        .collect();
+
+    let dropped = config.should_drop_request_ctx(&ctx);
+    let logging_rule = config.logging_rule_ctx(&ctx);
+    let timeout = logging_rule.and_then(|rule| rule.parse_timeout());
+    let request_log = match dropped {
+        Some(_) => None,
+        None => config.request_capture(logging_rule).map(|capture| {
+            let mut entry = request_log_entry(&request.method, &request.path, &headers, capture, std::time::Duration::ZERO, request.body.as_bytes(), timeout);
+            capture_claims(&mut entry, &capture.claims, claims);
+            capture_client(&mut entry, capture, &ClientInfo { ip: request.client_ip, ..Default::default() });
+            entry
+        }),
+    };
+
+    // A drop response is immediate
+    let response = match (&dropped, &input.response) {
+        (Some(drop), _) => Some((drop.status_code, HeaderMap::new(), String::new(), Some(Duration::ZERO))),
+        (None, Some(response)) => Some((
+            response.status_code,
+            header_map(&response.headers)?,
+            response.body.clone(),
+            response.latency_ms.map(Duration::from_millis),
+        )),
+        (None, None) => None,
+    };
+    let (response_logging, response_log) = match response {
+        Some((status, headers, body, latency)) => {
+            let mut response_ctx = ResponseContext::new(status, &headers, &body).with_request(&ctx);
+            if let Some(latency) = latency {
+                response_ctx = response_ctx.with_latency(latency);
+            }
+            let matched = rules.response_logging.iter().enumerate()
+                .filter(|(_, rule)| rule.matches_ctx(&response_ctx))
+                .map(|(index, _)| RuleMatch { index, name: config.response_logging.rules[index].name.clone() })
+                .collect();
+            let log = config.should_log_response_ctx(&response_ctx).map(|capture| {
+                let elapsed = latency.unwrap_or_default();
+                let mut entry = response_log_entry(&request.method, &request.path, status, &headers, capture, elapsed, body.as_bytes());
+                capture_claims(&mut entry, &capture.claims, claims);
+                entry
+            });
+            (Some(SectionResult::new(matched, config.response_logging.default)), log)
+        }
+        None => (None, None),
+    };
+
+    Ok(DryRunReport {
+        drop: SectionResult::new(drop_matches, config.drop.default),
+        logging: SectionResult::new(logging_matches, config.logging.default),
+        response_logging,
+        dropped,
+        timeout_ms: timeout.map(|t| t.as_millis() as u64),
+        request_log,
+        response_log,
+    })
+}
+
+/// `POST /config/test`: dry-runs a synthetic request against the current config.
+pub async fn test_config(State(config): State<Arc<ConfigHolder>>, Json(input): Json<DryRunInput>) -> Response {
+    match dry_run(&config.get(), &input) {
+        Ok(report) => (StatusCode::OK, Json(report)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": e }))).into_response(),
+    }
+}
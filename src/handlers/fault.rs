@@ -0,0 +1,122 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderMap, StatusCode},
+    response::Response,
+};
+use crate::config::{ErrorFault, FaultRule};
+use futures_util::stream;
+use serde::Serialize;
+use super::mock::MockReply;
+
+/// Interval between chunks when throttling bandwidth.
+const THROTTLE_INTERVAL: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Record of the faults injected into one request, attached to its log entries as `"fault"`.
+#[derive(Debug, Serialize)]
+pub(crate) struct InjectedFaults {
+    pub rule: String,
+    pub kinds: Vec<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+}
+
+/// The faults to apply to a single request, decided once up front.
+pub(crate) struct FaultPlan {
+    pub tag: InjectedFaults,
+    pub latency: Option<std::time::Duration>,
+    pub abort: bool,
+    pub error: Option<ErrorFault>,
+    truncate_body_bytes: Option<usize>,
+    bandwidth_bytes_per_sec: Option<u64>,
+}
+
+impl FaultPlan {
+    /// Rolls the rule's percentage; returns `None` if this request is spared.
+    pub fn roll(rule: &FaultRule) -> Option<Self> {
+        if !rule.should_inject() {
+            return None;
+        }
+        let latency = rule.latency.as_ref().and_then(|l| l.sample());
+        let mut kinds = Vec::new();
+        if latency.is_some() {
+            kinds.push("latency");
+        }
+        if rule.abort {
+            kinds.push("abort");
+        }
+        if rule.error.is_some() {
+            kinds.push("error");
+        }
+        if rule.truncate_body_bytes.is_some() {
+            kinds.push("truncate");
+        }
+        if rule.bandwidth_bytes_per_sec.is_some() {
+            kinds.push("bandwidth");
+        }
+        Some(Self {
+            tag: InjectedFaults {
+                rule: rule.name.clone(),
+                kinds,
+                latency_ms: latency.map(|d| d.as_millis() as u64),
+            },
+            latency,
+            abort: rule.abort,
+            error: rule.error.clone(),
+            truncate_body_bytes: rule.truncate_body_bytes,
+            bandwidth_bytes_per_sec: rule.bandwidth_bytes_per_sec,
+        })
+    }
+
+    /// Whether the upstream response body must be streamed through [`shape_body`](Self::shape_body).
+    pub fn shapes_body(&self) -> bool {
+        self.truncate_body_bytes.is_some() || self.bandwidth_bytes_per_sec.is_some()
+    }
+
+    /// Streams `body` with truncation and bandwidth throttling applied. A truncated body ends
+    /// in an I/O error so the client sees the connection drop mid-response.
+    pub fn shape_body(&self, body: Bytes) -> Body {
+        let limit = self.truncate_body_bytes.unwrap_or(body.len()).min(body.len());
+        let truncated = limit < body.len();
+        let chunk_size = match self.bandwidth_bytes_per_sec {
+            Some(bps) => ((bps as u128 * THROTTLE_INTERVAL.as_millis() / 1000) as usize).max(1),
+            None => limit.max(1),
+        };
+        let throttled = self.bandwidth_bytes_per_sec.is_some();
+
+        let chunks = stream::unfold(
+            (body.slice(..limit), truncated, true),
+            move |(rest, truncated, first)| async move {
+                if rest.is_empty() {
+                    return truncated.then(|| {
+                        (Err(aborted("fault injected: body truncated")), (rest, false, false))
+                    });
+                }
+                if throttled && !first {
+                    tokio::time::sleep(THROTTLE_INTERVAL).await;
+                }
+                let n = chunk_size.min(rest.len());
+                Some((Ok(rest.slice(..n)), (rest.slice(n..), truncated, false)))
+            },
+        );
+        Body::from_stream(chunks)
+    }
+}
+
+fn aborted(msg: &'static str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::ConnectionAborted, msg)
+}
+
+/// A response whose body fails immediately, making the server drop the client connection.
+pub(crate) fn aborted_response() -> Response {
+    let body = stream::once(async { Err::<Bytes, _>(aborted("fault injected: connection aborted")) });
+    Response::new(Body::from_stream(body))
+}
+
+/// The reply sent for an injected error status.
+pub(crate) fn error_reply(error: &ErrorFault) -> MockReply {
+    MockReply {
+        status: StatusCode::from_u16(error.status_code).unwrap_or(StatusCode::SERVICE_UNAVAILABLE),
+        headers: HeaderMap::new(),
+        body: Bytes::from(error.body.clone().unwrap_or_default()),
+    }
+}
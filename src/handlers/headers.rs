@@ -0,0 +1,96 @@
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use std::collections::HashMap;
+
+use super::template::TemplateContext;
+use crate::config::{HeaderRule, HeaderTransform};
+
+/// Header name for the request ID: reused when the client sends one, otherwise generated.
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The client's `x-request-id`, or a new random (version 4) UUID.
+pub fn request_id(headers: &HeaderMap) -> String {
+    if let Some(id) = headers.get(REQUEST_ID_HEADER).and_then(|v| v.to_str().ok()).filter(|v| !v.is_empty()) {
+        return id.to_string();
+    }
+    let mut bytes = rand::random::<[u8; 16]>();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40; // version 4
+    bytes[8] = (bytes[8] & 0x3f) | 0x80; // RFC 4122 variant
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+enum Edit {
+    Remove(HeaderName),
+    Rename(HeaderName, HeaderName),
+    Set(HeaderName, HeaderValue),
+    Add(HeaderName, HeaderValue),
+}
+
+/// The header changes of every rule matching one request, with their templates rendered.
+#[derive(Default)]
+pub struct HeaderEdits {
+    request: Vec<Edit>,
+    response: Vec<Edit>,
+}
+
+impl HeaderEdits {
+    /// Renders the transforms of `rules` (each with its path captures) against the request
+    /// in `ctx`. Names and values that are not valid headers once rendered are skipped.
+    pub fn new(rules: Vec<(&HeaderRule, HashMap<String, String>)>, ctx: &mut TemplateContext) -> Self {
+        let mut edits = Self::default();
+        for (rule, captures) in rules {
+            ctx.captures = captures;
+            render(&rule.name, &rule.request, ctx, &mut edits.request);
+            render(&rule.name, &rule.response, ctx, &mut edits.response);
+        }
+        edits
+    }
+
+    /// Applies the request changes to the headers forwarded upstream.
+    pub fn apply_request(&self, headers: &mut HeaderMap) {
+        apply(&self.request, headers);
+    }
+
+    /// Applies the response changes to the headers returned to the client.
+    pub fn apply_response(&self, headers: &mut HeaderMap) {
+        apply(&self.response, headers);
+    }
+}
+
+fn render(rule: &str, transform: &HeaderTransform, ctx: &TemplateContext, edits: &mut Vec<Edit>) {
+    let name = |name: &str| HeaderName::from_bytes(name.as_bytes()).ok();
+    edits.extend(transform.remove.iter().filter_map(|n| name(n)).map(Edit::Remove));
+    edits.extend(transform.rename.iter().filter_map(|(from, to)| Some(Edit::Rename(name(from)?, name(to)?))));
+    let rendered = |header: &String, template: &String| {
+        let value = HeaderValue::from_str(&ctx.render(template)).ok();
+        if value.is_none() {
+            tracing::warn!(rule = %rule, header = %header, "header rule rendered an invalid value; skipped");
+        }
+        Some((name(header)?, value?))
+    };
+    edits.extend(transform.set.iter().filter_map(|(h, t)| rendered(h, t)).map(|(h, v)| Edit::Set(h, v)));
+    edits.extend(transform.add.iter().filter_map(|(h, t)| rendered(h, t)).map(|(h, v)| Edit::Add(h, v)));
+}
+
+fn apply(edits: &[Edit], headers: &mut HeaderMap) {
+    for edit in edits {
+        match edit {
+            Edit::Remove(name) => {
+                headers.remove(name);
+            }
+            Edit::Rename(from, to) => {
+                let values: Vec<HeaderValue> = headers.get_all(from).iter().cloned().collect();
+                headers.remove(from);
+                for value in values {
+                    headers.append(to.clone(), value);
+                }
+            }
+            Edit::Set(name, value) => {
+                headers.insert(name.clone(), value.clone());
+            }
+            Edit::Add(name, value) => {
+                headers.append(name.clone(), value.clone());
+            }
+        }
+    }
+}
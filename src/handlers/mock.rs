@@ -0,0 +1,75 @@
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::Response,
+};
+use crate::config::{MockResponse, RecordedResponse};
+use super::proxy::ProxyError;
+use super::template::TemplateContext;
+
+/// A locally generated response, kept in parts so it can be logged before it is sent.
+pub(crate) struct MockReply {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl MockReply {
+    pub fn into_response(self) -> Response {
+        let mut response = Response::new(Body::from(self.body));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers;
+        response
+    }
+}
+
+/// Renders a mock rule's response, honouring its `delay`.
+pub(crate) async fn render_mock(
+    mock: &MockResponse,
+    ctx: &TemplateContext<'_>,
+) -> Result<MockReply, ProxyError> {
+    let template = match (&mock.body, &mock.body_file) {
+        (Some(body), _) => body.clone(),
+        (None, Some(file)) => tokio::fs::read_to_string(file).await.map_err(|e| {
+            tracing::warn!(file = %file, error = %e, "mock body file unreadable");
+            ProxyError::MockUnavailable
+        })?,
+        (None, None) => String::new(),
+    };
+
+    let status = StatusCode::from_u16(mock.status_code).map_err(|_| ProxyError::MockUnavailable)?;
+    let mut headers = HeaderMap::new();
+    for (name, value) in &mock.headers {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(&ctx.render(value)),
+        ) {
+            headers.insert(name, value);
+        }
+    }
+    let body = Bytes::from(ctx.render(&template));
+
+    if let Some(delay) = mock.parse_delay() {
+        tokio::time::sleep(delay).await;
+    }
+
+    Ok(MockReply { status, headers, body })
+}
+
+/// Converts a recorded response into a reply.
+pub(crate) fn replay_recording(recording: &RecordedResponse) -> MockReply {
+    let mut headers = HeaderMap::new();
+    for (name, value) in &recording.headers {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            headers.append(name, value);
+        }
+    }
+    MockReply {
+        status: StatusCode::from_u16(recording.status_code).unwrap_or(StatusCode::OK),
+        headers,
+        body: Bytes::from(recording.body.clone()),
+    }
+}
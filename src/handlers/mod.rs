@@ -1,5 +1,14 @@
 pub mod api;
+mod body;
+pub mod client;
+pub mod decompress;
+pub mod dry_run;
+mod fault;
+mod headers;
+mod mock;
 pub mod proxy;
+mod template;
 
 pub use api::*;
+pub use dry_run::test_config;
 pub use proxy::*;
@@ -5,11 +5,26 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use axum::extract::Request;
-use crate::config::{CaptureConfig, ConfigHolder, ResponseCaptureConfig};
+use axum::extract::ConnectInfo;
+use crate::config::{BodyCaptureOptions, BodyFields, CaptureConfig, Claims, Config, ConfigHolder, RateLimitKey, RequestContext, ResponseCaptureConfig, ResponseContext};
+use crate::concurrency::ShedReason;
+use crate::metrics::Metrics;
+use crate::rate_limiter::RateLimitDecision;
+use std::net::{IpAddr, SocketAddr};
+use super::body::BodyEdits;
+use super::client::ClientInfo;
+use super::decompress::decoded_body;
+use super::fault::{aborted_response, error_reply, FaultPlan, InjectedFaults};
+use super::headers::{request_id, HeaderEdits};
+use super::mock::{render_mock, replay_recording, MockReply};
+use super::template::TemplateContext;
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::LazyLock;
 use tracing::info;
 
+use crate::listener::TlsInfo;
+
 /// Maximum request body size accepted before returning 413. Prevents OOM via large uploads.
 const MAX_BODY_SIZE: usize = 10 * 1024 * 1024; // 10 MB
 
@@ -23,10 +38,24 @@ pub enum ProxyError {
     TimeoutError,
     BodyReadError,
     BodyTooLarge,
+    MockUnavailable,
+    NoRecording,
+    RateLimited(RateLimitDecision),
+    Overloaded(ShedReason),
+    /// Authentication failed; carries the `WWW-Authenticate` challenges to advertise.
+    Unauthorized(Vec<String>),
 }
 
 impl IntoResponse for ProxyError {
     fn into_response(self) -> Response {
+        let extra_headers = match &self {
+            ProxyError::RateLimited(decision) => decision.headers(),
+            ProxyError::Overloaded(_) => vec![("retry-after", "1".to_string())],
+            ProxyError::Unauthorized(challenges) => challenges.iter()
+                .map(|c| ("www-authenticate", c.clone()))
+                .collect(),
+            _ => vec![],
+        };
         let (status, error_msg) = match self {
             ProxyError::NoUpstreamUrl => (
                 StatusCode::BAD_REQUEST,
@@ -59,11 +88,41 @@ impl IntoResponse for ProxyError {
                 StatusCode::PAYLOAD_TOO_LARGE,
                 serde_json::json!({"error": "Request body too large"}),
             ),
+            ProxyError::MockUnavailable => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                serde_json::json!({"error": "Mock response unavailable"}),
+            ),
+            ProxyError::NoRecording => (
+                StatusCode::NOT_FOUND,
+                serde_json::json!({"error": "No recorded response for request"}),
+            ),
+            ProxyError::RateLimited(decision) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                serde_json::json!({"error": "Rate limit exceeded", "rule": decision.rule}),
+            ),
+            ProxyError::Overloaded(reason) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                serde_json::json!({
+                    "error": "Server overloaded",
+                    "reason": match reason {
+                        ShedReason::QueueFull => "queue_full",
+                        ShedReason::WaitTimeout => "wait_timeout",
+                    },
+                }),
+            ),
+            ProxyError::Unauthorized(_) => (
+                StatusCode::UNAUTHORIZED,
+                serde_json::json!({"error": "Authentication required"}),
+            ),
         };
 
-        Response::builder()
+        let mut builder = Response::builder()
             .status(status)
-            .header("content-type", "application/json")
+            .header("content-type", "application/json");
+        for (name, value) in extra_headers {
+            builder = builder.header(name, value);
+        }
+        builder
             .body(Body::from(serde_json::to_string(&error_msg).unwrap()))
             .unwrap()
     }
@@ -89,7 +148,7 @@ fn filter_headers(headers: &HeaderMap) -> reqwest::header::HeaderMap {
         if !HOP_BY_HOP.iter().any(|h| h.eq_ignore_ascii_case(name_str)) {
             if let Ok(key) = reqwest::header::HeaderName::from_bytes(name.as_str().as_bytes()) {
                 if let Ok(val) = reqwest::header::HeaderValue::from_bytes(value.as_bytes()) {
-                    result.insert(key, val);
+                    result.append(key, val);
                 }
             }
         }
@@ -97,14 +156,46 @@ fn filter_headers(headers: &HeaderMap) -> reqwest::header::HeaderMap {
     result
 }
 
+/// Routes served on the proxy listener: a health check, with every other path proxied.
+/// Admin endpoints live on [`admin_router`](super::admin_router) instead.
+pub fn proxy_router(state: Arc<ConfigHolder>) -> axum::Router {
+    axum::Router::new()
+        .route("/health", axum::routing::get(super::get_health_check))
+        .fallback(proxy_handler)
+        .with_state(state)
+}
+
 #[axum::debug_handler]
 pub async fn proxy_handler(State(config): State<Arc<ConfigHolder>>, req: Request) -> impl IntoResponse {
+    let mut held = None;
+    let response = proxy(&config, req, &mut held).await;
+    // A request entry sampled out by a rule with `always_log_errors` waits for the response
+    if let Some((entry, rule)) = held {
+        if response.status().is_server_error() {
+            emit_log(&entry);
+        } else {
+            config.metrics().record_sampled_out("logging", &rule);
+        }
+    }
+    response
+}
+
+/// Proxies one request. A request log entry held back until the response status is known
+/// is left in `held`, with the name of its rule.
+async fn proxy(config: &ConfigHolder, req: Request, held: &mut Option<(serde_json::Value, String)>) -> Response {
     let start_time = std::time::Instant::now();
+    // One immutable snapshot for the whole request, so a concurrent reload can't change the
+    // rules halfway through.
+    let cfg = config.get();
 
     // --- Extract request metadata before consuming the body ---
     let method_str = req.method().as_str().to_string();
-    let headers = req.headers().clone();
+    let mut headers = req.headers().clone();
     let req_path = req.uri().path().to_string();
+    let req_query = req.uri().query().map(str::to_string);
+    let client_addr = req.extensions().get::<ConnectInfo<SocketAddr>>().map(|info| info.0);
+    let client = ClientInfo::resolve(client_addr, req.extensions().get::<TlsInfo>().cloned(), &headers, &cfg.rules().trusted_proxies);
+    let upstream_host = upstream_host(&req_path);
 
     // --- Read body (with size cap) before any rule evaluation ---
     // Rules with body conditions need the real body to match correctly.
@@ -112,13 +203,51 @@ pub async fn proxy_handler(State(config): State<Arc<ConfigHolder>>, req: Request
         Ok(b) => b,
         Err(_) => return ProxyError::BodyTooLarge.into_response(),
     };
-    let body_content = String::from_utf8_lossy(&body_bytes).to_string();
+    // Rules and logs see compressed bodies decoded; the bytes forwarded stay as received
+    let request_body = decoded_body(&headers, &body_bytes, &cfg.decompression);
+    let body_content = String::from_utf8_lossy(&request_body).into_owned();
+
+    // --- Authenticate the caller (before any rule, so verified claims can be matched on) ---
+    let identity = {
+        let ctx = RequestContext::new(&method_str, &req_path, &headers, &body_content).with_client_ip(client.ip);
+        let identity = match cfg.authenticate_ctx(&ctx).await {
+            Ok(identity) => identity,
+            Err(e) => {
+                tracing::warn!(path = %req_path, reason = %e, "authentication failed");
+                let challenges = cfg.auth.authenticator().map(|a| a.challenges()).unwrap_or_default();
+                return ProxyError::Unauthorized(challenges).into_response();
+            }
+        };
+        if cfg.auth.strip_credentials {
+            for name in cfg.auth.authenticator().map(|a| a.credential_headers()).unwrap_or_default() {
+                headers.remove(name);
+            }
+        }
+        identity
+    };
+    let claims = identity.as_ref().map(|id| &id.claims);
+    let ctx = RequestContext::new(&method_str, &req_path, &headers, &body_content)
+        .with_claims(claims)
+        .with_client_ip(client.ip);
+
+    // --- Header and body rules (rendered once, from the request as received) ---
+    let request_id = request_id(&headers);
+    let sampling = LogSampling { request_id: &request_id, metrics: config.metrics() };
+    let client_ip = client.ip.map(|ip| ip.to_string());
+    let (header_edits, body_edits) = {
+        let header_rules = cfg.matching_header_rules_ctx(&ctx);
+        let body_rules = cfg.matching_body_rules_ctx(&ctx);
+        if header_rules.is_empty() && body_rules.is_empty() {
+            (HeaderEdits::default(), BodyEdits::default())
+        } else {
+            let mut template = TemplateContext::new(&method_str, &req_path, &headers, &body_content, HashMap::new())
+                .with_client(client_ip.clone(), &request_id);
+            (HeaderEdits::new(header_rules, &mut template), BodyEdits::new(body_rules, &mut template))
+        }
+    };
 
     // --- Drop check (with real body, before URL extraction so drop rules apply to all paths) ---
-    let drop_response = {
-        let cfg = config.get();
-        cfg.should_drop_request_parts(&method_str, &req_path, &headers, &body_content)
-    };
+    let drop_response = cfg.should_drop_request_ctx(&ctx);
 
     if let Some(drop_resp) = drop_response {
         let response = Response::builder()
@@ -127,46 +256,150 @@ pub async fn proxy_handler(State(config): State<Arc<ConfigHolder>>, req: Request
             .unwrap();
 
         // Log the drop response if response_logging is configured
-        let cfg = config.get();
-        if let Some(capture) = cfg.should_log_response(response.status().as_u16(), response.headers(), "") {
-            log_response(&method_str, &req_path, response.status().as_u16(), response.headers(), capture, start_time.elapsed(), "");
+        let response_ctx = ResponseContext::new(response.status().as_u16(), response.headers(), "")
+            .with_latency(start_time.elapsed())
+            .with_request(&ctx);
+        if let Some(capture) = sampling.response_capture(&cfg, &response_ctx) {
+            let mut entry = response_log_entry(&method_str, &req_path, response.status().as_u16(), response.headers(), capture, start_time.elapsed(), b"");
+            capture_claims(&mut entry, &capture.claims, claims);
+            emit_log(&entry);
         }
 
-        return response;
+        return with_headers(response, &header_edits);
+    }
+
+    // --- Rewrite rules (resolved here so concurrency and rate limits key on the upstream
+    // actually called; a redirect is only answered after mock rules) ---
+    let requested_url = extract_upstream_url(&req_path);
+    let rewrite = requested_url.as_ref().ok().and_then(|url| cfg.rewrite_ctx(&ctx, url));
+    let effective_host = match &rewrite {
+        Some((_, url)) => url_host(url),
+        None => upstream_host,
+    };
+
+    // --- Concurrency limits (held until the response is returned) ---
+    let limiter = config.concurrency_limiter();
+    let _permit = match limiter.acquire(effective_host.as_deref(), config.metrics()).await {
+        Ok(permit) => permit,
+        Err(reason) => return ProxyError::Overloaded(reason).into_response(),
+    };
+
+    // --- Rate limits (every matching rule is enforced; the first exceeded rejects) ---
+    let rate_limited = cfg.matching_rate_limits_ctx(&ctx)
+        .into_iter()
+        .map(|rule| {
+            let key = rate_limit_key(&rule.key, client.ip, effective_host.as_deref(), &headers);
+            config.rate_limiter().check(rule, &key)
+        })
+        .find(|decision| !decision.allowed);
+
+    if let Some(decision) = rate_limited {
+        return ProxyError::RateLimited(decision).into_response();
+    }
+
+    // --- Mock check (after drop rules, before URL extraction so mocks apply to any path) ---
+    let mock = cfg.should_mock_request_ctx(&ctx)
+        .map(|(rule, captures)| (rule.response.clone(), captures));
+
+    if let Some((mock_resp, captures)) = mock {
+        let template = TemplateContext::new(&method_str, &req_path, &headers, &body_content, captures)
+            .with_client(client_ip, &request_id);
+        return match render_mock(&mock_resp, &template).await {
+            Ok(reply) => {
+                let response = respond_locally(&cfg, &ctx, reply, start_time, None, &sampling);
+                with_headers(response, &header_edits)
+            }
+            Err(e) => e.into_response(),
+        };
     }
 
     // --- Extract upstream URL (after drop check so drop rules apply to any path) ---
-    let upstream_url = match extract_upstream_url(&req_path) {
+    let requested_url = match requested_url {
         Ok(url) => url,
         Err(e) => return e.into_response(),
     };
 
-    // --- SSRF validation ---
-    {
-        let cfg = config.get();
-        if let Err(reason) = validate_upstream_ssrf(&upstream_url, &cfg.upstream) {
-            tracing::warn!(upstream = %upstream_url, reason = %reason, "upstream blocked");
-            return ProxyError::BlockedUpstream.into_response();
+    // --- Rewrite redirects (first match wins; a redirect sends the client to the new URL) ---
+    if let Some((rule, url)) = &rewrite {
+        if let Some(status) = rule.redirect {
+            let reply = redirect_reply(status, url, req_query.as_deref());
+            let response = respond_locally(&cfg, &ctx, reply, start_time, None, &sampling);
+            return with_headers(response, &header_edits);
         }
     }
+    let rewrite_tag = rewrite.as_ref()
+        .map(|(rule, url)| serde_json::json!({"rule": rule.name, "from": requested_url, "to": url}));
+    let upstream_url = rewrite.map_or_else(|| requested_url.clone(), |(_, url)| url);
+
+    // --- Replay recorded responses, keyed by method and full upstream URL ---
+    let replay = cfg.mock.replay.as_ref().map(|replay| {
+        let recording_url = match &req_query {
+            Some(query) => format!("{}?{}", upstream_url, query),
+            None => upstream_url.clone(),
+        };
+        let recording = cfg.mock.recorded_response(&method_str, &recording_url).map(replay_recording);
+        (recording, replay.fallthrough)
+    });
 
-    // --- Get timeout and log config (with real body) ---
-    let (timeout, log_request_config) = {
-        let cfg = config.get();
-
-        let timeout = cfg.logging.rules.iter()
-            .find(|rule| cfg.matches_rule_parts(&method_str, &req_path, &headers, &body_content, &rule.match_conditions))
-            .and_then(|rule| rule.timeout.as_deref().and_then(parse_duration_string));
+    match replay {
+        Some((Some(mut reply), _)) => {
+            reply.body = body_edits.apply_response(reply.body, &mut reply.headers);
+            let response = respond_locally(&cfg, &ctx, reply, start_time, None, &sampling);
+            return with_headers(response, &header_edits);
+        }
+        Some((None, false)) => return ProxyError::NoRecording.into_response(),
+        _ => {}
+    }
 
-        let log_cfg = cfg.should_log_request_parts(&method_str, &req_path, &headers, &body_content)
-            .cloned();
+    // --- SSRF validation (of the requested URL, and again of the rewritten one) ---
+    for url in [&requested_url, &upstream_url] {
+        if let Err(reason) = validate_upstream_ssrf(url, &cfg.upstream) {
+            tracing::warn!(upstream = %url, reason = %reason, "upstream blocked");
+            return ProxyError::BlockedUpstream.into_response();
+        }
+    }
 
-        (timeout, log_cfg)
-    };
+    // --- Get timeout, log config and faults (with real body) ---
+    let logging_match = cfg.logging_match_ctx(&ctx);
+    let logging_rule = logging_match.map(|(rule, _)| rule);
+    let timeout = logging_rule.and_then(|rule| rule.timeout.as_deref().and_then(parse_duration_string));
+    let log_request_config = cfg.request_capture(logging_rule);
+    let fault = cfg.should_fault_request_ctx(&ctx).and_then(FaultPlan::roll);
+    let fault_tag = fault.as_ref().map(|plan| &plan.tag);
 
     // --- Log request if configured ---
-    if let Some(ref capture_config) = log_request_config {
-        log_request(&method_str, &req_path, &headers, capture_config, std::time::Duration::default(), &body_content, timeout);
+    if let Some(capture_config) = log_request_config {
+        let mut entry = request_log_entry(&method_str, &req_path, &headers, capture_config, std::time::Duration::default(), &request_body, timeout);
+        capture_claims(&mut entry, &capture_config.claims, claims);
+        capture_client(&mut entry, capture_config, &client);
+        tag_fault(&mut entry, fault_tag);
+        if let Some(tag) = &rewrite_tag {
+            entry["rewrite"] = tag.clone();
+        }
+        match logging_match {
+            Some((rule, sampler)) if !sampler.sample(&request_id, ctx.headers) => {
+                if rule.sampling.always_log_errors {
+                    *held = Some((entry, rule.name.clone()));
+                } else {
+                    config.metrics().record_sampled_out("logging", &rule.name);
+                }
+            }
+            _ => emit_log(&entry),
+        }
+    }
+
+    // --- Inject request-side faults ---
+    if let Some(plan) = &fault {
+        if let Some(delay) = plan.latency {
+            tokio::time::sleep(delay).await;
+        }
+        if plan.abort {
+            return aborted_response();
+        }
+        if let Some(error) = &plan.error {
+            let response = respond_locally(&cfg, &ctx, error_reply(error), start_time, fault_tag, &sampling);
+            return with_headers(response, &header_edits);
+        }
     }
 
     // --- Build and send upstream request ---
@@ -175,7 +408,13 @@ pub async fn proxy_handler(State(config): State<Arc<ConfigHolder>>, req: Request
         Err(_) => return ProxyError::UpstreamRequestFailed("Invalid method".to_string()).into_response(),
     };
 
-    let filtered_headers = filter_headers(&headers);
+    let mut upstream_headers = headers.clone();
+    if cfg.server.forwarded_headers {
+        client.add_forwarded_headers(&mut upstream_headers, &cfg.rules().trusted_proxies);
+    }
+    header_edits.apply_request(&mut upstream_headers);
+    let body_bytes = body_edits.apply_request(body_bytes, &mut upstream_headers);
+    let filtered_headers = filter_headers(&upstream_headers);
     let mut request_builder = HTTP_CLIENT.request(method, &upstream_url).headers(filtered_headers);
 
     if !body_bytes.is_empty() {
@@ -218,35 +457,147 @@ pub async fn proxy_handler(State(config): State<Arc<ConfigHolder>>, req: Request
     };
 
     // Decode response body only if response_logging is active (avoids allocation otherwise)
-    let response_logging_active = {
-        let cfg = config.get();
-        cfg.response_logging.default || !cfg.response_logging.rules.is_empty()
-    };
-    let resp_body_content = if response_logging_active {
-        String::from_utf8_lossy(&resp_body_bytes).into_owned()
+    let response_logging_active = cfg.response_logging.default || !cfg.response_logging.rules.is_empty();
+    let resp_body = if response_logging_active {
+        decoded_body(&resp_headers, &resp_body_bytes, &cfg.decompression)
     } else {
-        String::new()
+        axum::body::Bytes::new()
     };
+    let resp_body_content = String::from_utf8_lossy(&resp_body);
+    let resp_body_size = resp_body_bytes.len();
 
-    let final_resp = response_builder.body(Body::from(resp_body_bytes)).unwrap();
+    // Body rules apply after the response is captured for logging, like header rules
+    let resp_body_bytes = match response_builder.headers_mut() {
+        Some(headers) => body_edits.apply_response(resp_body_bytes, headers),
+        None => resp_body_bytes,
+    };
+
+    let final_body = match &fault {
+        Some(plan) if plan.shapes_body() => plan.shape_body(resp_body_bytes),
+        _ => Body::from(resp_body_bytes),
+    };
+    let final_resp = response_builder.body(final_body).unwrap();
 
     // --- Log response if configured ---
-    {
-        let cfg = config.get();
-        if let Some(capture) = cfg.should_log_response(
-            final_resp.status().as_u16(),
-            &resp_headers,
-            &resp_body_content,
-        ) {
-            log_response(
-                &method_str, &req_path,
-                final_resp.status().as_u16(), &resp_headers,
-                capture, start_time.elapsed(), &resp_body_content,
-            );
+    let response_ctx = ResponseContext::new(final_resp.status().as_u16(), &resp_headers, &resp_body_content)
+        .with_body_size(resp_body_size)
+        .with_latency(start_time.elapsed())
+        .with_request(&ctx);
+    if let Some(capture) = sampling.response_capture(&cfg, &response_ctx) {
+        let mut entry = response_log_entry(
+            &method_str, &req_path,
+            final_resp.status().as_u16(), &resp_headers,
+            capture, start_time.elapsed(), &resp_body,
+        );
+        capture_claims(&mut entry, &capture.claims, claims);
+        tag_fault(&mut entry, fault_tag);
+        emit_log(&entry);
+    }
+
+    with_headers(final_resp, &header_edits)
+}
+
+/// The value a rate limit counts this request against.
+fn rate_limit_key(
+    key: &RateLimitKey,
+    client_ip: Option<IpAddr>,
+    upstream_host: Option<&str>,
+    headers: &HeaderMap,
+) -> String {
+    match key {
+        RateLimitKey::ClientIp => client_ip
+            .map(|ip| ip.to_string())
+            .unwrap_or_default(),
+        RateLimitKey::UpstreamHost => upstream_host.unwrap_or_default().to_string(),
+        RateLimitKey::Header(name) => headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+/// Host of the upstream URL embedded in `path`, if it has one.
+fn upstream_host(path: &str) -> Option<String> {
+    url_host(&extract_upstream_url(path).ok()?)
+}
+
+/// Host of `url`, if it has one.
+fn url_host(url: &str) -> Option<String> {
+    url.parse::<reqwest::Url>().ok()?.host_str().map(str::to_string)
+}
+
+/// Sends a locally generated (mock or replayed) response, logging it like an upstream response.
+fn respond_locally(
+    cfg: &Config,
+    ctx: &RequestContext,
+    reply: MockReply,
+    start_time: std::time::Instant,
+    fault: Option<&InjectedFaults>,
+    sampling: &LogSampling,
+) -> Response {
+    let status = reply.status.as_u16();
+    let body = if cfg.response_logging.default || !cfg.response_logging.rules.is_empty() {
+        decoded_body(&reply.headers, &reply.body, &cfg.decompression)
+    } else {
+        axum::body::Bytes::new()
+    };
+    let body_content = String::from_utf8_lossy(&body);
+    let response_ctx = ResponseContext::new(status, &reply.headers, &body_content)
+        .with_body_size(reply.body.len())
+        .with_latency(start_time.elapsed())
+        .with_request(ctx);
+    if let Some(capture) = sampling.response_capture(cfg, &response_ctx) {
+        let mut entry = response_log_entry(ctx.method, ctx.path, status, &reply.headers, capture, start_time.elapsed(), &body);
+        capture_claims(&mut entry, &capture.claims, ctx.claims);
+        tag_fault(&mut entry, fault);
+        emit_log(&entry);
+    }
+    reply.into_response()
+}
+
+/// What response log sampling needs: the request ID that `sample_key: request_id` hashes,
+/// and the metrics sampled-out entries are counted in.
+struct LogSampling<'a> {
+    request_id: &'a str,
+    metrics: &'a Metrics,
+}
+
+impl LogSampling<'_> {
+    /// The capture config for the response, unless a matching rule samples its entry out.
+    fn response_capture<'c>(&self, cfg: &'c Config, ctx: &ResponseContext) -> Option<&'c ResponseCaptureConfig> {
+        let matched = cfg.response_logging_match_ctx(ctx);
+        if let Some((rule, sampler)) = matched {
+            if !sampler.sample_response(self.request_id, ctx) {
+                self.metrics.record_sampled_out("response_logging", &rule.name);
+                return None;
+            }
         }
+        cfg.response_capture(matched.map(|(rule, _)| rule))
     }
+}
+
+/// A redirect to `url` through the proxy, keeping the request's query string.
+fn redirect_reply(status: u16, url: &str, query: Option<&str>) -> MockReply {
+    let location = match query {
+        Some(query) => format!("/{}?{}", url, query),
+        None => format!("/{}", url),
+    };
+    let mut headers = HeaderMap::new();
+    if let Ok(location) = HeaderValue::from_str(&location) {
+        headers.insert(axum::http::header::LOCATION, location);
+    }
+    MockReply {
+        status: StatusCode::from_u16(status).unwrap_or(StatusCode::FOUND),
+        headers,
+        body: Default::default(),
+    }
+}
 
-    final_resp
+/// `response` with the header rules' response changes applied.
+fn with_headers(mut response: Response, edits: &HeaderEdits) -> Response {
+    edits.apply_response(response.headers_mut());
+    response
 }
 
 pub fn extract_upstream_url(path: &str) -> Result<String, ProxyError> {
@@ -263,15 +614,15 @@ pub fn extract_upstream_url(path: &str) -> Result<String, ProxyError> {
     Ok(url_str.to_string())
 }
 
-fn log_request(
+pub(super) fn request_log_entry(
     method: &str,
     path: &str,
     req_headers: &HeaderMap,
     capture_config: &CaptureConfig,
     duration: std::time::Duration,
-    body_content: &str,
+    body: &[u8],
     timeout: Option<std::time::Duration>,
-) {
+) -> serde_json::Value {
     let mut log_entry = serde_json::json!({
         "type": "request",
         "timestamp": chrono::Utc::now().to_rfc3339(),
@@ -303,21 +654,24 @@ fn log_request(
         }
     }
     if capture_config.body {
-        log_entry["body"] = body_content.into();
+        capture_body(&mut log_entry, req_headers, body, &capture_config.body_options);
+    }
+    if !capture_config.form.is_empty() || capture_config.multipart || capture_config.graphql {
+        capture_fields(&mut log_entry, capture_config, &BodyFields::parse(req_headers, &String::from_utf8_lossy(body)));
     }
 
-    info!("{}", serde_json::to_string(&log_entry).unwrap_or_else(|_| "Failed to serialize request log".to_string()));
+    log_entry
 }
 
-fn log_response(
+pub(super) fn response_log_entry(
     req_method: &str,
     req_path: &str,
     resp_status: u16,
     resp_headers: &HeaderMap,
     capture_config: &ResponseCaptureConfig,
     duration: std::time::Duration,
-    body_content: &str,
-) {
+    body: &[u8],
+) -> serde_json::Value {
     let mut log_entry = serde_json::json!({
         "type": "response",
         "timestamp": chrono::Utc::now().to_rfc3339(),
@@ -345,10 +699,86 @@ fn log_response(
         }
     }
     if capture_config.body {
-        log_entry["body"] = body_content.into();
+        capture_body(&mut log_entry, resp_headers, body, &capture_config.body_options);
+    }
+
+    log_entry
+}
+
+/// Adds the form fields, multipart parts and GraphQL operation `capture_config` asks for to a
+/// log entry.
+fn capture_fields(log_entry: &mut serde_json::Value, capture_config: &CaptureConfig, fields: &BodyFields) {
+    let mut form = serde_json::Map::new();
+    for name in &capture_config.form {
+        let values: Vec<&str> = fields.form.iter().filter(|(field, _)| field == name).map(|(_, v)| v.as_str()).collect();
+        match values.as_slice() {
+            [] => {}
+            [value] => {
+                form.insert(name.clone(), (*value).into());
+            }
+            values => {
+                form.insert(name.clone(), values.into());
+            }
+        }
+    }
+    if !form.is_empty() {
+        log_entry["form"] = form.into();
+    }
+    if capture_config.multipart && !fields.parts.is_empty() {
+        log_entry["multipart"] = serde_json::to_value(&fields.parts).unwrap_or_default();
+    }
+    if let Some(operation) = fields.graphql.as_ref().filter(|_| capture_config.graphql) {
+        log_entry["graphql"] = serde_json::to_value(operation).unwrap_or_default();
+    }
+}
+
+/// Adds the fields `options` capture from `body` to a log entry.
+fn capture_body(log_entry: &mut serde_json::Value, headers: &HeaderMap, body: &[u8], options: &BodyCaptureOptions) {
+    let content_type = headers.get(axum::http::header::CONTENT_TYPE).and_then(|v| v.to_str().ok());
+    for (field, value) in options.capture(content_type, body) {
+        log_entry[field] = value;
     }
+}
+
+/// Copies the named claims of the authenticated caller into a log entry.
+pub(super) fn capture_claims(log_entry: &mut serde_json::Value, names: &[String], claims: Option<&Claims>) {
+    let Some(claims) = claims else {
+        return;
+    };
+    let captured: serde_json::Map<_, _> = names.iter()
+        .filter_map(|name| claims.get(name).map(|v| (name.clone(), v.clone())))
+        .collect();
+    if !captured.is_empty() {
+        log_entry["claims"] = captured.into();
+    }
+}
+
+/// Adds the client address and TLS session fields selected in `capture_config`.
+pub(super) fn capture_client(log_entry: &mut serde_json::Value, capture_config: &CaptureConfig, client: &ClientInfo) {
+    if let Some(ip) = client.ip.filter(|_| capture_config.client_ip) {
+        log_entry["client_ip"] = ip.to_string().into();
+    }
+    if let Some(port) = client.port.filter(|_| capture_config.client_port) {
+        log_entry["client_port"] = port.into();
+    }
+    let tls = client.tls.as_ref();
+    if let Some(version) = tls.and_then(|tls| tls.version.as_ref()).filter(|_| capture_config.tls_version) {
+        log_entry["tls_version"] = version.as_str().into();
+    }
+    if let Some(sni) = tls.and_then(|tls| tls.sni.as_ref()).filter(|_| capture_config.sni) {
+        log_entry["sni"] = sni.as_str().into();
+    }
+}
+
+/// Marks a log entry with the faults injected into its request.
+fn tag_fault(log_entry: &mut serde_json::Value, fault: Option<&InjectedFaults>) {
+    if let Some(fault) = fault {
+        log_entry["fault"] = serde_json::to_value(fault).unwrap_or_default();
+    }
+}
 
-    info!("{}", serde_json::to_string(&log_entry).unwrap_or_else(|_| "Failed to serialize response log".to_string()));
+fn emit_log(log_entry: &serde_json::Value) {
+    info!("{}", serde_json::to_string(log_entry).unwrap_or_else(|_| "Failed to serialize log entry".to_string()));
 }
 
 fn is_private_ipv6(ip: std::net::Ipv6Addr) -> bool {
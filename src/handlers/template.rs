@@ -0,0 +1,92 @@
+use axum::http::HeaderMap;
+use std::collections::HashMap;
+use std::sync::{LazyLock, OnceLock};
+
+static PLACEHOLDER: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r"\{\{\s*([^{}]+?)\s*\}\}").unwrap());
+
+/// Request fields available to `{{...}}` placeholders in mock responses and header rules.
+pub struct TemplateContext<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    pub headers: &'a HeaderMap,
+    pub body: &'a str,
+    /// Capture groups from the matching path pattern, keyed by name and index.
+    pub captures: HashMap<String, String>,
+    /// Request body parsed as JSON on first use; `None` if it is not valid JSON.
+    body_json: OnceLock<Option<serde_json::Value>>,
+    pub client_ip: Option<String>,
+    pub request_id: Option<String>,
+}
+
+impl<'a> TemplateContext<'a> {
+    pub fn new(
+        method: &'a str,
+        path: &'a str,
+        headers: &'a HeaderMap,
+        body: &'a str,
+        captures: HashMap<String, String>,
+    ) -> Self {
+        Self {
+            method,
+            path,
+            headers,
+            body,
+            captures,
+            body_json: OnceLock::new(),
+            client_ip: None,
+            request_id: None,
+        }
+    }
+
+    /// Adds the caller's address and the request ID, for `{{client_ip}}` and `{{request_id}}`.
+    pub fn with_client(mut self, client_ip: Option<String>, request_id: &str) -> Self {
+        self.client_ip = client_ip;
+        self.request_id = Some(request_id.to_string());
+        self
+    }
+
+    /// The request body parsed as JSON, if it is valid JSON.
+    pub fn body_json(&self) -> Option<&serde_json::Value> {
+        self.body_json.get_or_init(|| serde_json::from_str(self.body).ok()).as_ref()
+    }
+
+    /// Replaces every `{{placeholder}}` in `template`. Unknown placeholders render as empty.
+    pub fn render(&self, template: &str) -> String {
+        if !template.contains("{{") {
+            return template.to_string();
+        }
+        PLACEHOLDER
+            .replace_all(template, |caps: &regex::Captures| self.lookup(&caps[1]).unwrap_or_default())
+            .into_owned()
+    }
+
+    fn lookup(&self, key: &str) -> Option<String> {
+        match key.split_once('.') {
+            None => match key {
+                "method" => Some(self.method.to_string()),
+                "path" => Some(self.path.to_string()),
+                "body" => Some(self.body.to_string()),
+                "client_ip" => self.client_ip.clone(),
+                "request_id" => self.request_id.clone(),
+                _ => None,
+            },
+            Some(("path", group)) => self.captures.get(group).cloned(),
+            Some(("header", name)) => self.headers.get(name)?.to_str().ok().map(str::to_string),
+            Some(("body", field)) => {
+                let mut value = self.body_json()?;
+                for segment in field.split('.') {
+                    value = match value {
+                        serde_json::Value::Array(items) => items.get(segment.parse::<usize>().ok()?)?,
+                        _ => value.get(segment)?,
+                    };
+                }
+                Some(match value {
+                    serde_json::Value::String(s) => s.clone(),
+                    other => other.to_string(),
+                })
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! Config version history. Every config installed by a [`ConfigHolder`](crate::config::ConfigHolder)
+//! gets a version number; the most recent ones are kept so an earlier config can be rolled back to.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt;
+
+/// One installed config.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigVersion {
+    pub version: u64,
+    pub installed_at: DateTime<Utc>,
+    /// What installed it: `startup`, `file_watch`, `sighup`, `api`, `put`, `rollback`, ...
+    pub trigger: String,
+    /// The config as JSON. Rolling back re-validates and re-loads it like a new config.
+    #[serde(skip)]
+    pub content: Value,
+}
+
+/// The most recent config versions, oldest first. The last entry is the active config.
+#[derive(Debug, Default)]
+pub struct ConfigHistory {
+    versions: VecDeque<ConfigVersion>,
+    next_version: u64,
+}
+
+impl ConfigHistory {
+    /// Records a newly installed config, keeping at most `keep` versions (at least the new one).
+    /// Returns the new version and the changes since the previous one.
+    pub fn record(&mut self, content: Value, trigger: &str, keep: usize) -> (u64, Option<u64>, Vec<String>) {
+        self.next_version += 1;
+        let previous = self.versions.back();
+        let changes = previous.map(|prev| diff_summary(&prev.content, &content)).unwrap_or_default();
+        let previous = previous.map(|prev| prev.version);
+        self.versions.push_back(ConfigVersion {
+            version: self.next_version,
+            installed_at: Utc::now(),
+            trigger: trigger.to_string(),
+            content,
+        });
+        while self.versions.len() > keep.max(1) {
+            self.versions.pop_front();
+        }
+        (self.next_version, previous, changes)
+    }
+
+    /// The active version, or 0 before any config is recorded.
+    pub fn current(&self) -> u64 {
+        self.versions.back().map_or(0, |v| v.version)
+    }
+
+    pub fn get(&self, version: u64) -> Option<&ConfigVersion> {
+        self.versions.iter().find(|v| v.version == version)
+    }
+
+    /// The kept versions, oldest first.
+    pub fn versions(&self) -> impl Iterator<Item = &ConfigVersion> {
+        self.versions.iter()
+    }
+}
+
+/// Why a rollback did not happen.
+#[derive(Debug)]
+pub enum RollbackError {
+    /// The version was never installed or is no longer kept.
+    UnknownVersion(u64),
+    /// The stored config no longer loads, e.g. because a file it references was removed.
+    Invalid(Box<dyn std::error::Error>),
+}
+
+impl fmt::Display for RollbackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownVersion(version) => write!(f, "config version {} is not in the history", version),
+            Self::Invalid(e) => write!(f, "config version could not be loaded: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RollbackError {}
+
+/// Summarises what changed between two configs, one line per changed setting. Rule lists are
+/// summarised by rule name; nested settings are reported down to `section.key.key`.
+pub fn diff_summary(old: &Value, new: &Value) -> Vec<String> {
+    let mut changes = Vec::new();
+    diff_value("", old, new, 0, &mut changes);
+    changes
+}
+
+const MAX_DEPTH: usize = 3;
+
+fn diff_value(path: &str, old: &Value, new: &Value, depth: usize, out: &mut Vec<String>) {
+    if old == new {
+        return;
+    }
+    match (old, new) {
+        (Value::Object(a), Value::Object(b)) if depth < MAX_DEPTH => {
+            let keys: BTreeSet<&String> = a.keys().chain(b.keys()).collect();
+            for key in keys {
+                let child = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                diff_value(&child, a.get(key).unwrap_or(&Value::Null), b.get(key).unwrap_or(&Value::Null), depth + 1, out);
+            }
+        }
+        (Value::Array(a), Value::Array(b)) if is_named(a) && is_named(b) => out.push(format!("{}: {}", path, diff_named(a, b))),
+        (Value::Array(a), Value::Array(b)) => out.push(format!("{}: {} -> {} items", path, a.len(), b.len())),
+        (Value::Object(_), _) | (_, Value::Object(_)) | (Value::Array(_), _) | (_, Value::Array(_)) => {
+            out.push(format!("{}: changed", path))
+        }
+        _ => out.push(format!("{}: {} -> {}", path, old, new)),
+    }
+}
+
+/// Whether every element is an object with a string `name`, as rules are.
+fn is_named(items: &[Value]) -> bool {
+    items.iter().all(|item| item.get("name").and_then(Value::as_str).is_some())
+}
+
+fn name(item: &Value) -> &str {
+    item.get("name").and_then(Value::as_str).unwrap_or_default()
+}
+
+fn diff_named(old: &[Value], new: &[Value]) -> String {
+    let find = |items: &'_ [Value], wanted: &str| items.iter().position(|item| name(item) == wanted);
+    let mut parts = Vec::new();
+    let added: Vec<&str> = new.iter().map(name).filter(|n| find(old, n).is_none()).collect();
+    let removed: Vec<&str> = old.iter().map(name).filter(|n| find(new, n).is_none()).collect();
+    let changed: Vec<&str> = new.iter()
+        .filter(|item| find(old, name(item)).is_some_and(|i| &old[i] != *item))
+        .map(name)
+        .collect();
+    let common_old: Vec<&str> = old.iter().map(name).filter(|n| find(new, n).is_some()).collect();
+    let common_new: Vec<&str> = new.iter().map(name).filter(|n| find(old, n).is_some()).collect();
+    for (label, names) in [("added", added), ("removed", removed), ("changed", changed)] {
+        if !names.is_empty() {
+            parts.push(format!("{} {}", label, names.iter().map(|n| format!("'{}'", n)).collect::<Vec<_>>().join(", ")));
+        }
+    }
+    if common_old != common_new {
+        parts.push("reordered".to_string());
+    }
+    if parts.is_empty() {
+        return "changed".to_string();
+    }
+    parts.join("; ")
+}
@@ -34,15 +34,30 @@
 //! }
 //! ```
 //!
+//! [`proxy_router`] and [`admin_router`] build the two routers the binary serves: proxied
+//! traffic and the admin endpoints, on separate listeners.
+//!
 //! ## Configuration
 //!
 //! See [`config::Config`] and the `/config/docs` endpoint (served by [`get_config_docs`])
 //! for full configuration reference.
 
+pub mod auth;
+pub mod concurrency;
 pub mod config;
 pub mod handlers;
+pub mod history;
+pub mod listener;
+pub mod metrics;
+pub mod rate_limiter;
+pub mod reload;
 
-pub use handlers::{get_health_check, get_config, get_config_docs, reload_config, proxy_handler};
+pub use handlers::{
+    admin_router, get_health_check, get_config, get_config_docs, get_config_history, get_config_schema, get_metrics,
+    proxy_handler, proxy_router, put_config, reload_config, rollback_config, test_config,
+};
 
+#[doc(hidden)]
+pub use handlers::decompress::decoded_text;
 #[doc(hidden)]
 pub use handlers::proxy::{extract_upstream_url, parse_duration_string};
@@ -0,0 +1,117 @@
+//! The proxy and admin listeners: plain HTTP, or TLS with optional client certificate
+//! verification. Every request carries the peer address as [`ConnectInfo`], and requests
+//! received over TLS carry a [`TlsInfo`].
+
+use crate::config::TlsConfig;
+use axum::extract::ConnectInfo;
+use axum::{Extension, Router};
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::service::TowerToHyperService;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{ProtocolVersion, RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+/// The TLS session a request arrived on.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TlsInfo {
+    /// Negotiated protocol version, e.g. `TLSv1.3`.
+    pub version: Option<String>,
+    /// Server name the client asked for (SNI).
+    pub sni: Option<String>,
+}
+
+/// Builds a TLS acceptor from PEM files. Client certificates are required when
+/// `client_ca_file` is set.
+pub fn tls_acceptor(tls: &TlsConfig) -> Result<TlsAcceptor, Box<dyn std::error::Error>> {
+    let certs = load_certs(&tls.cert_file)?;
+    let key = load_key(&tls.key_file)?;
+    let provider = Arc::new(tokio_rustls::rustls::crypto::ring::default_provider());
+    let builder = ServerConfig::builder_with_provider(provider.clone()).with_safe_default_protocol_versions()?;
+
+    let builder = match &tls.client_ca_file {
+        Some(ca_file) => {
+            let mut roots = RootCertStore::empty();
+            for cert in load_certs(ca_file)? {
+                roots.add(cert)?;
+            }
+            let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider).build()?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let mut config = builder.with_single_cert(certs, key)?;
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Serves `router` on `listener` until `shutdown` resolves, over TLS when `tls` is given.
+pub async fn serve(
+    listener: TcpListener,
+    router: Router,
+    tls: Option<TlsAcceptor>,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+) -> std::io::Result<()> {
+    let Some(acceptor) = tls else {
+        return axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>())
+            .with_graceful_shutdown(shutdown)
+            .await;
+    };
+
+    tokio::pin!(shutdown);
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => accepted?,
+            _ = &mut shutdown => return Ok(()),
+        };
+        let acceptor = acceptor.clone();
+        let router = router.clone();
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    tracing::warn!(peer = %peer, error = %e, "TLS handshake failed");
+                    return;
+                }
+            };
+            let session = stream.get_ref().1;
+            let tls = TlsInfo {
+                version: session.protocol_version().map(version_name),
+                sni: session.server_name().map(str::to_string),
+            };
+            let service = TowerToHyperService::new(router.layer(Extension(ConnectInfo(peer))).layer(Extension(tls)));
+            let builder = hyper_util::server::conn::auto::Builder::new(TokioExecutor::new());
+            if let Err(e) = builder.serve_connection(TokioIo::new(stream), service).await {
+                tracing::debug!(peer = %peer, error = %e, "connection closed with error");
+            }
+        });
+    }
+}
+
+fn version_name(version: ProtocolVersion) -> String {
+    match version {
+        ProtocolVersion::TLSv1_2 => "TLSv1.2".to_string(),
+        ProtocolVersion::TLSv1_3 => "TLSv1.3".to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+fn load_certs(path: &str) -> Result<Vec<CertificateDer<'static>>, Box<dyn std::error::Error>> {
+    let file = std::fs::File::open(path).map_err(|e| format!("Failed to open '{}': {}", path, e))?;
+    let certs = rustls_pemfile::certs(&mut std::io::BufReader::new(file)).collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(format!("No certificates found in '{}'", path).into());
+    }
+    Ok(certs)
+}
+
+fn load_key(path: &str) -> Result<PrivateKeyDer<'static>, Box<dyn std::error::Error>> {
+    let file = std::fs::File::open(path).map_err(|e| format!("Failed to open '{}': {}", path, e))?;
+    rustls_pemfile::private_key(&mut std::io::BufReader::new(file))?
+        .ok_or_else(|| format!("No private key found in '{}'", path).into())
+}
@@ -1,15 +1,28 @@
-pub mod config;
-pub mod handlers;
-
-use axum::{
-    routing::{get, post, Router},
-};
-use config::{Config, ConfigHolder};
+use clap::Parser;
+use logprox::config::{config_file_path, Config, ConfigHolder};
+use logprox::{handlers, listener, reload};
+use std::process::ExitCode;
 use std::sync::Arc;
-use tracing::{info, Level};
+use tokio::sync::watch;
+use tracing::{info, warn, Level};
+
+mod cli;
+
+fn main() -> ExitCode {
+    match cli::Cli::parse().command {
+        Some(cli::Command::Validate { file, allow_warnings, json }) => cli::validate(&file, allow_warnings, json),
+        Some(cli::Command::TestRule(args)) => cli::test_rule(*args),
+        Some(cli::Command::Schema) => cli::schema(),
+        Some(cli::Command::Docs) => cli::docs(),
+        None => {
+            serve();
+            ExitCode::SUCCESS
+        }
+    }
+}
 
 #[tokio::main]
-async fn main() {
+async fn serve() {
     // Initialize logging
     tracing_subscriber::fmt()
         .with_max_level(Level::INFO)
@@ -17,30 +30,121 @@ async fn main() {
         .init();
 
     // Load configuration
-    let config_file = std::env::var("CONFIG_FILE").unwrap_or_else(|_| "config.yaml".to_string());
+    let config_file = config_file_path();
     let config = Config::from_file(&config_file).unwrap_or_else(|e| {
         eprintln!("Failed to load config from {}: {}", config_file, e);
         std::process::exit(1);
     });
     let config_holder = Arc::new(ConfigHolder::new(config));
 
-    // Build our application with health check and config routes
-    let app = Router::new()
-        .route("/health", get(handlers::get_health_check))
-        .route("/config", get(handlers::get_config))
-        .route("/config/docs", get(handlers::get_config_docs))
-        .route("/config/reload", post(handlers::reload_config))
-        .fallback(handlers::proxy_handler)
-        .with_state(config_holder);
+    // Reload on config file changes and SIGHUP
+    let reload_config = config_holder.get().reload.clone();
+    let _watcher = if reload_config.watch {
+        match reload::watch_config_file(config_holder.clone(), &config_file, reload_config.parse_debounce()) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                warn!("Failed to watch {} for changes: {}", config_file, e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+    #[cfg(unix)]
+    if let Err(e) = reload::spawn_sighup_reloader(config_holder.clone(), config_file.clone()) {
+        warn!("Failed to install SIGHUP handler: {}", e);
+    }
+
+    // Periodically persist rate limit state so limits survive a restart
+    tokio::spawn(snapshot_rate_limits(config_holder.clone()));
+
+    // Both listeners stop on the same signal
+    let (shutdown_tx, shutdown_rx) = watch::channel(());
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        drop(shutdown_tx);
+    });
+    let shutdown = move || {
+        let mut rx = shutdown_rx.clone();
+        async move {
+            while rx.changed().await.is_ok() {}
+        }
+    };
+
+    // Admin routes get their own listener so proxy clients cannot reach them
+    let admin_config = config_holder.get().admin.clone();
+    let admin_tls = admin_config.tls.as_ref().map(listener::tls_acceptor).transpose().unwrap_or_else(|e| {
+        eprintln!("Failed to load admin TLS config: {}", e);
+        std::process::exit(1);
+    });
+    if admin_config.bearer_token_sha256.is_none()
+        && admin_config.tls.as_ref().and_then(|tls| tls.client_ca_file.as_ref()).is_none()
+    {
+        warn!("Admin listener has no bearer token or client certificate auth configured");
+    }
+    let admin_listener = tokio::net::TcpListener::bind(&admin_config.listen).await.unwrap_or_else(|e| {
+        eprintln!("Failed to bind admin listener on {}: {}", admin_config.listen, e);
+        std::process::exit(1);
+    });
+    info!("Starting admin server on {}", admin_config.listen);
+    let admin_server = tokio::spawn(listener::serve(
+        admin_listener,
+        handlers::admin_router(config_holder.clone()),
+        admin_tls,
+        shutdown(),
+    ));
+
+    let app = handlers::proxy_router(config_holder.clone());
 
     let port = std::env::var("PORT")
         .unwrap_or_else(|_| "3000".to_string())
         .parse::<u16>()
         .unwrap_or(3000);
     let addr = format!("0.0.0.0:{}", port);
-    info!("Starting proxy server on {}", addr);
+    let proxy_tls = config_holder.get().server.tls.as_ref().map(listener::tls_acceptor).transpose().unwrap_or_else(|e| {
+        eprintln!("Failed to load proxy TLS config: {}", e);
+        std::process::exit(1);
+    });
+    info!("Starting proxy server on {}{}", addr, if proxy_tls.is_some() { " (TLS)" } else { "" });
 
     // Run it
-    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    let proxy_listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+    listener::serve(proxy_listener, app, proxy_tls, shutdown()).await.unwrap();
+    if let Ok(Err(e)) = admin_server.await {
+        warn!("Admin server failed: {}", e);
+    }
+
+    if let Err(e) = config_holder.save_rate_limit_snapshot() {
+        warn!("Failed to save rate limit snapshot: {}", e);
+    }
+}
+
+async fn snapshot_rate_limits(config_holder: Arc<ConfigHolder>) {
+    loop {
+        let interval = config_holder.get().rate_limits.parse_snapshot_interval();
+        tokio::time::sleep(interval).await;
+        if let Err(e) = config_holder.save_rate_limit_snapshot() {
+            warn!("Failed to save rate limit snapshot: {}", e);
+        }
+    }
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.ok();
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        if let Ok(mut sigterm) = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            sigterm.recv().await;
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+    info!("Shutting down");
 }
@@ -0,0 +1,57 @@
+//! Process-wide counters exposed at `GET /metrics`.
+
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+/// Counters and gauges shared by all requests. Held by
+/// [`ConfigHolder`](crate::config::ConfigHolder) so values survive config reloads.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    /// Requests currently holding a concurrency slot.
+    pub in_flight: AtomicI64,
+    /// Requests currently waiting for a concurrency slot.
+    pub queue_depth: AtomicI64,
+    /// Requests shed because the wait queue was full.
+    pub shed_queue_full: AtomicU64,
+    /// Requests shed because they waited longer than `max_wait`.
+    pub shed_wait_timeout: AtomicU64,
+    /// Log entries left out by sampling, per rule, keyed `logging.NAME` or
+    /// `response_logging.NAME`.
+    sampled_out: Mutex<BTreeMap<String, u64>>,
+}
+
+/// Point-in-time copy of [`Metrics`], serialised as the `/metrics` response.
+#[derive(Debug, Serialize)]
+pub struct MetricsSnapshot {
+    pub in_flight: i64,
+    pub queue_depth: i64,
+    pub shed_total: u64,
+    pub shed_queue_full: u64,
+    pub shed_wait_timeout: u64,
+    pub sampled_out_total: u64,
+    pub sampled_out: BTreeMap<String, u64>,
+}
+
+impl Metrics {
+    /// Counts a log entry of rule `rule` in `section` that sampling left out.
+    pub fn record_sampled_out(&self, section: &str, rule: &str) {
+        *self.sampled_out.lock().entry(format!("{}.{}", section, rule)).or_default() += 1;
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        let shed_queue_full = self.shed_queue_full.load(Ordering::Relaxed);
+        let shed_wait_timeout = self.shed_wait_timeout.load(Ordering::Relaxed);
+        let sampled_out = self.sampled_out.lock().clone();
+        MetricsSnapshot {
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            queue_depth: self.queue_depth.load(Ordering::Relaxed),
+            shed_total: shed_queue_full + shed_wait_timeout,
+            shed_queue_full,
+            shed_wait_timeout,
+            sampled_out_total: sampled_out.values().sum(),
+            sampled_out,
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! In-memory rate limiter state for the `rate_limits:` config section.
+
+use crate::config::{RateLimitAlgorithm, RateLimitRule};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Number of tracked keys of a rule above which its idle entries are pruned.
+const PRUNE_THRESHOLD: usize = 10_000;
+
+/// Most keys tracked per rule. Requests with new keys beyond it share one counter, so
+/// client-chosen keys cannot grow the state without bound.
+const MAX_KEYS_PER_RULE: usize = 100_000;
+
+/// The key requests over [`MAX_KEYS_PER_RULE`] are counted under. Request keys are header
+/// values or IP addresses, which never contain a NUL.
+const OVERFLOW_KEY: &str = "\0overflow";
+
+/// Outcome of checking one request against one rate limit rule.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitDecision {
+    pub allowed: bool,
+    pub rule: String,
+    pub limit: u64,
+    pub remaining: u64,
+    /// Seconds until the limit is fully replenished.
+    pub reset_secs: u64,
+    /// Seconds until a rejected request may be retried. Zero when allowed.
+    pub retry_after_secs: u64,
+}
+
+impl RateLimitDecision {
+    /// `RateLimit-*` and `Retry-After` response headers for this decision.
+    pub fn headers(&self) -> Vec<(&'static str, String)> {
+        let mut headers = vec![
+            ("ratelimit-limit", self.limit.to_string()),
+            ("ratelimit-remaining", self.remaining.to_string()),
+            ("ratelimit-reset", self.reset_secs.to_string()),
+        ];
+        if !self.allowed {
+            headers.push(("retry-after", self.retry_after_secs.to_string()));
+        }
+        headers
+    }
+}
+
+/// Per-key counter state. Times are seconds since the Unix epoch so snapshots stay valid
+/// across restarts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "algorithm", rename_all = "snake_case")]
+enum LimitState {
+    TokenBucket { tokens: f64, updated_at: f64 },
+    SlidingWindow { window_start: f64, current: u64, previous: u64 },
+}
+
+impl LimitState {
+    fn new(algorithm: RateLimitAlgorithm, limit: f64, window: f64, now: f64) -> Self {
+        match algorithm {
+            RateLimitAlgorithm::TokenBucket => Self::TokenBucket { tokens: limit, updated_at: now },
+            RateLimitAlgorithm::SlidingWindow => Self::SlidingWindow {
+                window_start: (now / window).floor() * window,
+                current: 0,
+                previous: 0,
+            },
+        }
+    }
+
+    /// Whether the entry has fully recovered, so dropping it changes no decision.
+    fn is_idle(&self, window: f64, now: f64) -> bool {
+        match self {
+            Self::TokenBucket { updated_at, .. } => now - *updated_at >= window,
+            Self::SlidingWindow { window_start, .. } => now - *window_start >= 2.0 * window,
+        }
+    }
+
+    fn uses(&self, algorithm: RateLimitAlgorithm) -> bool {
+        matches!(
+            (self, algorithm),
+            (Self::TokenBucket { .. }, RateLimitAlgorithm::TokenBucket)
+                | (Self::SlidingWindow { .. }, RateLimitAlgorithm::SlidingWindow)
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SnapshotEntry {
+    rule: String,
+    key: String,
+    state: LimitState,
+}
+
+/// The counters of one rule, keyed by request key.
+#[derive(Debug, Default)]
+struct RuleState {
+    entries: HashMap<String, LimitState>,
+    /// Size at which idle entries are next pruned; doubles with the live entries so pruning
+    /// stays amortized O(1) per request.
+    prune_at: usize,
+}
+
+impl RuleState {
+    /// The key to count a request for `key` under, pruning idle entries first when a new key
+    /// would pass the prune size.
+    fn slot(&mut self, key: &str, window: f64, now: f64) -> String {
+        if self.entries.contains_key(key) {
+            return key.to_string();
+        }
+        if self.entries.len() >= self.prune_at.max(PRUNE_THRESHOLD) {
+            self.entries.retain(|_, state| !state.is_idle(window, now));
+            self.prune_at = self.entries.len() * 2;
+        }
+        if self.entries.len() >= MAX_KEYS_PER_RULE {
+            OVERFLOW_KEY.to_string()
+        } else {
+            key.to_string()
+        }
+    }
+}
+
+/// Rate limit counters, kept per rule name and keyed by request key. Held by
+/// [`ConfigHolder`](crate::config::ConfigHolder) so state survives config reloads.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    state: Mutex<HashMap<String, RuleState>>,
+}
+
+fn now_secs() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+}
+
+impl RateLimiter {
+    /// Counts one request for `key` against `rule`. Rules with an unparseable window always allow.
+    pub fn check(&self, rule: &RateLimitRule, key: &str) -> RateLimitDecision {
+        self.check_at(rule, key, now_secs())
+    }
+
+    /// [`check`](Self::check) at `now`, in seconds since the Unix epoch.
+    pub fn check_at(&self, rule: &RateLimitRule, key: &str, now: f64) -> RateLimitDecision {
+        let window = rule.parse_window().map(|w| w.as_secs_f64()).unwrap_or_default();
+        let limit = rule.limit as f64;
+        if window <= 0.0 {
+            return RateLimitDecision {
+                allowed: true,
+                rule: rule.name.clone(),
+                limit: rule.limit,
+                remaining: rule.limit,
+                reset_secs: 0,
+                retry_after_secs: 0,
+            };
+        }
+        // Nothing is ever allowed; the bucket has no rate to refill at, so retry after a window
+        if rule.limit == 0 {
+            let window_secs = (window.ceil() as u64).max(1);
+            return RateLimitDecision {
+                allowed: false,
+                rule: rule.name.clone(),
+                limit: 0,
+                remaining: 0,
+                reset_secs: window_secs,
+                retry_after_secs: window_secs,
+            };
+        }
+
+        let mut state = self.state.lock();
+        let rule_state = state.entry(rule.name.clone()).or_default();
+        let slot = rule_state.slot(key, window, now);
+
+        let fresh = || LimitState::new(rule.algorithm, limit, window, now);
+        let entry = rule_state.entries.entry(slot).or_insert_with(fresh);
+        // A rule switched algorithms across a reload: start it afresh.
+        if !entry.uses(rule.algorithm) {
+            *entry = fresh();
+        }
+
+        let (allowed, remaining, reset, retry_after) = match entry {
+            LimitState::TokenBucket { tokens, updated_at } => {
+                let rate = limit / window;
+                *tokens = (*tokens + (now - *updated_at).max(0.0) * rate).min(limit);
+                *updated_at = now;
+                if *tokens >= 1.0 {
+                    *tokens -= 1.0;
+                    (true, tokens.floor(), (limit - *tokens) / rate, 0.0)
+                } else {
+                    (false, 0.0, (limit - *tokens) / rate, (1.0 - *tokens) / rate)
+                }
+            }
+            LimitState::SlidingWindow { window_start, current, previous } => {
+                if now >= *window_start + 2.0 * window {
+                    *previous = 0;
+                    *current = 0;
+                    *window_start = (now / window).floor() * window;
+                } else if now >= *window_start + window {
+                    *previous = *current;
+                    *current = 0;
+                    *window_start += window;
+                }
+                let elapsed = now - *window_start;
+                let estimated = *previous as f64 * (1.0 - elapsed / window) + *current as f64;
+                let reset = window - elapsed;
+                if estimated + 1.0 <= limit {
+                    *current += 1;
+                    (true, (limit - estimated - 1.0).floor(), reset, 0.0)
+                } else {
+                    (false, 0.0, reset, reset)
+                }
+            }
+        };
+
+        RateLimitDecision {
+            allowed,
+            rule: rule.name.clone(),
+            limit: rule.limit,
+            remaining: remaining.max(0.0) as u64,
+            reset_secs: reset.max(0.0).ceil() as u64,
+            retry_after_secs: if allowed { 0 } else { (retry_after.ceil() as u64).max(1) },
+        }
+    }
+
+    /// Writes all counters to `path` as JSON.
+    pub fn save_snapshot(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let entries: Vec<SnapshotEntry> = self
+            .state
+            .lock()
+            .iter()
+            .flat_map(|(rule, rule_state)| {
+                rule_state.entries.iter().map(move |(key, state)| SnapshotEntry {
+                    rule: rule.clone(),
+                    key: key.clone(),
+                    state: state.clone(),
+                })
+            })
+            .collect();
+        // Write then rename so a crash mid-write never leaves a truncated snapshot.
+        let tmp_path = format!("{}.tmp", path);
+        std::fs::write(&tmp_path, serde_json::to_vec(&entries)?)?;
+        std::fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+
+    /// Replaces all counters with those saved in `path`. A missing file is not an error.
+    pub fn load_snapshot(&self, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let content = match std::fs::read(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e.into()),
+        };
+        let entries: Vec<SnapshotEntry> = serde_json::from_slice(&content)?;
+        let mut state: HashMap<String, RuleState> = HashMap::new();
+        for e in entries {
+            state.entry(e.rule).or_default().entries.insert(e.key, e.state);
+        }
+        *self.state.lock() = state;
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+//! Config reload triggers: file changes and SIGHUP. Every reload goes through
+//! [`reload_and_log`], so a config that fails to load leaves the previous one in place.
+
+use crate::config::sources::{self, overlay_path, ENV_VAR};
+use crate::config::{Config, ConfigHolder};
+use notify_debouncer_mini::notify::{RecommendedWatcher, RecursiveMode};
+use notify_debouncer_mini::{new_debouncer, DebounceEventResult, Debouncer};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Reloads the config from `path` and logs the outcome, tagged with what triggered it.
+pub fn reload_and_log(config: &ConfigHolder, path: &str, trigger: &str) -> Result<(), Box<dyn std::error::Error>> {
+    match Config::from_file(path) {
+        Ok(new_config) => {
+            let version = config.install(new_config, trigger);
+            info!(trigger, file = %path, version, "configuration reloaded");
+            Ok(())
+        }
+        Err(e) => {
+            warn!(trigger, file = %path, error = %e, "configuration reload failed, keeping previous configuration");
+            Err(e)
+        }
+    }
+}
+
+/// Watches the config at `path` and reloads once changes have been quiet for `debounce`.
+/// Watches the directories holding the config's files as loaded at startup, including
+/// includes and the overlay, so editors that replace files on save are picked up. A change to
+/// any file of the current config, a new overlay, or any YAML file of a config directory
+/// triggers a reload. Watching stops when the returned debouncer is dropped.
+pub fn watch_config_file(
+    config: Arc<ConfigHolder>,
+    path: &str,
+    debounce: Duration,
+) -> notify_debouncer_mini::notify::Result<Debouncer<RecommendedWatcher>> {
+    let root = Path::new(path);
+    let config_dir = root.is_dir();
+    let mut dirs = BTreeSet::new();
+    dirs.insert(if config_dir { root.to_path_buf() } else { parent_dir(root) });
+    for file in &config.get().source_files {
+        dirs.insert(parent_dir(Path::new(file)));
+    }
+    let overlay = std::env::var(ENV_VAR).ok()
+        .and_then(|env| overlay_path(root, &env))
+        .and_then(|overlay| overlay.file_name().map(|name| name.to_os_string()));
+    let root_name = root.file_name().map(|name| name.to_os_string());
+    let watched = config.clone();
+    let path = path.to_string();
+
+    let is_config_file = move |file: &Path| {
+        let name = file.file_name();
+        if config_dir {
+            return sources::is_config_file(file);
+        }
+        name == root_name.as_deref()
+            || name == overlay.as_deref()
+            || watched.get().source_files.iter().any(|source| Path::new(source).file_name() == name)
+    };
+    let mut debouncer = new_debouncer(debounce, move |result: DebounceEventResult| match result {
+        Ok(events) => {
+            if events.iter().any(|event| is_config_file(&event.path)) {
+                let _ = reload_and_log(&config, &path, "file_watch");
+            }
+        }
+        Err(e) => warn!(error = %e, "config file watch error"),
+    })?;
+    for dir in &dirs {
+        debouncer.watcher().watch(dir, RecursiveMode::NonRecursive)?;
+    }
+    Ok(debouncer)
+}
+
+fn parent_dir(file: &Path) -> PathBuf {
+    match file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => Path::new(".").to_path_buf(),
+    }
+}
+
+/// Reloads the config from `path` on every SIGHUP. The handler is installed before this
+/// returns, so a SIGHUP sent afterwards never falls through to the default (terminate) action.
+#[cfg(unix)]
+pub fn spawn_sighup_reloader(config: Arc<ConfigHolder>, path: String) -> std::io::Result<tokio::task::JoinHandle<()>> {
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+    Ok(tokio::spawn(async move {
+        while hangup.recv().await.is_some() {
+            let _ = reload_and_log(&config, &path, "sighup");
+        }
+    }))
+}
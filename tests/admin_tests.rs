@@ -0,0 +1,148 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use logprox::listener;
+use logprox::config::{AdminConfig, Config, ConfigHolder, TlsConfig};
+use logprox::{admin_router, proxy_router};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tower::util::ServiceExt;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/admin_tls");
+
+fn holder(yaml: &str) -> Arc<ConfigHolder> {
+    Arc::new(ConfigHolder::new(serde_norway::from_str(yaml).unwrap()))
+}
+
+fn get(uri: &str, token: Option<&str>) -> Request<Body> {
+    let mut builder = Request::builder().uri(uri);
+    if let Some(token) = token {
+        builder = builder.header("authorization", format!("Bearer {}", token));
+    }
+    builder.body(Body::empty()).unwrap()
+}
+
+#[tokio::test]
+async fn test_proxy_router_does_not_serve_admin_routes() {
+    let state = holder(
+        r#"
+logging: { default: false, rules: [] }
+drop: { default: false, rules: [] }
+mock:
+  rules:
+    - name: "Catch-all"
+      match_conditions: {}
+      response:
+        body: "proxied {{path}}"
+"#,
+    );
+    let app = proxy_router(state);
+
+    for path in ["/config", "/config/docs", "/metrics"] {
+        let resp = app.clone().oneshot(get(path, None)).await.unwrap();
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, format!("proxied {}", path));
+    }
+    let resp = app.oneshot(get("/health", None)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_admin_router_requires_bearer_token() {
+    let state = holder(&format!(
+        r#"
+logging: {{ default: false, rules: [] }}
+drop: {{ default: false, rules: [] }}
+admin:
+  bearer_token_sha256: "{}"
+"#,
+        hex::encode(Sha256::digest(b"admin-secret"))
+    ));
+    let app = admin_router(state);
+
+    let resp = app.clone().oneshot(get("/config", None)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert!(resp.headers().contains_key("www-authenticate"));
+
+    let resp = app.clone().oneshot(get("/config", Some("wrong"))).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = app.clone().oneshot(
+        Request::builder().method("POST").uri("/config/reload").body(Body::empty()).unwrap(),
+    ).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    for path in ["/config", "/config/docs", "/metrics", "/health"] {
+        let resp = app.clone().oneshot(get(path, Some("admin-secret"))).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK, "{}", path);
+    }
+
+    // Unknown paths are not proxied from the admin listener
+    let resp = app.oneshot(get("/https://example.com/", Some("admin-secret"))).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn test_admin_config_defaults_and_validation() {
+    let config: Config = serde_norway::from_str("logging: { default: false, rules: [] }\ndrop: { default: false, rules: [] }").unwrap();
+    assert_eq!(config.admin.listen, "127.0.0.1:9090");
+    assert_eq!(config.admin.token_hash(), Ok(None));
+
+    let bad = AdminConfig { bearer_token_sha256: Some("not-hex".to_string()), ..Default::default() };
+    assert!(bad.token_hash().is_err());
+}
+
+async fn https_get(addr: std::net::SocketAddr, with_client_cert: bool) -> std::io::Result<String> {
+    let mut roots = RootCertStore::empty();
+    let ca = std::fs::read(format!("{}/ca.pem", FIXTURES)).unwrap();
+    for cert in rustls_pemfile::certs(&mut ca.as_slice()) {
+        roots.add(cert.unwrap()).unwrap();
+    }
+    let provider = Arc::new(tokio_rustls::rustls::crypto::ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots);
+    let config = if with_client_cert {
+        let cert = std::fs::read(format!("{}/client.pem", FIXTURES)).unwrap();
+        let key = std::fs::read(format!("{}/client.key", FIXTURES)).unwrap();
+        let certs = rustls_pemfile::certs(&mut cert.as_slice()).collect::<Result<Vec<_>, _>>().unwrap();
+        let key = rustls_pemfile::private_key(&mut key.as_slice()).unwrap().unwrap();
+        builder.with_client_auth_cert(certs, key).unwrap()
+    } else {
+        builder.with_no_client_auth()
+    };
+
+    let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+    let tcp = tokio::net::TcpStream::connect(addr).await?;
+    let mut tls = connector.connect(ServerName::try_from("localhost").unwrap(), tcp).await?;
+    tls.write_all(b"GET /health HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n").await?;
+    let mut response = String::new();
+    tls.read_to_string(&mut response).await?;
+    Ok(response)
+}
+
+#[tokio::test]
+async fn test_admin_mtls_requires_client_certificate() {
+    let tls = TlsConfig {
+        cert_file: format!("{}/server.pem", FIXTURES),
+        key_file: format!("{}/server.key", FIXTURES),
+        client_ca_file: Some(format!("{}/ca.pem", FIXTURES)),
+    };
+    let acceptor = listener::tls_acceptor(&tls).unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let state = holder("logging: { default: false, rules: [] }\ndrop: { default: false, rules: [] }");
+    let server = tokio::spawn(listener::serve(listener, admin_router(state), Some(acceptor), std::future::pending()));
+
+    let response = https_get(addr, true).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.ends_with("OK"));
+
+    let rejected = https_get(addr, false).await;
+    assert!(!rejected.is_ok_and(|r| r.contains("200")));
+
+    server.abort();
+}
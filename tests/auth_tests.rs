@@ -0,0 +1,396 @@
+use axum::body::Body;
+use axum::http::{HeaderMap, Request, StatusCode};
+use axum::Router;
+use base64::Engine;
+use jsonwebtoken::{EncodingKey, Header};
+use logprox::auth::AuthError;
+use logprox::config::{Config, ConfigHolder, RequestContext};
+use logprox::proxy_handler;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use tower::util::ServiceExt;
+
+const HMAC_SECRET: &[u8] = b"test-signing-secret";
+
+fn write_file(dir: &tempfile::TempDir, name: &str, content: &str) -> String {
+    let path = dir.path().join(name);
+    std::fs::write(&path, content).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+fn config_from_yaml(dir: &tempfile::TempDir, yaml: &str) -> Config {
+    let config_path = write_file(dir, "config.yaml", yaml);
+    Config::from_file(&config_path).unwrap()
+}
+
+fn create_proxy_app(config: Config) -> Router {
+    Router::new()
+        .fallback(proxy_handler)
+        .with_state(Arc::new(ConfigHolder::new(config)))
+}
+
+async fn body_string(resp: axum::response::Response) -> String {
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+fn get(uri: &str, header: Option<(&str, &str)>) -> Request<Body> {
+    let mut builder = Request::builder().uri(uri);
+    if let Some((name, value)) = header {
+        builder = builder.header(name, value);
+    }
+    builder.body(Body::empty()).unwrap()
+}
+
+fn write_jwks(dir: &tempfile::TempDir) -> String {
+    let k = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(HMAC_SECRET);
+    write_file(
+        dir,
+        "jwks.json",
+        &format!(r#"{{"keys": [{{"kty": "oct", "kid": "k1", "alg": "HS256", "k": "{}"}}]}}"#, k),
+    )
+}
+
+fn sign(claims: serde_json::Value) -> String {
+    let header = Header { kid: Some("k1".to_string()), ..Header::default() };
+    jsonwebtoken::encode(&header, &claims, &EncodingKey::from_secret(HMAC_SECRET)).unwrap()
+}
+
+fn in_an_hour() -> u64 {
+    jsonwebtoken::get_current_timestamp() + 3600
+}
+
+#[tokio::test]
+async fn test_api_key_auth() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = config_from_yaml(
+        &dir,
+        &format!(
+            r#"
+logging: {{ default: false, rules: [] }}
+drop: {{ default: false, rules: [] }}
+auth:
+  api_keys:
+    keys:
+      - name: "frontend"
+        sha256: "{}"
+mock:
+  rules:
+    - name: "Frontend"
+      match_conditions:
+        claims: {{ sub: "^frontend$" }}
+      response:
+        body: "hello frontend"
+"#,
+            hex::encode(Sha256::digest(b"secret-key"))
+        ),
+    );
+    let app = create_proxy_app(config);
+
+    let resp = app.clone().oneshot(get("/anything", None)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert!(resp.headers().get("www-authenticate").is_none());
+
+    let resp = app.clone().oneshot(get("/anything", Some(("x-api-key", "wrong")))).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = app.oneshot(get("/anything", Some(("x-api-key", "secret-key")))).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(body_string(resp).await, "hello frontend");
+}
+
+#[tokio::test]
+async fn test_basic_auth_htpasswd() {
+    let dir = tempfile::tempdir().unwrap();
+    let sha = base64::engine::general_purpose::STANDARD.encode(sha1::Sha1::digest(b"hunter2"));
+    let htpasswd = write_file(
+        &dir,
+        "htpasswd",
+        &format!(
+            "# users\nalice:{}\nbob:{{SHA}}{}\n",
+            bcrypt::hash("wonderland", 4).unwrap(),
+            sha
+        ),
+    );
+    let config = config_from_yaml(
+        &dir,
+        &format!(
+            r#"
+logging: {{ default: false, rules: [] }}
+drop: {{ default: false, rules: [] }}
+auth:
+  basic:
+    htpasswd_file: "{}"
+mock:
+  rules:
+    - name: "Echo"
+      match_conditions: {{}}
+      response:
+        body: "ok"
+"#,
+            htpasswd
+        ),
+    );
+    let app = create_proxy_app(config);
+    let basic = |user: &str, pass: &str| {
+        format!("Basic {}", base64::engine::general_purpose::STANDARD.encode(format!("{}:{}", user, pass)))
+    };
+
+    let resp = app.clone().oneshot(get("/x", None)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(resp.headers().get("www-authenticate").unwrap(), "Basic realm=\"logprox\"");
+
+    for (user, pass, expected) in [
+        ("alice", "wonderland", StatusCode::OK),
+        ("bob", "hunter2", StatusCode::OK),
+        ("alice", "hunter2", StatusCode::UNAUTHORIZED),
+        ("carol", "wonderland", StatusCode::UNAUTHORIZED),
+    ] {
+        let auth = basic(user, pass);
+        let resp = app.clone().oneshot(get("/x", Some(("authorization", &auth)))).await.unwrap();
+        assert_eq!(resp.status(), expected, "{}:{}", user, pass);
+    }
+}
+
+#[tokio::test]
+async fn test_jwt_claims_in_match_conditions() {
+    let dir = tempfile::tempdir().unwrap();
+    let jwks = write_jwks(&dir);
+    let config = config_from_yaml(
+        &dir,
+        &format!(
+            r#"
+logging: {{ default: false, rules: [] }}
+drop:
+  default: false
+  rules:
+    - name: "Admins only"
+      match_conditions:
+        path: {{ patterns: ["^/admin"] }}
+        claims: {{ "realm.roles": "^user$" }}
+      response:
+        status_code: 403
+mock:
+  rules:
+    - name: "Admin"
+      match_conditions:
+        claims: {{ "realm.roles": "^admin$", tenant: "^42$" }}
+      response:
+        body: "admin"
+    - name: "Everyone else"
+      match_conditions: {{}}
+      response:
+        body: "user"
+auth:
+  jwt:
+    jwks_file: "{}"
+    issuers: ["https://issuer.example.com"]
+    audiences: ["logprox"]
+"#,
+            jwks
+        ),
+    );
+    let app = create_proxy_app(config);
+    let token = |roles: &[&str]| {
+        sign(serde_json::json!({
+            "sub": "ada",
+            "iss": "https://issuer.example.com",
+            "aud": "logprox",
+            "exp": in_an_hour(),
+            "tenant": 42,
+            "realm": {"roles": roles},
+        }))
+    };
+
+    let admin = format!("Bearer {}", token(&["admin"]));
+    let resp = app.clone().oneshot(get("/admin", Some(("authorization", &admin)))).await.unwrap();
+    assert_eq!(body_string(resp).await, "admin");
+
+    let user = format!("Bearer {}", token(&["user"]));
+    let resp = app.clone().oneshot(get("/other", Some(("authorization", &user)))).await.unwrap();
+    assert_eq!(body_string(resp).await, "user");
+    let resp = app.clone().oneshot(get("/admin", Some(("authorization", &user)))).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+
+    let resp = app.oneshot(get("/other", None)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(resp.headers().get("www-authenticate").unwrap(), "Bearer realm=\"logprox\"");
+}
+
+#[tokio::test]
+async fn test_jwt_rejects_bad_issuer_audience_and_expiry() {
+    let dir = tempfile::tempdir().unwrap();
+    let jwks = write_jwks(&dir);
+    let config = config_from_yaml(
+        &dir,
+        &format!(
+            r#"
+logging: {{ default: false, rules: [] }}
+drop: {{ default: false, rules: [] }}
+auth:
+  jwt:
+    jwks_file: "{}"
+    issuers: ["https://issuer.example.com"]
+    audiences: ["logprox"]
+    algorithms: ["HS256"]
+    leeway: 0s
+"#,
+            jwks
+        ),
+    );
+    let valid = serde_json::json!({
+        "sub": "ada",
+        "iss": "https://issuer.example.com",
+        "aud": ["other", "logprox"],
+        "exp": in_an_hour(),
+    });
+    let config = &config;
+    let authenticate = |claims: serde_json::Value| async move {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", format!("Bearer {}", sign(claims)).parse().unwrap());
+        config
+            .authenticate_ctx(&RequestContext::new("GET", "/", &headers, ""))
+            .await
+            .map(|identity| identity.unwrap().claims["sub"].clone())
+    };
+
+    assert_eq!(authenticate(valid.clone()).await.unwrap(), "ada");
+
+    let mut wrong_issuer = valid.clone();
+    wrong_issuer["iss"] = "https://evil.example.com".into();
+    let mut wrong_audience = valid.clone();
+    wrong_audience["aud"] = "someone-else".into();
+    let mut expired = valid.clone();
+    expired["exp"] = (jsonwebtoken::get_current_timestamp() - 10).into();
+    let mut no_expiry = valid.clone();
+    no_expiry.as_object_mut().unwrap().remove("exp");
+
+    for claims in [wrong_issuer, wrong_audience, expired, no_expiry] {
+        assert!(
+            matches!(authenticate(claims.clone()).await, Err(AuthError::InvalidCredentials(_))),
+            "accepted {}",
+            claims
+        );
+    }
+
+    let mut headers = HeaderMap::new();
+    headers.insert("authorization", "Bearer not-a-jwt".parse().unwrap());
+    assert!(config.authenticate_ctx(&RequestContext::new("GET", "/", &headers, "")).await.is_err());
+}
+
+#[tokio::test]
+async fn test_auth_scope_and_credential_stripping() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = config_from_yaml(
+        &dir,
+        &format!(
+            r#"
+logging: {{ default: false, rules: [] }}
+drop: {{ default: false, rules: [] }}
+auth:
+  match_conditions:
+    path: {{ patterns: ["^/private"] }}
+  strip_credentials: true
+  api_keys:
+    header: "X-Token"
+    keys:
+      - name: "ci"
+        sha256: "{}"
+mock:
+  rules:
+    - name: "Echo"
+      match_conditions: {{}}
+      response:
+        body: "token={{{{header.x-token}}}}"
+"#,
+            hex::encode(Sha256::digest(b"ci-token"))
+        ),
+    );
+    let app = create_proxy_app(config);
+
+    let resp = app.clone().oneshot(get("/public", None)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = app.clone().oneshot(get("/public", Some(("x-token", "bogus")))).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let resp = app.clone().oneshot(get("/private", None)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let resp = app.oneshot(get("/private", Some(("x-token", "ci-token")))).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(body_string(resp).await, "token=");
+}
+
+#[tokio::test]
+async fn test_unscoped_credentials_are_verified_only_for_claim_rules() {
+    let dir = tempfile::tempdir().unwrap();
+    let yaml = |rules: &str| {
+        format!(
+            r#"
+logging: {{ default: false, rules: [] }}
+drop: {{ default: false, rules: [] }}
+auth:
+  match_conditions:
+    path: {{ patterns: ["^/private"] }}
+  api_keys:
+    keys:
+      - name: "ci"
+        sha256: "{}"
+mock:
+  rules: {}
+"#,
+            hex::encode(Sha256::digest(b"ci-key")),
+            rules
+        )
+    };
+    let mut headers = HeaderMap::new();
+    headers.insert("x-api-key", "ci-key".parse().unwrap());
+    let private = RequestContext::new("GET", "/private", &headers, "");
+    let public = RequestContext::new("GET", "/public", &headers, "");
+
+    let config = config_from_yaml(&dir, &yaml("[]"));
+    assert!(config.authenticate_ctx(&private).await.unwrap().is_some());
+    assert!(config.authenticate_ctx(&public).await.unwrap().is_none());
+
+    // A nested claims condition anywhere makes the identity worth verifying
+    let config = config_from_yaml(&dir, &yaml(r#"[{ name: "CI", match_conditions: { not: { claims: { sub: "^ci$" } } }, response: { body: "ok" } }]"#));
+    assert!(config.authenticate_ctx(&public).await.unwrap().is_some());
+}
+
+#[test]
+fn test_invalid_auth_files_fail_load() {
+    let dir = tempfile::tempdir().unwrap();
+    let htpasswd = write_file(&dir, "htpasswd", "alice:plaintext\n");
+    let config_path = write_file(
+        &dir,
+        "config.yaml",
+        &format!(
+            r#"
+logging: {{ default: false, rules: [] }}
+drop: {{ default: false, rules: [] }}
+auth:
+  basic:
+    htpasswd_file: "{}"
+"#,
+            htpasswd
+        ),
+    );
+    let err = Config::from_file(&config_path).unwrap_err();
+    assert!(err.to_string().contains("Unsupported htpasswd hash"));
+
+    let config_path = write_file(
+        &dir,
+        "config.yaml",
+        r#"
+logging: { default: false, rules: [] }
+drop: { default: false, rules: [] }
+auth:
+  api_keys:
+    keys:
+      - name: "short"
+        sha256: "abc123"
+"#,
+    );
+    let err = Config::from_file(&config_path).unwrap_err();
+    assert!(err.to_string().contains("not a hex SHA-256 hash"));
+}
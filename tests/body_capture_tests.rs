@@ -0,0 +1,107 @@
+use logprox::config::{BodyCaptureOptions, Config};
+use logprox::handlers::dry_run::{dry_run, DryRunInput, SyntheticRequest, SyntheticResponse};
+use serde_json::json;
+
+fn captured(options: &BodyCaptureOptions, content_type: Option<&str>, body: &[u8]) -> serde_json::Value {
+    options.capture(content_type, body).into()
+}
+
+#[test]
+fn test_bodies_are_truncated_with_flag_and_original_size() {
+    let options = BodyCaptureOptions { max_body_bytes: Some(5), ..Default::default() };
+    assert_eq!(
+        captured(&options, None, b"hello world"),
+        json!({"body": "hello", "body_truncated": true, "body_size": 11})
+    );
+    assert_eq!(captured(&options, None, b"short"), json!({"body": "short"}));
+    // A cut through a multi-byte character drops the partial character, not the encoding
+    assert_eq!(
+        captured(&options, None, "hhhhé!".as_bytes()),
+        json!({"body": "hhhh", "body_truncated": true, "body_size": 7})
+    );
+}
+
+#[test]
+fn test_binary_bodies_are_base64_encoded() {
+    let options = BodyCaptureOptions::default();
+    assert_eq!(
+        captured(&options, Some("application/octet-stream"), &[0xff, 0x00, 0x10]),
+        json!({"body": "/wAQ", "body_encoding": "base64"})
+    );
+    let truncated = BodyCaptureOptions { max_body_bytes: Some(2), ..Default::default() };
+    assert_eq!(
+        captured(&truncated, None, &[0xff, 0x00, 0x10]),
+        json!({"body": "/wA=", "body_encoding": "base64", "body_truncated": true, "body_size": 3})
+    );
+}
+
+#[test]
+fn test_content_type_allow_and_deny_lists() {
+    let options = BodyCaptureOptions {
+        body_content_types: vec!["application/json".into(), "text/*".into()],
+        skip_body_content_types: vec!["text/csv".into()],
+        ..Default::default()
+    };
+    assert!(options.logs_content_type(Some("application/json; charset=utf-8")));
+    assert!(options.logs_content_type(Some("Text/HTML")));
+    assert!(!options.logs_content_type(Some("text/csv")));
+    assert!(!options.logs_content_type(Some("image/png")));
+    assert!(!options.logs_content_type(None));
+    assert_eq!(captured(&options, Some("image/png"), b"\x89PNG"), json!({"body_size": 4}));
+
+    let skip_images = BodyCaptureOptions { skip_body_content_types: vec!["image/*".into()], ..Default::default() };
+    assert!(skip_images.logs_content_type(None));
+    assert!(!skip_images.logs_content_type(Some("image/webp")));
+}
+
+#[test]
+fn test_json_bodies_are_logged_structured() {
+    let options = BodyCaptureOptions { json_body: true, ..Default::default() };
+    assert_eq!(captured(&options, None, br#"{"a":[1,2]}"#), json!({"body": {"a": [1, 2]}}));
+    assert_eq!(captured(&options, None, b"42"), json!({"body": "42"}));
+    assert_eq!(captured(&options, None, b"{broken"), json!({"body": "{broken"}));
+}
+
+const CONFIG: &str = r#"
+logging:
+  default: false
+  rules:
+    - name: "Events"
+      match_conditions: {}
+      capture: { body: true, json_body: true, max_body_bytes: 1024 }
+drop: { default: false, rules: [] }
+response_logging:
+  default: false
+  rules:
+    - name: "All"
+      match_conditions: {}
+      capture: { body: true, skip_body_content_types: ["image/*"] }
+"#;
+
+#[test]
+fn test_log_entries_use_capture_options() {
+    let (config, issues) = Config::check_source(CONFIG);
+    assert!(issues.is_empty(), "{:?}", issues);
+    let input = DryRunInput {
+        request: SyntheticRequest { path: "/https://api.example.com/events".into(), body: r#"{"event":"signup"}"#.into(), ..Default::default() },
+        response: Some(SyntheticResponse {
+            status_code: 200,
+            headers: [("content-type".to_string(), "image/png".to_string())].into(),
+            body: "not really a png".into(),
+            ..Default::default()
+        }),
+    };
+    let report = dry_run(&config.unwrap(), &input).unwrap();
+    assert_eq!(report.request_log.unwrap()["body"], json!({"event": "signup"}));
+    let response_log = report.response_log.unwrap();
+    assert!(response_log.get("body").is_none());
+    assert_eq!(response_log["body_size"], 16);
+}
+
+#[test]
+fn test_content_types_without_subtype_are_warned_about() {
+    let yaml = CONFIG.replace(r#"["image/*"]"#, r#"["image"]"#);
+    let (_, issues) = Config::check_source(&yaml);
+    let found: Vec<(&str, bool)> = issues.iter().map(|issue| (issue.path.as_str(), issue.is_error())).collect();
+    assert_eq!(found, vec![("response_logging.rules[0].capture.skip_body_content_types[0]", false)]);
+}
@@ -0,0 +1,198 @@
+use axum::http::{HeaderMap, HeaderValue};
+use logprox::config::{BodyFields, Config, GraphqlOperationType};
+use logprox::handlers::dry_run::{dry_run, DryRunInput, SyntheticRequest};
+use serde_json::json;
+
+const BOUNDARY: &str = "----logprox7MA4YWxk";
+
+fn content_type(value: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert("content-type", HeaderValue::from_str(value).unwrap());
+    headers
+}
+
+fn multipart_type() -> String {
+    format!("multipart/form-data; boundary={}", BOUNDARY)
+}
+
+fn multipart_body() -> String {
+    format!(
+        "preamble\r\n--{b}\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nHoliday\r\n\
+         --{b}\r\nContent-Disposition: form-data; name=\"avatar\"; filename=\"me.png\"\r\nContent-Type: image/png\r\n\r\n\
+         \u{89}PNG title=secret --{b}x\r\n\
+         --{b}\r\nContent-Disposition: form-data; name=\"tag\"\r\n\r\na\r\n\
+         --{b}\r\nContent-Disposition: form-data; name=\"tag\"\r\n\r\nb\r\n\
+         --{b}--\r\n",
+        b = BOUNDARY
+    )
+}
+
+fn graphql_body(query: &str, operation_name: Option<&str>) -> String {
+    json!({"query": query, "operationName": operation_name, "variables": {}}).to_string()
+}
+
+#[test]
+fn test_form_and_multipart_bodies_are_parsed() {
+    let form = BodyFields::parse(&content_type("application/x-www-form-urlencoded"), "user=ada+l&role=admin&role=ops&x=%3D");
+    assert_eq!(
+        form.form,
+        vec![("user".into(), "ada l".into()), ("role".into(), "admin".into()), ("role".into(), "ops".into()), ("x".into(), "=".into())]
+    );
+
+    let fields = BodyFields::parse(&content_type(&multipart_type()), &multipart_body());
+    assert_eq!(fields.form, vec![("title".into(), "Holiday".into()), ("tag".into(), "a".into()), ("tag".into(), "b".into())]);
+    let parts: Vec<_> = fields.parts.iter()
+        .map(|p| (p.name.as_deref(), p.filename.as_deref(), p.content_type.as_deref()))
+        .collect();
+    assert_eq!(
+        parts,
+        vec![(Some("title"), None, None), (Some("avatar"), Some("me.png"), Some("image/png")), (Some("tag"), None, None), (Some("tag"), None, None)]
+    );
+    assert!(BodyFields::parse(&content_type("text/plain"), "user=ada").form.is_empty());
+}
+
+#[test]
+fn test_graphql_operations_are_identified() {
+    let json = content_type("application/json");
+    let operation = |query: &str, name: Option<&str>| BodyFields::parse(&json, &graphql_body(query, name)).graphql.unwrap();
+
+    let named = operation("mutation CreateUser($input: UserInput!) { createUser(input: $input) { id } }", None);
+    assert_eq!(named.operation_name.as_deref(), Some("CreateUser"));
+    assert_eq!(named.operation_type, Some(GraphqlOperationType::Mutation));
+
+    let shorthand = operation("{ me { name } }", None);
+    assert_eq!((shorthand.operation_name, shorthand.operation_type), (None, Some(GraphqlOperationType::Query)));
+
+    let document = r#"
+        # mutation Hidden { x }
+        fragment Fields on User { id name }
+        query GetUser { user(note: "{ mutation Fake }") { ...Fields } }
+        subscription OnMessage @live { message { text } }
+    "#;
+    assert_eq!(operation(document, None).operation_type, Some(GraphqlOperationType::Query));
+    let selected = operation(document, Some("OnMessage"));
+    assert_eq!(selected.operation_name.as_deref(), Some("OnMessage"));
+    assert_eq!(selected.operation_type, Some(GraphqlOperationType::Subscription));
+
+    let raw = BodyFields::parse(&content_type("application/graphql"), "query Feed { feed { id } }").graphql.unwrap();
+    assert_eq!(raw.operation_name.as_deref(), Some("Feed"));
+
+    // Persisted queries carry only the name
+    let persisted = BodyFields::parse(&json, r#"{"operationName":"Feed","extensions":{}}"#).graphql.unwrap();
+    assert_eq!((persisted.operation_name.as_deref(), persisted.operation_type), (Some("Feed"), None));
+    assert!(BodyFields::parse(&json, r#"{"user":"ada"}"#).graphql.is_none());
+}
+
+const RULES: &str = r#"
+logging:
+  default: false
+  rules:
+    - name: "Capture"
+      match_conditions: {}
+      capture: { form: ["title", "tag"], multipart: true, graphql: true }
+drop:
+  default: false
+  rules:
+    - name: "Admin signups"
+      match_conditions:
+        form: { role: "^admin$" }
+      response: { status_code: 403 }
+    - name: "Executable uploads"
+      match_conditions:
+        multipart:
+          - { name: "^avatar$", content_type: "^image/" }
+          - { filename: "\\.exe$" }
+      response: { status_code: 415 }
+    - name: "Image uploads titled Holiday"
+      match_conditions:
+        form: { title: "Holiday" }
+        multipart:
+          - { name: "^avatar$", filename: "\\.png$" }
+      response: { status_code: 202 }
+    - name: "No mutations"
+      match_conditions:
+        graphql: { operation_types: [mutation] }
+      response: { status_code: 405 }
+    - name: "No introspection"
+      match_conditions:
+        graphql: { operation_name: "^IntrospectionQuery$" }
+      response: { status_code: 400 }
+"#;
+
+fn dropped(config: &Config, headers: &HeaderMap, body: &str) -> Option<u16> {
+    config.should_drop_request_parts("POST", "/https://api.example.com/submit", headers, body).map(|drop| drop.status_code)
+}
+
+#[test]
+fn test_form_multipart_and_graphql_conditions_match() {
+    let (config, issues) = Config::check_source(RULES);
+    assert!(issues.is_empty(), "{:?}", issues);
+    let config = config.unwrap();
+
+    let form = content_type("application/x-www-form-urlencoded");
+    assert_eq!(dropped(&config, &form, "user=ada&role=ops&role=admin"), Some(403));
+    assert_eq!(dropped(&config, &form, "user=ada&role=administrator"), None);
+
+    // Both part conditions must be met; the file content is never matched
+    let multipart = content_type(&multipart_type());
+    assert_eq!(dropped(&config, &multipart, &multipart_body()), Some(202));
+    assert_eq!(dropped(&config, &multipart, &multipart_body().replace("me.png", "setup.exe")), Some(415));
+
+    let json = content_type("application/json");
+    assert_eq!(dropped(&config, &json, &graphql_body("mutation { deleteAll }", None)), Some(405));
+    assert_eq!(dropped(&config, &json, &graphql_body("query IntrospectionQuery { __schema { types { name } } }", None)), Some(400));
+    assert_eq!(dropped(&config, &json, &graphql_body("query Feed { feed { id } }", None)), None);
+    assert_eq!(dropped(&config, &json, r#"{"role":"admin"}"#), None);
+}
+
+#[test]
+fn test_form_multipart_and_graphql_fields_are_logged() {
+    let (config, _) = Config::check_source(RULES);
+    let config = config.unwrap();
+    let request = |content_type: String, body: String| DryRunInput {
+        request: SyntheticRequest {
+            method: "POST".into(),
+            path: "/https://api.example.com/submit".into(),
+            headers: [("content-type".to_string(), content_type)].into(),
+            body,
+            ..Default::default()
+        },
+        response: None,
+    };
+
+    // A .jpg avatar is not dropped by "Image uploads titled Holiday"
+    let report = dry_run(&config, &request(multipart_type(), multipart_body().replace("me.png", "me.jpg"))).unwrap();
+    let log = report.request_log.unwrap();
+    assert_eq!(log["form"], json!({"title": "Holiday", "tag": ["a", "b"]}));
+    assert_eq!(log["multipart"][1], json!({"name": "avatar", "filename": "me.jpg", "content_type": "image/png"}));
+    assert!(log.get("graphql").is_none());
+
+    let report = dry_run(&config, &request("application/json".into(), graphql_body("query Feed { feed { id } }", None))).unwrap();
+    assert_eq!(report.request_log.unwrap()["graphql"], json!({"operation_name": "Feed", "operation_type": "query"}));
+}
+
+#[test]
+fn test_invalid_field_patterns_are_errors() {
+    let yaml = r#"
+logging: { default: false, rules: [] }
+drop:
+  default: false
+  rules:
+    - name: "Bad"
+      match_conditions:
+        form: { user: "(oops" }
+        multipart: [{ filename: "[" }]
+        graphql: { operation_name: "*" }
+      response: { status_code: 403 }
+"#;
+    let (_, issues) = Config::check_source(yaml);
+    let paths: Vec<&str> = issues.iter().map(|issue| issue.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            "drop.rules[0].match_conditions.form.user",
+            "drop.rules[0].match_conditions.multipart[0].filename",
+            "drop.rules[0].match_conditions.graphql.operation_name",
+        ]
+    );
+}
@@ -0,0 +1,144 @@
+use axum::body::{Body, Bytes};
+use axum::http::{HeaderMap, Request, StatusCode};
+use axum::routing::{get, post};
+use axum::Router;
+use logprox::config::{Config, ConfigHolder};
+use logprox::proxy_handler;
+use std::sync::Arc;
+use tower::util::ServiceExt;
+
+/// An upstream echoing request bodies (with the `content-length` it received) on `/echo/...`
+/// and serving a fixed JSON document on `/users/...`.
+async fn spawn_upstream() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let echo = |headers: HeaderMap, body: Bytes| async move {
+        let length = headers.get("content-length").map(|v| v.to_str().unwrap().to_string()).unwrap_or_default();
+        ([("x-received-length", length)], body)
+    };
+    let user = || async { r#"{"user":{"name":"ada","legacy_id":7},"items":[1,2],"note":"colour"}"# };
+    let app = Router::new().route("/echo/*path", post(echo)).route("/users/*path", get(user));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+fn app(yaml: &str) -> Router {
+    let (config, issues) = Config::check_source(yaml);
+    assert!(issues.is_empty(), "{:?}", issues);
+    Router::new().fallback(proxy_handler).with_state(Arc::new(ConfigHolder::new(config.unwrap())))
+}
+
+const RULES: &str = r#"
+logging: { default: false, rules: [] }
+drop: { default: false, rules: [] }
+upstream: { allow_private_networks: true }
+bodies:
+  rules:
+    - name: "Migrate v1 payloads"
+      match_conditions:
+        path: { patterns: ["/echo/(?P<tenant>[a-z]+)$"] }
+      request:
+        json_patch:
+          - { op: remove, path: "/legacy" }
+          - { op: replace, path: "/version", value: 2 }
+          - { op: add, path: "/tenant", value: { id: "{{path.tenant}}" } }
+          - { op: add, path: "/tags/0", value: "first" }
+        replace:
+          - { pattern: "colou?r", replacement: "color" }
+    - name: "Wrap text"
+      match_conditions:
+        path: { patterns: ["/echo/text$"] }
+      request:
+        template: "<msg method=\"{{method}}\">{{body}}</msg>"
+    - name: "Slim users"
+      match_conditions:
+        path: { patterns: ["/users/"] }
+      response:
+        json_patch:
+          - { op: remove, path: "/user/legacy_id" }
+          - { op: add, path: "/items/-", value: 3 }
+          - { op: replace, path: "/missing", value: 1 }
+        replace:
+          - { pattern: "colou?r", replacement: "color" }
+"#;
+
+async fn send(app: Router, req: Request<Body>) -> (StatusCode, HeaderMap, String) {
+    let resp = app.oneshot(req).await.unwrap();
+    let (parts, body) = resp.into_parts();
+    let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+    (parts.status, parts.headers, String::from_utf8(body.to_vec()).unwrap())
+}
+
+#[tokio::test]
+async fn test_request_body_is_patched_with_content_length_recomputed() {
+    let upstream = spawn_upstream().await;
+    let body = r#"{"version":1,"legacy":true,"tags":["a"],"favourite":"colour"}"#;
+    let req = Request::builder()
+        .method("POST")
+        .uri(format!("/{}/echo/acme", upstream))
+        .header("content-type", "application/json")
+        .header("content-length", body.len())
+        .body(Body::from(body))
+        .unwrap();
+    let (status, headers, received) = send(app(RULES), req).await;
+    assert_eq!(status, StatusCode::OK);
+    let received_json: serde_json::Value = serde_json::from_str(&received).unwrap();
+    assert_eq!(
+        received_json,
+        serde_json::json!({"version": 2, "tags": ["first", "a"], "favourite": "color", "tenant": {"id": "acme"}})
+    );
+    assert_eq!(headers["x-received-length"], received.len().to_string().as_str());
+}
+
+#[tokio::test]
+async fn test_request_body_is_replaced_from_template() {
+    let upstream = spawn_upstream().await;
+    let req = Request::builder()
+        .method("POST")
+        .uri(format!("/{}/echo/text", upstream))
+        .body(Body::from("hello"))
+        .unwrap();
+    let (_, headers, received) = send(app(RULES), req).await;
+    assert_eq!(received, r#"<msg method="POST">hello</msg>"#);
+    assert_eq!(headers["x-received-length"], "30");
+}
+
+#[tokio::test]
+async fn test_response_body_is_patched_and_unresolved_operations_skipped() {
+    let upstream = spawn_upstream().await;
+    let req = Request::builder().uri(format!("/{}/users/1", upstream)).body(Body::empty()).unwrap();
+    let (status, headers, body) = send(app(RULES), req).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, r#"{"items":[1,2,3],"note":"color","user":{"name":"ada"}}"#);
+    assert_eq!(headers["content-length"], body.len().to_string().as_str());
+}
+
+#[test]
+fn test_invalid_body_rules_are_reported() {
+    let yaml = r#"
+logging: { default: false, rules: [] }
+drop: { default: false, rules: [] }
+bodies:
+  rules:
+    - name: "Bad"
+      request:
+        json_patch:
+          - { op: add, path: "items" , value: 1 }
+          - { op: replace, path: "/a" }
+          - { op: remove, path: "" }
+        replace:
+          - { pattern: "(oops", replacement: "x" }
+"#;
+    let (_, issues) = Config::check_source(yaml);
+    let paths: Vec<&str> = issues.iter().map(|issue| issue.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        vec![
+            "bodies.rules[0].request.json_patch[0].path",
+            "bodies.rules[0].request.json_patch[1]",
+            "bodies.rules[0].request.json_patch[2].path",
+            "bodies.rules[0].request.replace[0].pattern",
+        ]
+    );
+    assert!(issues.iter().all(|issue| issue.is_error()));
+}
@@ -0,0 +1,255 @@
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{HeaderMap, HeaderValue, Request, StatusCode};
+use axum::routing::get;
+use axum::{Extension, Json, Router};
+use logprox::config::{Config, ConfigHolder, TlsConfig};
+use logprox::handlers::client::ClientInfo;
+use logprox::handlers::dry_run::{dry_run, DryRunInput, SyntheticRequest};
+use logprox::listener::{self, TlsInfo};
+use logprox::proxy_handler;
+use serde_json::json;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+use tower::util::ServiceExt;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/admin_tls");
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.append(*name, HeaderValue::from_str(value).unwrap());
+    }
+    headers
+}
+
+fn peer(addr: &str) -> Option<SocketAddr> {
+    Some(addr.parse().unwrap())
+}
+
+fn ip(addr: &str) -> Option<IpAddr> {
+    Some(addr.parse().unwrap())
+}
+
+fn trusted() -> Vec<ipnet::IpNet> {
+    vec!["10.0.0.0/8".parse().unwrap(), "::1/128".parse().unwrap()]
+}
+
+#[test]
+fn test_forwarded_for_is_only_trusted_from_trusted_proxies() {
+    let spoofed = headers(&[("x-forwarded-for", "198.51.100.7")]);
+    let client = ClientInfo::resolve(peer("203.0.113.5:5000"), None, &spoofed, &trusted());
+    assert_eq!((client.ip, client.port), (ip("203.0.113.5"), Some(5000)));
+
+    // The nearest untrusted hop is the client; anything further left could be forged
+    let chain = headers(&[("x-forwarded-for", "198.51.100.7, 203.0.113.9"), ("x-forwarded-for", "10.1.1.1")]);
+    let client = ClientInfo::resolve(peer("10.0.0.2:443"), None, &chain, &trusted());
+    assert_eq!((client.ip, client.port), (ip("203.0.113.9"), None));
+    assert_eq!(client.peer, peer("10.0.0.2:443"));
+
+    let client = ClientInfo::resolve(peer("10.0.0.2:443"), None, &HeaderMap::new(), &trusted());
+    assert_eq!(client.ip, ip("10.0.0.2"));
+}
+
+#[test]
+fn test_forwarded_header_takes_precedence() {
+    let both = headers(&[
+        ("forwarded", r#"for="[2001:db8::7]:4711";proto=https, for=10.2.2.2"#),
+        ("x-forwarded-for", "198.51.100.7"),
+    ]);
+    let client = ClientInfo::resolve(peer("[::1]:8080"), None, &both, &trusted());
+    assert_eq!((client.ip, client.port), (ip("2001:db8::7"), Some(4711)));
+
+    // An obfuscated hop ends the walk at the last known address
+    let unknown = headers(&[("forwarded", "for=unknown, for=10.2.2.2")]);
+    assert_eq!(ClientInfo::resolve(peer("10.0.0.2:80"), None, &unknown, &trusted()).ip, ip("10.2.2.2"));
+
+    // IPv4 peers on a dual-stack listener arrive IPv4-mapped
+    let mapped = ClientInfo::resolve(peer("[::ffff:10.0.0.2]:80"), None, &headers(&[("x-forwarded-for", "192.0.2.1")]), &trusted());
+    assert_eq!(mapped.ip, ip("192.0.2.1"));
+}
+
+#[test]
+fn test_forwarded_headers_extend_trusted_chains_and_replace_others() {
+    let incoming = headers(&[("x-forwarded-for", "198.51.100.7"), ("forwarded", "for=198.51.100.7"), ("host", "proxy.example.com")]);
+
+    let mut upstream = incoming.clone();
+    let client = ClientInfo::resolve(peer("203.0.113.5:5000"), None, &incoming, &trusted());
+    client.add_forwarded_headers(&mut upstream, &trusted());
+    assert_eq!(upstream["x-forwarded-for"], "203.0.113.5");
+    assert_eq!(upstream["x-forwarded-proto"], "http");
+    assert_eq!(upstream["x-forwarded-host"], "proxy.example.com");
+    assert_eq!(upstream["forwarded"], r#"for=203.0.113.5;host="proxy.example.com";proto=http"#);
+
+    let mut upstream = incoming.clone();
+    let tls = Some(TlsInfo { version: Some("TLSv1.3".into()), sni: None });
+    let client = ClientInfo::resolve(peer("[::1]:5000"), tls, &incoming, &trusted());
+    client.add_forwarded_headers(&mut upstream, &trusted());
+    assert_eq!(upstream["x-forwarded-for"], "198.51.100.7, ::1");
+    assert_eq!(upstream["x-forwarded-proto"], "https");
+    assert_eq!(upstream["forwarded"], r#"for=198.51.100.7, for="[::1]";host="proxy.example.com";proto=https"#);
+}
+
+const CONFIG: &str = r#"
+server:
+  trusted_proxies: ["127.0.0.1"]
+logging:
+  default: false
+  rules:
+    - name: "Clients"
+      match_conditions: {}
+      capture: { client_ip: true, client_port: true, tls_version: true, sni: true }
+drop:
+  default: false
+  rules:
+    - name: "Blocked network"
+      match_conditions:
+        client_ip: ["203.0.113.0/24", "2001:db8::1"]
+      response: { status_code: 403 }
+    - name: "Admin from outside the office"
+      match_conditions:
+        path: { patterns: ["/admin"] }
+        not: { client_ip: ["192.168.0.0/16"] }
+      response: { status_code: 404 }
+upstream: { allow_private_networks: true }
+"#;
+
+fn dropped(config: &Config, path: &str, client_ip: Option<IpAddr>) -> Option<u16> {
+    let input = DryRunInput { request: SyntheticRequest { path: path.into(), client_ip, ..Default::default() }, response: None };
+    dry_run(config, &input).unwrap().dropped.map(|drop| drop.status_code)
+}
+
+#[test]
+fn test_client_ip_conditions_match_cidr_ranges() {
+    let (config, issues) = Config::check_source(CONFIG);
+    assert!(issues.is_empty(), "{:?}", issues);
+    let config = config.unwrap();
+    assert_eq!(dropped(&config, "/https://api.example.com/", ip("203.0.113.77")), Some(403));
+    assert_eq!(dropped(&config, "/https://api.example.com/", ip("2001:db8::1")), Some(403));
+    assert_eq!(dropped(&config, "/https://api.example.com/", ip("198.51.100.1")), None);
+    // Unknown clients never fall in a range
+    assert_eq!(dropped(&config, "/https://api.example.com/", None), None);
+    assert_eq!(dropped(&config, "/https://api.example.com/admin", ip("198.51.100.1")), Some(404));
+    assert_eq!(dropped(&config, "/https://api.example.com/admin", ip("192.168.4.20")), None);
+
+    let input = DryRunInput {
+        request: SyntheticRequest { path: "/https://api.example.com/".into(), client_ip: ip("198.51.100.1"), ..Default::default() },
+        response: None,
+    };
+    let log = dry_run(&config, &input).unwrap().request_log.unwrap();
+    assert_eq!(log["client_ip"], "198.51.100.1");
+    assert!(log.get("tls_version").is_none());
+}
+
+/// An upstream echoing the forwarding headers it receives.
+async fn spawn_upstream() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let echo = |headers: HeaderMap| async move {
+        let value = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).map(str::to_string);
+        Json(json!({"x-forwarded-for": value("x-forwarded-for"), "forwarded": value("forwarded")}))
+    };
+    let app = Router::new().route("/*path", get(echo));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+fn request(uri: &str, from: &str, forwarded_for: Option<&str>) -> Request<Body> {
+    let mut builder = Request::builder().uri(uri);
+    if let Some(forwarded_for) = forwarded_for {
+        builder = builder.header("x-forwarded-for", forwarded_for);
+    }
+    let mut req = builder.body(Body::empty()).unwrap();
+    req.extensions_mut().insert(ConnectInfo(from.parse::<SocketAddr>().unwrap()));
+    req
+}
+
+#[tokio::test]
+async fn test_proxy_resolves_clients_behind_trusted_proxies() {
+    let upstream = spawn_upstream().await;
+    let (config, _) = Config::check_source(CONFIG);
+    let app = Router::new().fallback(proxy_handler).with_state(Arc::new(ConfigHolder::new(config.unwrap())));
+    let uri = format!("/{}/echo", upstream);
+
+    let blocked = request(&uri, "127.0.0.1:40000", Some("203.0.113.8"));
+    assert_eq!(app.clone().oneshot(blocked).await.unwrap().status(), StatusCode::FORBIDDEN);
+
+    // The header means nothing from an untrusted peer
+    let spoofed = request(&uri, "127.0.0.2:40000", Some("203.0.113.8"));
+    let resp = app.clone().oneshot(spoofed).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    let received: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(received["x-forwarded-for"], "127.0.0.2");
+
+    let forwarded = request(&uri, "127.0.0.1:40000", Some("198.51.100.1"));
+    let resp = app.oneshot(forwarded).await.unwrap();
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    let received: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(received["x-forwarded-for"], "198.51.100.1, 127.0.0.1");
+    assert_eq!(received["forwarded"], "for=127.0.0.1;proto=http");
+}
+
+#[test]
+fn test_invalid_ranges_are_errors() {
+    let yaml = CONFIG
+        .replace(r#"["127.0.0.1"]"#, r#"["127.0.0.1/33"]"#)
+        .replace(r#""2001:db8::1""#, r#""example.com""#);
+    let faults = r#"faults:
+  rules:
+    - { name: "Too often", match_conditions: { methods: [GET] }, percentage: 250, error: { status_code: 503 } }
+    - { name: "Less than never", match_conditions: { methods: [POST] }, percentage: -5, error: { status_code: 503 } }
+"#;
+    let (_, issues) = Config::check_source(&(yaml + faults));
+    let found: Vec<(&str, bool)> = issues.iter().map(|issue| (issue.path.as_str(), issue.is_error())).collect();
+    assert_eq!(
+        found,
+        vec![
+            ("server.trusted_proxies[0]", true),
+            ("drop.rules[0].match_conditions.client_ip[1]", true),
+            ("faults.rules[0].percentage", true),
+            ("faults.rules[1].percentage", true),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_tls_listener_attaches_connection_info() {
+    let tls = TlsConfig {
+        cert_file: format!("{}/server.pem", FIXTURES),
+        key_file: format!("{}/server.key", FIXTURES),
+        client_ca_file: None,
+    };
+    let acceptor = listener::tls_acceptor(&tls).unwrap();
+    let tcp = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = tcp.local_addr().unwrap();
+    let describe = |ConnectInfo(peer): ConnectInfo<SocketAddr>, Extension(tls): Extension<TlsInfo>| async move {
+        format!("{} {} {}", peer.ip(), tls.version.unwrap_or_default(), tls.sni.unwrap_or_default())
+    };
+    let router = Router::new().route("/", get(describe));
+    let server = tokio::spawn(listener::serve(tcp, router, Some(acceptor), std::future::pending()));
+
+    let mut roots = RootCertStore::empty();
+    let ca = std::fs::read(format!("{}/ca.pem", FIXTURES)).unwrap();
+    for cert in rustls_pemfile::certs(&mut ca.as_slice()) {
+        roots.add(cert.unwrap()).unwrap();
+    }
+    let provider = Arc::new(tokio_rustls::rustls::crypto::ring::default_provider());
+    let config = ClientConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let connector = tokio_rustls::TlsConnector::from(Arc::new(config));
+    let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+    let mut stream = connector.connect(ServerName::try_from("localhost").unwrap(), stream).await.unwrap();
+    stream.write_all(b"GET / HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n").await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.ends_with("127.0.0.1 TLSv1.3 localhost"), "{}", response);
+
+    server.abort();
+}
@@ -0,0 +1,183 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use logprox::concurrency::{ConcurrencyLimiter, ShedReason};
+use logprox::config::{Config, ConcurrencyConfig, ConfigHolder};
+use logprox::metrics::Metrics;
+use logprox::{get_metrics, proxy_handler};
+use std::sync::Arc;
+use std::time::Duration;
+use tower::util::ServiceExt;
+
+#[tokio::test]
+async fn test_global_limit_sheds_when_queue_full() {
+    let limiter = ConcurrencyLimiter::new(ConcurrencyConfig { max_in_flight: Some(1), ..Default::default() });
+    let metrics = Arc::new(Metrics::default());
+
+    let held = limiter.acquire(None, &metrics).await.unwrap();
+    assert_eq!(metrics.snapshot().in_flight, 1);
+    assert_eq!(limiter.acquire(None, &metrics).await.unwrap_err(), ShedReason::QueueFull);
+
+    drop(held);
+    assert_eq!(metrics.snapshot().in_flight, 0);
+    assert!(limiter.acquire(None, &metrics).await.is_ok());
+
+    let snapshot = metrics.snapshot();
+    assert_eq!(snapshot.shed_queue_full, 1);
+    assert_eq!(snapshot.shed_total, 1);
+}
+
+#[tokio::test]
+async fn test_queued_request_waits_for_slot() {
+    let limiter = ConcurrencyLimiter::new(ConcurrencyConfig {
+        max_in_flight: Some(1),
+        max_queue: 1,
+        max_wait: Some("1s".to_string()),
+        ..Default::default()
+    });
+    let metrics = Arc::new(Metrics::default());
+
+    let held = limiter.acquire(None, &metrics).await.unwrap();
+    let release = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(held);
+    });
+    assert!(limiter.acquire(None, &metrics).await.is_ok());
+    release.await.unwrap();
+    assert_eq!(metrics.snapshot().queue_depth, 0);
+}
+
+#[tokio::test]
+async fn test_queued_request_times_out() {
+    let limiter = ConcurrencyLimiter::new(ConcurrencyConfig {
+        max_in_flight: Some(1),
+        max_queue: 1,
+        max_wait: Some("50ms".to_string()),
+        ..Default::default()
+    });
+    let metrics = Arc::new(Metrics::default());
+
+    let _held = limiter.acquire(None, &metrics).await.unwrap();
+    assert_eq!(limiter.acquire(None, &metrics).await.unwrap_err(), ShedReason::WaitTimeout);
+    assert_eq!(metrics.snapshot().shed_wait_timeout, 1);
+}
+
+#[tokio::test]
+async fn test_per_host_limits() {
+    let limiter = ConcurrencyLimiter::new(ConcurrencyConfig {
+        per_upstream_host: Some(1),
+        upstream_hosts: [("big.example.com".to_string(), 2)].into(),
+        ..Default::default()
+    });
+    let metrics = Arc::new(Metrics::default());
+
+    let _a = limiter.acquire(Some("a.example.com"), &metrics).await.unwrap();
+    assert!(limiter.acquire(Some("a.example.com"), &metrics).await.is_err());
+    assert!(limiter.acquire(Some("b.example.com"), &metrics).await.is_ok());
+
+    let _big1 = limiter.acquire(Some("big.example.com"), &metrics).await.unwrap();
+    let _big2 = limiter.acquire(Some("big.example.com"), &metrics).await.unwrap();
+    assert!(limiter.acquire(Some("big.example.com"), &metrics).await.is_err());
+}
+
+#[tokio::test]
+async fn test_proxy_sheds_with_503_and_exposes_metrics() {
+    let config: Config = serde_norway::from_str(
+        r#"
+logging: { default: false, rules: [] }
+drop: { default: false, rules: [] }
+mock:
+  rules:
+    - name: "Slow stub"
+      match_conditions: {}
+      response:
+        delay: 200ms
+concurrency:
+  max_in_flight: 1
+"#,
+    )
+    .unwrap();
+    let app = Router::new()
+        .route("/metrics", axum::routing::get(get_metrics))
+        .fallback(proxy_handler)
+        .with_state(Arc::new(ConfigHolder::new(config)));
+
+    let request = || Request::builder().uri("/slow").body(Body::empty()).unwrap();
+    let first = tokio::spawn(app.clone().oneshot(request()));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+
+    let shed = app.clone().oneshot(request()).await.unwrap();
+    assert_eq!(shed.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(shed.headers().get("retry-after").unwrap(), "1");
+    assert_eq!(first.await.unwrap().unwrap().status(), StatusCode::OK);
+
+    let resp = app
+        .oneshot(Request::builder().uri("/metrics").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["shed_total"], 1);
+    assert_eq!(json["in_flight"], 0);
+}
+
+#[tokio::test]
+async fn test_host_cap_applies_to_the_rewritten_host() {
+    let (config, issues) = Config::check_source(
+        r#"
+logging: { default: false, rules: [] }
+drop: { default: false, rules: [] }
+mock:
+  rules:
+    - name: "Slow stub"
+      match_conditions: {}
+      response:
+        delay: 200ms
+rewrites:
+  rules:
+    - name: "Mirror"
+      match_conditions: { path: { patterns: ["^/https://mirror\\.example\\.com/"] } }
+      host: "slow-api.example.com"
+concurrency:
+  upstream_hosts:
+    "slow-api.example.com": 1
+"#,
+    );
+    assert!(issues.is_empty(), "{:?}", issues);
+    let app = Router::new().fallback(proxy_handler).with_state(Arc::new(ConfigHolder::new(config.unwrap())));
+    let request = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+    let first = tokio::spawn(app.clone().oneshot(request("/https://slow-api.example.com/a")));
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    // Rewritten to slow-api.example.com, so it waits on that host's slot
+    let shed = app.clone().oneshot(request("/https://mirror.example.com/a")).await.unwrap();
+    assert_eq!(shed.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let other = app.oneshot(request("/https://other.example.com/a")).await.unwrap();
+    assert_eq!(other.status(), StatusCode::OK);
+    assert_eq!(first.await.unwrap().unwrap().status(), StatusCode::OK);
+}
+
+#[test]
+fn test_zero_caps_are_rejected() {
+    let (_, issues) = Config::check_source(
+        r#"
+logging: { default: false, rules: [] }
+drop: { default: false, rules: [] }
+concurrency:
+  max_in_flight: 0
+  per_upstream_host: 0
+  upstream_hosts:
+    "fine.example.com": 5
+    "closed.example.com": 0
+"#,
+    );
+    let found: Vec<(&str, bool)> = issues.iter().map(|issue| (issue.path.as_str(), issue.is_error())).collect();
+    assert_eq!(
+        found,
+        vec![
+            ("concurrency.max_in_flight", true),
+            ("concurrency.per_upstream_host", true),
+            ("concurrency.upstream_hosts.closed.example.com", true),
+        ]
+    );
+}
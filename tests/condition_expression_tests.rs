@@ -0,0 +1,146 @@
+use axum::http::{HeaderMap, HeaderValue};
+use logprox::config::Config;
+
+const CONFIG: &str = r#"
+logging: { default: false, rules: [] }
+drop:
+  default: false
+  rules:
+    - name: "Writes without a token"
+      match_conditions:
+        path: { patterns: ["^/https://api\\.example\\.com/"] }
+        not:
+          any:
+            - methods: [GET, HEAD]
+            - headers: { authorization: "" }
+      response: { status_code: 401 }
+    - name: "Debug or admin traffic"
+      match_conditions:
+        any:
+          - headers: { x-debug: "^1$" }
+          - all:
+              - path: { patterns: ["/admin/"] }
+              - not: { methods: [GET] }
+      response: { status_code: 403 }
+response_logging:
+  default: false
+  rules:
+    - name: "Unexpected responses"
+      match_conditions:
+        not: { status_codes: [200, 204, 304] }
+        any:
+          - headers: { content-type: "json" }
+          - body: { patterns: ["error"] }
+      capture: { status_code: true }
+"#;
+
+fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.insert(*name, HeaderValue::from_str(value).unwrap());
+    }
+    headers
+}
+
+fn config() -> Config {
+    let (config, issues) = Config::check_source(CONFIG);
+    assert!(issues.is_empty(), "{:?}", issues);
+    config.unwrap()
+}
+
+fn dropped(config: &Config, method: &str, path: &str, headers: &HeaderMap) -> Option<u16> {
+    config.should_drop_request_parts(method, path, headers, "").map(|drop| drop.status_code)
+}
+
+#[test]
+fn test_not_negates_methods_and_detects_absent_headers() {
+    let config = config();
+    let api = "/https://api.example.com/orders";
+    let token = headers(&[("authorization", "Bearer abc")]);
+    assert_eq!(dropped(&config, "POST", api, &HeaderMap::new()), Some(401));
+    assert_eq!(dropped(&config, "POST", api, &token), None);
+    assert_eq!(dropped(&config, "GET", api, &HeaderMap::new()), None);
+    assert_eq!(dropped(&config, "POST", "/https://other.example.com/orders", &HeaderMap::new()), None);
+}
+
+#[test]
+fn test_any_combines_condition_types_with_or() {
+    let config = config();
+    let token = headers(&[("authorization", "Bearer abc")]);
+    let debug = headers(&[("authorization", "Bearer abc"), ("x-debug", "1")]);
+    assert_eq!(dropped(&config, "GET", "/https://svc.example.com/users", &debug), Some(403));
+    assert_eq!(dropped(&config, "DELETE", "/https://svc.example.com/admin/users", &token), Some(403));
+    assert_eq!(dropped(&config, "GET", "/https://svc.example.com/admin/users", &token), None);
+    assert_eq!(dropped(&config, "DELETE", "/https://svc.example.com/users", &token), None);
+}
+
+#[test]
+fn test_response_rules_nest_conditions() {
+    let config = config();
+    let json = headers(&[("content-type", "application/json")]);
+    assert!(config.should_log_response(500, &json, "").is_some());
+    assert!(config.should_log_response(502, &HeaderMap::new(), "upstream error").is_some());
+    assert!(config.should_log_response(200, &json, "error").is_none());
+    assert!(config.should_log_response(404, &HeaderMap::new(), "not here").is_none());
+}
+
+#[test]
+fn test_nested_conditions_are_validated() {
+    let yaml = r#"
+logging: { default: false, rules: [] }
+drop:
+  default: false
+  rules:
+    - name: "Bad"
+      match_conditions:
+        any:
+          - methods: [GET]
+          - not: { headers: { x-api-key: "(" } }
+      response: { status_code: 403 }
+    - name: "Never"
+      match_conditions:
+        not: { path: { patterns: [".*"] } }
+      response: { status_code: 403 }
+response_logging:
+  default: false
+  rules:
+    - name: "Bad response"
+      match_conditions:
+        all: [{ status_codes: [1000] }]
+      capture: {}
+"#;
+    let (_, issues) = Config::check_source(yaml);
+    let found: Vec<(&str, bool)> = issues.iter().map(|issue| (issue.path.as_str(), issue.is_error())).collect();
+    assert_eq!(
+        found,
+        vec![
+            ("drop.rules[0].match_conditions.any[1].not.headers.x-api-key", true),
+            ("drop.rules[1].match_conditions.not", false),
+            ("response_logging.rules[0].match_conditions.all[0].status_codes[0]", true),
+        ]
+    );
+}
+
+#[test]
+fn test_catch_all_detection_sees_nested_conditions() {
+    let yaml = r#"
+logging:
+  default: false
+  rules:
+    - name: "Everything"
+      match_conditions:
+        any: [{ methods: [POST] }, {}]
+      capture: {}
+    - name: "Later"
+      match_conditions: { methods: [GET] }
+      capture: {}
+drop: { default: false, rules: [] }
+"#;
+    let (_, issues) = Config::check_source(yaml);
+    let paths: Vec<&str> = issues.iter().map(|issue| issue.path.as_str()).collect();
+    assert_eq!(paths, vec!["logging.rules[1]"]);
+
+    let narrowed = yaml.replace("any: [{ methods: [POST] }, {}]", "not: { methods: [POST] }");
+    let (_, issues) = Config::check_source(&narrowed);
+    assert!(issues.is_empty(), "{:?}", issues);
+}
@@ -0,0 +1,137 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use logprox::admin_router;
+use logprox::config::{Config, ConfigHolder};
+use logprox::history::{diff_summary, RollbackError};
+use serde_json::Value;
+use std::sync::Arc;
+use tower::util::ServiceExt;
+
+fn config_yaml(body: &str, history: usize) -> String {
+    format!(
+        r#"
+logging: {{ default: false, rules: [] }}
+drop: {{ default: false, rules: [] }}
+reload: {{ history: {} }}
+mock:
+  rules:
+    - name: "Stub"
+      match_conditions: {{}}
+      response:
+        body: "{}"
+"#,
+        history, body
+    )
+}
+
+fn parse(yaml: &str) -> Config {
+    Config::check_source(yaml).0.unwrap()
+}
+
+fn mock_body(holder: &ConfigHolder) -> Option<String> {
+    holder.get().mock.rules[0].response.body.clone()
+}
+
+async fn send(app: &axum::Router, method: &str, uri: &str, body: &str) -> (StatusCode, Value) {
+    let req = Request::builder().method(method).uri(uri).body(Body::from(body.to_string())).unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    let status = resp.status();
+    let bytes = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    (status, serde_json::from_slice(&bytes).unwrap())
+}
+
+#[test]
+fn test_history_keeps_configured_number_of_versions() {
+    let holder = ConfigHolder::new(parse(&config_yaml("v1", 3)));
+    assert_eq!(holder.version(), 1);
+    for body in ["v2", "v3", "v4"] {
+        holder.install(parse(&config_yaml(body, 3)), "test");
+    }
+
+    let versions: Vec<u64> = holder.history().iter().map(|v| v.version).collect();
+    assert_eq!(versions, vec![2, 3, 4]);
+    assert_eq!(holder.version(), 4);
+    assert_eq!(holder.history()[0].trigger, "test");
+}
+
+#[test]
+fn test_rollback_reinstalls_previous_config_as_new_version() {
+    let holder = ConfigHolder::new(parse(&config_yaml("v1", 10)));
+    holder.install(parse(&config_yaml("v2", 10)), "test");
+
+    assert_eq!(holder.rollback(1).unwrap(), 3);
+    assert_eq!(mock_body(&holder).as_deref(), Some("v1"));
+    assert_eq!(holder.history().last().unwrap().trigger, "rollback:1");
+
+    assert!(matches!(holder.rollback(42), Err(RollbackError::UnknownVersion(42))));
+    assert_eq!(holder.version(), 3);
+}
+
+#[tokio::test]
+async fn test_put_config_validates_and_installs() {
+    let holder = Arc::new(ConfigHolder::new(parse(&config_yaml("v1", 10))));
+    let app = admin_router(holder.clone());
+
+    let (status, body) = send(&app, "PUT", "/config?validate_only=true", &config_yaml("v2", 10)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["valid"], true);
+    assert_eq!(mock_body(&holder).as_deref(), Some("v1"));
+
+    let invalid = config_yaml("v2", 10).replace("match_conditions: {}", "match_conditions: { path: { patterns: ['(oops'] } }");
+    let (status, body) = send(&app, "PUT", "/config", &invalid).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body["valid"], false);
+    assert_eq!(body["issues"][0]["path"], "mock.rules[0].match_conditions.path.patterns[0]");
+    assert_eq!(holder.version(), 1);
+
+    let (status, body) = send(&app, "PUT", "/config", &config_yaml("v2", 10)).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["version"], 2);
+    assert_eq!(mock_body(&holder).as_deref(), Some("v2"));
+
+    // JSON bodies are accepted too: the config as served by GET /config.
+    let json = serde_json::to_string_pretty(&parse(&config_yaml("v3", 10))).unwrap();
+    let (status, _) = send(&app, "PUT", "/config", &json).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(mock_body(&holder).as_deref(), Some("v3"));
+
+    let (status, body) = send(&app, "GET", "/config/history", "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["current"], 3);
+    assert_eq!(body["versions"].as_array().unwrap().len(), 3);
+
+    let (status, body) = send(&app, "POST", "/config/rollback/1", "").await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["version"], 4);
+    assert_eq!(mock_body(&holder).as_deref(), Some("v1"));
+
+    let (status, _) = send(&app, "POST", "/config/rollback/99", "").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn test_diff_summary_names_changed_rules_and_settings() {
+    let old = serde_json::json!({
+        "drop": { "default": false, "rules": [{ "name": "a", "x": 1 }, { "name": "b" }] },
+        "server": { "port": 3000 },
+    });
+    let new = serde_json::json!({
+        "drop": { "default": true, "rules": [{ "name": "a", "x": 2 }, { "name": "c" }] },
+        "server": { "port": 3000 },
+    });
+    assert_eq!(
+        diff_summary(&old, &new),
+        vec!["drop.default: false -> true", "drop.rules: added 'c'; removed 'b'; changed 'a'"]
+    );
+}
+
+#[test]
+fn test_rollback_round_trips_example_config() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/config.yaml");
+    let holder = ConfigHolder::new(Config::from_file(path).unwrap());
+    holder.install(parse(&config_yaml("v2", 10)), "test");
+
+    holder.rollback(1).unwrap();
+    let history = holder.history();
+    assert_eq!(history[0].content, history[2].content);
+}
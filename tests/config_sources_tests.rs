@@ -0,0 +1,115 @@
+use logprox::config::{Config, ConfigSources};
+use std::path::Path;
+
+fn write(dir: &Path, name: &str, contents: &str) -> String {
+    let path = dir.join(name);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(&path, contents).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+fn load(path: &str, env: Option<&str>) -> Config {
+    let (config, issues) = Config::check_sources(&ConfigSources::load_for_env(path, env));
+    assert!(issues.is_empty(), "{:?}", issues);
+    config.unwrap()
+}
+
+fn rule_names(config: &Config) -> Vec<&str> {
+    config.drop.rules.iter().map(|rule| rule.name.as_str()).collect()
+}
+
+const BASE: &str = r#"
+logging: { default: false, rules: [] }
+drop:
+  default: false
+  rules:
+    - name: "Admin"
+      match_conditions: { path: { patterns: ["^/admin"] } }
+      response: { status_code: 403 }
+    - name: "Debug"
+      match_conditions: { path: { patterns: ["^/debug"] } }
+      response: { status_code: 404 }
+include: "rules/*.yaml"
+"#;
+
+#[test]
+fn test_includes_merge_rules_by_name() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = write(dir.path(), "config.yaml", BASE);
+    write(dir.path(), "rules/a.yaml", r#"
+drop:
+  rules:
+    - name: "Admin"
+      match_conditions: { path: { patterns: ["^/admin"] } }
+      response: { status_code: 401 }
+    - name: "Legacy"
+      match_conditions: { path: { patterns: ["^/v0"] } }
+      response: { status_code: 410 }
+"#);
+    write(dir.path(), "rules/b.yaml", "drop:\n  default: true\n  rules:\n    - { name: \"Debug\", remove: true }\n");
+
+    let config = load(&base, None);
+    assert_eq!(rule_names(&config), vec!["Admin", "Legacy"]);
+    assert_eq!(config.drop.rules[0].response.status_code, 401);
+    assert!(config.drop.default);
+    assert!(config.drop.rules[0].source.as_deref().unwrap().ends_with("a.yaml"));
+    assert_eq!(config.source_files.len(), 3);
+
+    // GET /config shows where each rule came from.
+    let json = serde_json::to_value(&config).unwrap();
+    assert!(json["drop"]["rules"][1]["source"].as_str().unwrap().ends_with("a.yaml"));
+}
+
+#[test]
+fn test_environment_overlay_applies_last() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = write(dir.path(), "config.yaml", &BASE.replace("include: \"rules/*.yaml\"", ""));
+    write(dir.path(), "config.prod.yaml", "drop:\n  rules:\n    - { name: \"Debug\", remove: true }\nserver: { port: 8080 }\n");
+
+    assert_eq!(rule_names(&load(&base, None)), vec!["Admin", "Debug"]);
+    let prod = load(&base, Some("prod"));
+    assert_eq!(rule_names(&prod), vec!["Admin"]);
+    assert_eq!(prod.server.port, 8080);
+    assert_eq!(rule_names(&load(&base, Some("staging"))), vec!["Admin", "Debug"]);
+}
+
+#[test]
+fn test_config_directory_loads_files_in_name_order() {
+    let dir = tempfile::tempdir().unwrap();
+    write(dir.path(), "00-base.yaml", &BASE.replace("include: \"rules/*.yaml\"", ""));
+    write(dir.path(), "10-extra.yml", "drop:\n  rules:\n    - { name: \"Extra\", match_conditions: {}, response: { status_code: 418 } }\n");
+    write(dir.path(), "99-overlay.prod.yaml", "drop:\n  rules:\n    - { name: \"Extra\", remove: true }\n");
+    write(dir.path(), "notes.txt", "not yaml");
+
+    let path = dir.path().to_str().unwrap();
+    assert_eq!(rule_names(&load(path, None)), vec!["Admin", "Debug", "Extra"]);
+    assert_eq!(rule_names(&load(path, Some("prod"))), vec!["Admin", "Debug"]);
+}
+
+#[test]
+fn test_issues_are_located_in_the_file_they_come_from() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = write(dir.path(), "config.yaml", BASE);
+    write(dir.path(), "rules/bad.yaml", "\ndrop:\n  rules:\n    - name: \"Broken\"\n      match_conditions: { path: { patterns: [\"(oops\"] } }\n      response: { status_code: 403 }\n");
+
+    let (config, issues) = Config::check_sources(&ConfigSources::load_for_env(&base, None));
+    assert!(config.is_some());
+    assert_eq!(issues.len(), 1, "{:?}", issues);
+    assert_eq!(issues[0].path, "drop.rules[2].match_conditions.path.patterns[0]");
+    assert!(issues[0].file.as_deref().unwrap().ends_with("bad.yaml"));
+    assert_eq!(issues[0].line, Some(5));
+}
+
+#[test]
+fn test_include_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let base = write(dir.path(), "config.yaml", &BASE.replace("\"rules/*.yaml\"", "[\"missing.yaml\", \"config.yaml\"]"));
+
+    let (config, issues) = Config::check_sources(&ConfigSources::load_for_env(&base, None));
+    assert!(config.is_none());
+    let messages: Vec<String> = issues.iter().map(|issue| issue.to_string()).collect();
+    assert_eq!(issues.len(), 2, "{:?}", messages);
+    assert!(messages[0].contains("include[0]") && messages[0].contains("not found"), "{}", messages[0]);
+    assert!(messages[1].contains("include[1]") && messages[1].contains("include cycle"), "{}", messages[1]);
+    assert_eq!(issues[0].line, Some(12));
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::bool_assert_comparison)]
+
 use axum::http::{Method, Uri};
 use logprox::config::*;
 
@@ -169,6 +171,7 @@ fn test_matches_rule_method() {
         methods: vec!["POST".to_string()],
         headers: std::collections::HashMap::new(),
         body: BodyMatch { patterns: vec![] },
+        ..Default::default()
     };
     assert!(config.matches_rule(&post_req, &conditions, ""));
 
@@ -190,6 +193,7 @@ fn test_matches_rule_path() {
         methods: vec![],
         headers: std::collections::HashMap::new(),
         body: BodyMatch { patterns: vec![] },
+        ..Default::default()
     };
     assert!(config.matches_rule(&req, &conditions, ""));
 
@@ -215,6 +219,7 @@ fn test_matches_rule_headers() {
         methods: vec![],
         headers,
         body: BodyMatch { patterns: vec![] },
+        ..Default::default()
     };
     assert!(config.matches_rule(&req, &conditions, ""));
 
@@ -246,6 +251,7 @@ fn test_matches_rule_combined_conditions() {
         methods: vec!["POST".to_string()],
         headers,
         body: BodyMatch { patterns: vec![] },
+        ..Default::default()
     };
     assert!(config.matches_rule(&req, &conditions, ""));
 
@@ -271,6 +277,7 @@ fn test_matches_rule_body() {
         body: BodyMatch {
             patterns: vec![r#""amount":\s*\d+"#.to_string()],
         },
+        ..Default::default()
     };
     let body_with_amount = r#"{"amount": 123, "user": "test"}"#;
     assert!(config.matches_rule(&req, &conditions, body_with_amount));
@@ -287,6 +294,7 @@ fn test_matches_rule_body() {
         body: BodyMatch {
             patterns: vec![r#"admin"#.to_string(), r#"secret"#.to_string()],
         },
+        ..Default::default()
     };
     assert!(config.matches_rule(&req, &conditions_multi, "user admin access"));
     assert!(config.matches_rule(&req, &conditions_multi, "contains secret data"));
@@ -296,7 +304,7 @@ fn test_matches_rule_body() {
 #[test]
 fn test_should_drop_request_default() {
     let config = Config {
-        server: ServerConfig { port: 3000 },
+        server: ServerConfig { port: 3000, ..Default::default() },
         logging: LoggingConfig {
             default: false,
             rules: vec![],
@@ -309,7 +317,7 @@ fn test_should_drop_request_default() {
             default: false,
             rules: vec![],
         },
-        upstream: Default::default(),
+        ..Default::default()
     };
 
     let req = create_test_request(Method::GET, "/any", vec![]);
@@ -327,7 +335,7 @@ fn test_config_from_file_invalid() {
 #[test]
 fn test_config_holder() {
     let initial_config = Config {
-        server: ServerConfig { port: 3000 },
+        server: ServerConfig { port: 3000, ..Default::default() },
         logging: LoggingConfig {
             default: false,
             rules: vec![],
@@ -340,7 +348,7 @@ fn test_config_holder() {
             default: false,
             rules: vec![],
         },
-        upstream: Default::default(),
+        ..Default::default()
     };
     let holder = ConfigHolder::new(initial_config);
 
@@ -388,7 +396,7 @@ response_logging:
     .unwrap();
 
     let config = Config {
-        server: ServerConfig { port: 3000 },
+        server: ServerConfig { port: 3000, ..Default::default() },
         logging: LoggingConfig {
             default: false,
             rules: vec![],
@@ -401,7 +409,7 @@ response_logging:
             default: false,
             rules: vec![],
         },
-        upstream: Default::default(),
+        ..Default::default()
     };
 
     let holder = ConfigHolder::new(config);
@@ -534,8 +542,8 @@ response_logging:
     )
     .unwrap();
 
-    let config = Config::from_file(config_path.to_str().unwrap()).unwrap();
-    assert!(config.logging.rules[0].parse_timeout().is_none());
+    let err = Config::from_file(config_path.to_str().unwrap()).unwrap_err();
+    assert!(err.to_string().contains("Invalid duration '30'"), "{}", err);
 }
 
 #[test]
@@ -546,7 +554,7 @@ fn test_should_log_response() {
     assert!(config.should_log_response(200, &headers, "").is_none());
 
     let config_with_default = Config {
-        server: ServerConfig { port: 3000 },
+        server: ServerConfig { port: 3000, ..Default::default() },
         logging: LoggingConfig {
             default: false,
             rules: vec![],
@@ -559,7 +567,7 @@ fn test_should_log_response() {
             default: true,
             rules: vec![],
         },
-        upstream: Default::default(),
+        ..Default::default()
     };
     assert!(config_with_default
         .should_log_response(200, &headers, "")
@@ -575,6 +583,7 @@ fn test_matches_response_rule_status_code() {
         status_codes: vec![200, 201],
         headers: std::collections::HashMap::new(),
         body: BodyMatch { patterns: vec![] },
+        ..Default::default()
     };
 
     assert!(config.matches_response_rule(200, &headers, "", &conditions));
@@ -595,6 +604,7 @@ fn test_matches_response_rule_headers() {
         status_codes: vec![],
         headers: conditions_headers,
         body: BodyMatch { patterns: vec![] },
+        ..Default::default()
     };
 
     assert!(config.matches_response_rule(200, &headers, "", &conditions));
@@ -615,8 +625,67 @@ fn test_matches_response_rule_body() {
         body: BodyMatch {
             patterns: vec![r#"success"#.to_string()],
         },
+        ..Default::default()
     };
 
     assert!(config.matches_response_rule(200, &headers, "operation successful", &conditions));
     assert!(!config.matches_response_rule(200, &headers, "error occurred", &conditions));
 }
+
+#[test]
+fn test_compiled_rules_first_match_wins() {
+    let config: Config = serde_norway::from_str(
+        r#"
+logging: { default: false, rules: [] }
+drop:
+  default: false
+  rules:
+    - name: "Post only"
+      match_conditions: { path: { patterns: ["^/api/"] }, methods: ["POST"] }
+      response: { status_code: 405 }
+    - name: "Admin"
+      match_conditions: { path: { patterns: ["^/api/admin", "^/internal"] } }
+      response: { status_code: 403 }
+    - name: "Any API"
+      match_conditions: { path: { patterns: ["^/api/"] } }
+      response: { status_code: 404 }
+"#,
+    )
+    .unwrap();
+    let headers = axum::http::HeaderMap::new();
+    let status = |method: &str, path: &str| {
+        config.should_drop_request_parts(method, path, &headers, "").map(|r| r.status_code)
+    };
+
+    assert_eq!(status("POST", "/api/admin"), Some(405));
+    assert_eq!(status("GET", "/api/admin"), Some(403));
+    assert_eq!(status("GET", "/internal/x"), Some(403));
+    assert_eq!(status("GET", "/api/users"), Some(404));
+    assert_eq!(status("GET", "/health"), None);
+}
+
+#[test]
+fn test_invalid_patterns_rejected_at_load_and_never_match_otherwise() {
+    let yaml = r#"
+logging: { default: false, rules: [] }
+drop:
+  default: false
+  rules:
+    - name: "Broken"
+      match_conditions: { path: { patterns: ["/api/(unclosed"] } }
+      response: { status_code: 400 }
+    - name: "Valid"
+      match_conditions: { path: { patterns: ["/api/"] } }
+      response: { status_code: 410 }
+"#;
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.yaml");
+    std::fs::write(&path, yaml).unwrap();
+    let err = Config::from_file(path.to_str().unwrap()).unwrap_err();
+    assert!(err.to_string().contains("Invalid path pattern '/api/(unclosed'"), "{}", err);
+
+    // Configs built without from_file skip the broken rule rather than failing
+    let config: Config = serde_norway::from_str(yaml).unwrap();
+    let drop = config.should_drop_request_parts("GET", "/api/(unclosed", &axum::http::HeaderMap::new(), "");
+    assert_eq!(drop.map(|r| r.status_code), Some(410));
+}
@@ -0,0 +1,129 @@
+use axum::body::{Body, Bytes};
+use axum::http::{HeaderMap, HeaderValue, Request, StatusCode};
+use axum::routing::{get, post};
+use axum::Router;
+use logprox::config::{Config, ConfigHolder, DecompressionConfig};
+use logprox::{decoded_text, proxy_handler};
+use std::io::Write;
+use std::sync::Arc;
+use tower::util::ServiceExt;
+
+const TEXT: &str = r#"{"event":"signup","user":"ada"}"#;
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn brotli(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    brotli::BrotliCompress(&mut &data[..], &mut out, &Default::default()).unwrap();
+    out
+}
+
+fn encoded(encoding: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert("content-encoding", HeaderValue::from_str(encoding).unwrap());
+    headers
+}
+
+#[test]
+fn test_supported_encodings_are_decoded() {
+    let config = DecompressionConfig::default();
+    let zstd = zstd::encode_all(TEXT.as_bytes(), 0).unwrap();
+    for (encoding, body) in [("gzip", gzip(TEXT.as_bytes())), ("deflate", deflate(TEXT.as_bytes())), ("br", brotli(TEXT.as_bytes())), ("zstd", zstd)] {
+        assert_eq!(decoded_text(&encoded(encoding), &body, &config), TEXT, "{}", encoding);
+    }
+    // Encodings listed in the order applied: undone last to first
+    let layered = brotli(&gzip(TEXT.as_bytes()));
+    assert_eq!(decoded_text(&encoded("gzip, br"), &layered, &config), TEXT);
+}
+
+#[test]
+fn test_undecodable_bodies_are_returned_as_received() {
+    let config = DecompressionConfig::default();
+    assert_eq!(decoded_text(&encoded("compress"), b"raw", &config), "raw");
+    assert_eq!(decoded_text(&encoded("gzip"), b"not gzip", &config), "not gzip");
+    assert_eq!(decoded_text(&HeaderMap::new(), TEXT.as_bytes(), &config), TEXT);
+
+    let disabled = DecompressionConfig { enabled: false, ..Default::default() };
+    let body = gzip(TEXT.as_bytes());
+    assert_eq!(decoded_text(&encoded("gzip"), &body, &disabled), String::from_utf8_lossy(&body));
+}
+
+#[test]
+fn test_decoded_size_is_capped() {
+    let bomb = gzip(&vec![b'a'; 5 * 1024 * 1024]);
+    assert!(bomb.len() < 10 * 1024);
+    let config = DecompressionConfig { enabled: true, max_bytes: 1024 };
+    assert_eq!(decoded_text(&encoded("gzip"), &bomb, &config), "a".repeat(1024));
+}
+
+/// An upstream echoing request bodies on `/echo/...` and serving a gzipped body on `/data`.
+async fn spawn_upstream() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let data = || async { ([("content-encoding", "gzip")], gzip(TEXT.as_bytes())) };
+    let app = Router::new()
+        .route("/echo/*path", post(|body: Bytes| async move { body }))
+        .route("/data", get(data));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+fn app() -> Router {
+    let yaml = r#"
+logging: { default: false, rules: [] }
+drop:
+  default: false
+  rules:
+    - name: "No signups"
+      match_conditions: { body: { patterns: ["\"event\":\"signup\""] } }
+      response: { status_code: 403 }
+upstream: { allow_private_networks: true }
+"#;
+    let (config, issues) = Config::check_source(yaml);
+    assert!(issues.is_empty(), "{:?}", issues);
+    Router::new().fallback(proxy_handler).with_state(Arc::new(ConfigHolder::new(config.unwrap())))
+}
+
+#[tokio::test]
+async fn test_compressed_request_body_is_matched_decoded_and_forwarded_untouched() {
+    let upstream = spawn_upstream().await;
+    let signup = Request::builder()
+        .method("POST")
+        .uri(format!("/{}/echo/events", upstream))
+        .header("content-encoding", "gzip")
+        .body(Body::from(gzip(TEXT.as_bytes())))
+        .unwrap();
+    assert_eq!(app().oneshot(signup).await.unwrap().status(), StatusCode::FORBIDDEN);
+
+    let login = gzip(br#"{"event":"login"}"#);
+    let req = Request::builder()
+        .method("POST")
+        .uri(format!("/{}/echo/events", upstream))
+        .header("content-encoding", "gzip")
+        .body(Body::from(login.clone()))
+        .unwrap();
+    let resp = app().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(body.to_vec(), login);
+}
+
+#[tokio::test]
+async fn test_compressed_response_is_returned_untouched() {
+    let upstream = spawn_upstream().await;
+    let req = Request::builder().uri(format!("/{}/data", upstream)).body(Body::empty()).unwrap();
+    let resp = app().oneshot(req).await.unwrap();
+    assert_eq!(resp.headers()["content-encoding"], "gzip");
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(body.to_vec(), gzip(TEXT.as_bytes()));
+}
@@ -0,0 +1,132 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use logprox::admin_router;
+use logprox::config::{Config, ConfigHolder};
+use logprox::handlers::dry_run::{dry_run, DryRunInput, SyntheticRequest, SyntheticResponse};
+use std::process::Command;
+use std::sync::Arc;
+use tower::util::ServiceExt;
+
+const CONFIG: &str = r#"
+logging:
+  default: false
+  rules:
+    - name: "Slow API"
+      match_conditions: { path: { patterns: ["/api/slow"] } }
+      capture: { method: true, path: true, headers: ["x-user"] }
+      timeout: 2s
+    - name: "API"
+      match_conditions: { path: { patterns: ["/api/"] } }
+      capture: { method: true }
+drop:
+  default: false
+  rules:
+    - name: "Legacy"
+      match_conditions: { path: { patterns: ["/legacy"] } }
+      response: { status_code: 410, body: "gone" }
+response_logging:
+  default: false
+  rules:
+    - name: "Errors"
+      match_conditions: { status_codes: [500, 502] }
+      capture: { status_code: true, body: true }
+    - name: "Error body"
+      match_conditions: { body: { patterns: ["error"] } }
+      capture: { status_code: true }
+"#;
+
+fn config() -> Config {
+    serde_norway::from_str(CONFIG).unwrap()
+}
+
+fn request(path: &str) -> SyntheticRequest {
+    SyntheticRequest { path: path.to_string(), ..Default::default() }
+}
+
+#[test]
+fn test_dry_run_reports_matches_winner_and_log_entries() {
+    let mut req = request("/api/slow/report");
+    req.headers.insert("x-user".to_string(), "alice".to_string());
+    let input = DryRunInput {
+        request: req,
+        response: Some(SyntheticResponse { status_code: 500, headers: Default::default(), body: "internal error".to_string(), ..Default::default() }),
+    };
+    let report = dry_run(&config(), &input).unwrap();
+
+    assert!(report.drop.matched.is_empty() && report.dropped.is_none());
+    let names: Vec<_> = report.logging.matched.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(names, ["Slow API", "API"]);
+    assert_eq!(report.logging.winner.as_ref().unwrap().index, 0);
+    assert_eq!(report.timeout_ms, Some(2000));
+
+    let log = report.request_log.unwrap();
+    assert_eq!(log["method"], "GET");
+    assert_eq!(log["path"], "/api/slow/report");
+    assert_eq!(log["headers"]["x-user"], "alice");
+    assert_eq!(log["timeout_ms"], 2000);
+
+    let response = report.response_logging.unwrap();
+    assert_eq!(response.matched.len(), 2);
+    assert_eq!(response.winner.unwrap().name, "Errors");
+    let log = report.response_log.unwrap();
+    assert_eq!(log["status_code"], 500);
+    assert_eq!(log["body"], "internal error");
+}
+
+#[test]
+fn test_dry_run_dropped_request_logs_drop_response_only() {
+    let input = DryRunInput {
+        request: request("/legacy/api/x"),
+        response: Some(SyntheticResponse { status_code: 500, headers: Default::default(), body: String::new(), ..Default::default() }),
+    };
+    let report = dry_run(&config(), &input).unwrap();
+
+    assert_eq!(report.drop.winner.unwrap().name, "Legacy");
+    assert_eq!(report.dropped.unwrap().status_code, 410);
+    assert_eq!(report.logging.matched[0].name, "API");
+    assert!(report.request_log.is_none());
+    // The 410 drop response is evaluated, not the supplied 500
+    assert!(report.response_logging.unwrap().matched.is_empty());
+    assert!(report.response_log.is_none());
+}
+
+#[tokio::test]
+async fn test_config_test_endpoint() {
+    let app = admin_router(Arc::new(ConfigHolder::new(config())));
+    let post = |body: &str| {
+        Request::builder()
+            .method("POST")
+            .uri("/config/test")
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    };
+
+    let resp = app.clone().oneshot(post(r#"{"request": {"method": "POST", "path": "/api/users"}}"#)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(report["logging"]["winner"]["name"], "API");
+    assert_eq!(report["request_log"]["method"], "POST");
+    assert!(report["response_logging"].is_null());
+
+    let resp = app.oneshot(post(r#"{"request": {"path": "/x", "headers": {"bad header": "v"}}}"#)).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+}
+
+#[test]
+fn test_test_rule_command() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.yaml");
+    std::fs::write(&path, CONFIG).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_logprox"))
+        .args(["test-rule", "--config", path.to_str().unwrap(), "--path", "/api/slow", "-H", "x-user: bob", "--status", "502"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["logging"]["winner"]["name"], "Slow API");
+    assert_eq!(report["request_log"]["headers"]["x-user"], "bob");
+    assert_eq!(report["response_logging"]["winner"]["name"], "Errors");
+}
@@ -0,0 +1,142 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::{routing::get, Router};
+use futures_util::StreamExt;
+use logprox::config::{Config, ConfigHolder, FaultConfig, FaultRule, LatencyFault, ErrorFault, MatchConditions, UpstreamConfig};
+use logprox::proxy_handler;
+use std::sync::Arc;
+use std::time::Duration;
+use tower::util::ServiceExt;
+
+const UPSTREAM_BODY_LEN: usize = 1000;
+
+async fn spawn_upstream() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new().route("/data", get(|| async { "x".repeat(UPSTREAM_BODY_LEN) }));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+fn fault_rule(name: &str) -> FaultRule {
+    FaultRule {
+        name: name.to_string(),
+        source: None,
+        match_conditions: MatchConditions::default(),
+        percentage: 100.0,
+        latency: None,
+        abort: false,
+        error: None,
+        truncate_body_bytes: None,
+        bandwidth_bytes_per_sec: None,
+    }
+}
+
+fn create_app(rule: FaultRule) -> Router {
+    let config = Config {
+        upstream: UpstreamConfig { allow_private_networks: true, ..Default::default() },
+        faults: FaultConfig { rules: vec![rule] },
+        ..Default::default()
+    };
+    Router::new()
+        .fallback(proxy_handler)
+        .with_state(Arc::new(ConfigHolder::new(config)))
+}
+
+fn get_request(uri: String) -> Request<Body> {
+    Request::builder().method("GET").uri(uri).body(Body::empty()).unwrap()
+}
+
+/// Reads the body frame by frame, returning the bytes received and whether it ended in an error.
+async fn read_body(resp: axum::response::Response) -> (usize, bool) {
+    let mut stream = resp.into_body().into_data_stream();
+    let mut received = 0;
+    while let Some(frame) = stream.next().await {
+        match frame {
+            Ok(chunk) => received += chunk.len(),
+            Err(_) => return (received, true),
+        }
+    }
+    (received, false)
+}
+
+#[tokio::test]
+async fn test_error_fault_skips_upstream() {
+    let rule = FaultRule {
+        error: Some(ErrorFault { status_code: 503, body: Some("chaos".to_string()) }),
+        ..fault_rule("Error")
+    };
+    // Port 1 is never listening: a 502 would mean the upstream was contacted.
+    let resp = create_app(rule).oneshot(get_request("/http://127.0.0.1:1/data".to_string())).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..], b"chaos");
+}
+
+#[tokio::test]
+async fn test_abort_fault_fails_body() {
+    let rule = FaultRule { abort: true, ..fault_rule("Abort") };
+    let resp = create_app(rule).oneshot(get_request("/http://127.0.0.1:1/data".to_string())).await.unwrap();
+    assert_eq!(read_body(resp).await, (0, true));
+}
+
+#[tokio::test]
+async fn test_latency_fault_delays_request() {
+    let upstream = spawn_upstream().await;
+    let rule = FaultRule {
+        latency: Some(LatencyFault { fixed: Some("100ms".to_string()), ..Default::default() }),
+        ..fault_rule("Latency")
+    };
+    let start = std::time::Instant::now();
+    let resp = create_app(rule).oneshot(get_request(format!("/{}/data", upstream))).await.unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(100));
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_truncate_fault_cuts_body() {
+    let upstream = spawn_upstream().await;
+    let rule = FaultRule { truncate_body_bytes: Some(10), ..fault_rule("Truncate") };
+    let resp = create_app(rule).oneshot(get_request(format!("/{}/data", upstream))).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(read_body(resp).await, (10, true));
+}
+
+#[tokio::test]
+async fn test_bandwidth_fault_throttles_body() {
+    let upstream = spawn_upstream().await;
+    // 5000 B/s sends 500-byte chunks every 100ms: two chunks for the 1000-byte body.
+    let rule = FaultRule { bandwidth_bytes_per_sec: Some(5000), ..fault_rule("Throttle") };
+    let resp = create_app(rule).oneshot(get_request(format!("/{}/data", upstream))).await.unwrap();
+    let start = std::time::Instant::now();
+    assert_eq!(read_body(resp).await, (UPSTREAM_BODY_LEN, false));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn test_zero_percentage_never_injects() {
+    let upstream = spawn_upstream().await;
+    let rule = FaultRule { percentage: 0.0, abort: true, ..fault_rule("Never") };
+    let app = create_app(rule);
+    for _ in 0..10 {
+        let resp = app.clone().oneshot(get_request(format!("/{}/data", upstream))).await.unwrap();
+        assert_eq!(read_body(resp).await, (UPSTREAM_BODY_LEN, false));
+    }
+}
+
+#[test]
+fn test_latency_fault_sampling() {
+    let uniform = LatencyFault {
+        fixed: None,
+        min: Some("100ms".to_string()),
+        max: Some("200ms".to_string()),
+    };
+    for _ in 0..100 {
+        let delay = uniform.sample().unwrap();
+        assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(200));
+    }
+
+    let fixed = LatencyFault { fixed: Some("1s".to_string()), ..uniform };
+    assert_eq!(fixed.sample(), Some(Duration::from_secs(1)));
+    assert!(LatencyFault::default().sample().is_none());
+}
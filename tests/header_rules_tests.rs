@@ -0,0 +1,116 @@
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{HeaderMap, Request, StatusCode};
+use axum::routing::get;
+use axum::{Json, Router};
+use logprox::config::{Config, ConfigHolder};
+use logprox::proxy_handler;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower::util::ServiceExt;
+
+/// An upstream echoing the request headers it receives as JSON, and sending a `server` header.
+async fn spawn_upstream() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let echo = |headers: HeaderMap| async move {
+        let mut received = serde_json::Map::new();
+        for name in headers.keys() {
+            let values: Vec<_> = headers.get_all(name).iter().map(|v| v.to_str().unwrap_or_default().to_string()).collect();
+            received.insert(name.to_string(), values.into());
+        }
+        ([("server", "upstream/1.0"), ("x-powered-by", "php")], Json(received))
+    };
+    let app = Router::new().route("/*path", get(echo));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+fn app(yaml: &str) -> Router {
+    let (config, issues) = Config::check_source(yaml);
+    assert!(issues.is_empty(), "{:?}", issues);
+    Router::new().fallback(proxy_handler).with_state(Arc::new(ConfigHolder::new(config.unwrap())))
+}
+
+const RULES: &str = r#"
+logging: { default: false, rules: [] }
+drop:
+  default: false
+  rules:
+    - name: "Gone"
+      match_conditions: { path: { patterns: ["/gone$"] } }
+      response: { status_code: 410 }
+upstream: { allow_private_networks: true }
+headers:
+  rules:
+    - name: "Internal auth"
+      match_conditions:
+        path: { patterns: ["/tenants/(?P<tenant>[a-z]+)/"] }
+      request:
+        remove: ["cookie"]
+        rename: { x-legacy-user: x-user }
+        set:
+          x-internal-auth: "secret-token"
+          x-tenant: "{{path.tenant}}"
+          x-forwarded-for: "{{client_ip}}"
+          x-request-id: "{{request_id}}"
+        add: { accept: "application/json" }
+    - name: "Security headers"
+      match_conditions: {}
+      response:
+        remove: ["server", "x-powered-by"]
+        set:
+          strict-transport-security: "max-age=63072000"
+          x-request-id: "{{request_id}}"
+"#;
+
+#[tokio::test]
+async fn test_request_and_response_headers_are_transformed() {
+    let upstream = spawn_upstream().await;
+    let req = Request::builder()
+        .uri(format!("/{}/tenants/acme/items", upstream))
+        .header("cookie", "session=abc")
+        .header("x-legacy-user", "ada")
+        .header("accept", "text/html")
+        .header("x-request-id", "req-123")
+        .extension(ConnectInfo(SocketAddr::from(([203, 0, 113, 7], 4000))))
+        .body(Body::empty())
+        .unwrap();
+    let resp = app(RULES).oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert!(resp.headers().get("server").is_none());
+    assert!(resp.headers().get("x-powered-by").is_none());
+    assert_eq!(resp.headers()["strict-transport-security"], "max-age=63072000");
+    assert_eq!(resp.headers()["x-request-id"], "req-123");
+
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    let received: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert!(received.get("cookie").is_none());
+    assert!(received.get("x-legacy-user").is_none());
+    assert_eq!(received["x-user"], serde_json::json!(["ada"]));
+    assert_eq!(received["x-internal-auth"], serde_json::json!(["secret-token"]));
+    assert_eq!(received["x-tenant"], serde_json::json!(["acme"]));
+    assert_eq!(received["x-forwarded-for"], serde_json::json!(["203.0.113.7"]));
+    assert_eq!(received["x-request-id"], serde_json::json!(["req-123"]));
+    assert_eq!(received["accept"], serde_json::json!(["text/html", "application/json"]));
+}
+
+#[tokio::test]
+async fn test_response_rules_apply_to_local_responses_with_generated_request_id() {
+    let req = Request::builder().uri("/https://api.example.com/gone").body(Body::empty()).unwrap();
+    let resp = app(RULES).oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::GONE);
+    assert_eq!(resp.headers()["strict-transport-security"], "max-age=63072000");
+    let id = resp.headers()["x-request-id"].to_str().unwrap();
+    assert_eq!(id.len(), 36, "{}", id);
+    assert_eq!(&id[14..15], "4");
+}
+
+#[test]
+fn test_invalid_header_names_are_errors() {
+    let yaml = "logging: { default: false, rules: [] }\ndrop: { default: false, rules: [] }\nheaders:\n  rules:\n    - name: \"Bad\"\n      request: { remove: [\"bad header\"], set: { x-ok: \"line\\nbreak\" } }\n";
+    let (_, issues) = Config::check_source(yaml);
+    let paths: Vec<&str> = issues.iter().map(|issue| issue.path.as_str()).collect();
+    assert_eq!(paths, vec!["headers.rules[0].request.remove[0]", "headers.rules[0].request.set.x-ok"]);
+    assert!(issues.iter().all(|issue| issue.is_error() && issue.line == Some(6)));
+}
@@ -1,3 +1,5 @@
+#![allow(clippy::needless_borrows_for_generic_args)]
+
 use axum::body::Body;
 use axum::http::{Request, StatusCode};
 use axum::Router;
@@ -25,11 +27,11 @@ fn create_test_app(config: Config) -> Router {
 #[tokio::test]
 async fn test_health_check() {
     let config = Arc::new(ConfigHolder::new(Config {
-        server: ServerConfig { port: 3000 },
+        server: ServerConfig { port: 3000, ..Default::default() },
         logging: LoggingConfig { default: false, rules: vec![] },
         drop: DropConfig { default: false, rules: vec![] },
         response_logging: ResponseLoggingConfig { default: false, rules: vec![] },
-        upstream: Default::default(),
+        ..Default::default()
     }));
     let app = Router::new()
         .route("/health", axum::routing::get(get_health_check))
@@ -51,11 +53,11 @@ async fn test_health_check() {
 #[tokio::test]
 async fn test_get_config() {
     let config = Arc::new(ConfigHolder::new(Config {
-        server: ServerConfig { port: 3000 },
+        server: ServerConfig { port: 3000, ..Default::default() },
         logging: LoggingConfig { default: false, rules: vec![] },
         drop: DropConfig { default: false, rules: vec![] },
         response_logging: ResponseLoggingConfig { default: false, rules: vec![] },
-        upstream: Default::default(),
+        ..Default::default()
     }));
     let app = Router::new()
         .route("/config", axum::routing::get(get_config))
@@ -80,11 +82,11 @@ async fn test_get_config() {
 #[tokio::test]
 async fn test_get_config_docs() {
     let config = Arc::new(ConfigHolder::new(Config {
-        server: ServerConfig { port: 3000 },
+        server: ServerConfig { port: 3000, ..Default::default() },
         logging: LoggingConfig { default: false, rules: vec![] },
         drop: DropConfig { default: false, rules: vec![] },
         response_logging: ResponseLoggingConfig { default: false, rules: vec![] },
-        upstream: Default::default(),
+        ..Default::default()
     }));
 
     let app = Router::new()
@@ -113,17 +115,19 @@ async fn test_get_config_docs() {
 #[tokio::test]
 async fn test_proxy_handler_drop_request() {
     let config = Arc::new(ConfigHolder::new(Config {
-        server: ServerConfig { port: 3000 },
+        server: ServerConfig { port: 3000, ..Default::default() },
         logging: LoggingConfig { default: false, rules: vec![] },
         drop: DropConfig {
             default: false,
             rules: vec![DropRule {
                 name: "Test drop".to_string(),
+                source: None,
                 match_conditions: MatchConditions {
                     path: PathMatch { patterns: vec!["/drop.*".to_string()] },
                     methods: vec![],
                     headers: HashMap::new(),
                     body: BodyMatch { patterns: vec![] },
+                    ..Default::default()
                 },
                 response: DropResponse {
                     status_code: 403,
@@ -132,7 +136,7 @@ async fn test_proxy_handler_drop_request() {
             }],
         },
         response_logging: ResponseLoggingConfig { default: false, rules: vec![] },
-        upstream: Default::default(),
+        ..Default::default()
     }));
 
     let app = Router::new()
@@ -249,16 +253,18 @@ async fn test_forward_various_status_codes() {
 #[tokio::test]
 async fn test_timeout_with_short_timeout() {
     let config = Config {
-        server: ServerConfig { port: 3000 },
+        server: ServerConfig { port: 3000, ..Default::default() },
         logging: LoggingConfig {
             default: false,
             rules: vec![LoggingRule {
                 name: "Short timeout".into(),
+                source: None,
                 match_conditions: MatchConditions {
                     path: PathMatch { patterns: vec!["httpbin.org/delay.*".to_string()] },
                     methods: vec![],
                     headers: HashMap::new(),
                     body: BodyMatch { patterns: vec![] },
+                    ..Default::default()
                 },
                 capture: CaptureConfig {
                     headers: vec![],
@@ -266,13 +272,15 @@ async fn test_timeout_with_short_timeout() {
                     method: true,
                     path: true,
                     timing: true,
+                    ..Default::default()
                 },
                 timeout: Some("2s".to_string()),
+                sampling: Default::default(),
             }],
         },
         drop: DropConfig { default: false, rules: vec![] },
         response_logging: ResponseLoggingConfig { default: false, rules: vec![] },
-        upstream: Default::default(),
+        ..Default::default()
     };
 
     let app = create_test_app(config);
@@ -294,16 +302,18 @@ async fn test_timeout_with_short_timeout() {
 #[tokio::test]
 async fn test_timeout_with_no_timeout_rule() {
     let config = Config {
-        server: ServerConfig { port: 3000 },
+        server: ServerConfig { port: 3000, ..Default::default() },
         logging: LoggingConfig {
             default: false,
             rules: vec![LoggingRule {
                 name: "No timeout".into(),
+                source: None,
                 match_conditions: MatchConditions {
                     path: PathMatch { patterns: vec!["httpbin.org/.*".to_string()] },
                     methods: vec![],
                     headers: HashMap::new(),
                     body: BodyMatch { patterns: vec![] },
+                    ..Default::default()
                 },
                 capture: CaptureConfig {
                     headers: vec![],
@@ -311,13 +321,15 @@ async fn test_timeout_with_no_timeout_rule() {
                     method: true,
                     path: true,
                     timing: true,
+                    ..Default::default()
                 },
                 timeout: None,
+                sampling: Default::default(),
             }],
         },
         drop: DropConfig { default: false, rules: vec![] },
         response_logging: ResponseLoggingConfig { default: false, rules: vec![] },
-        upstream: Default::default(),
+        ..Default::default()
     };
 
     let app = create_test_app(config);
@@ -335,11 +347,11 @@ async fn test_timeout_with_no_timeout_rule() {
 #[tokio::test]
 async fn test_upstream_error_handling() {
     let config = Config {
-        server: ServerConfig { port: 3000 },
+        server: ServerConfig { port: 3000, ..Default::default() },
         logging: LoggingConfig { default: false, rules: vec![] },
         drop: DropConfig { default: false, rules: vec![] },
         response_logging: ResponseLoggingConfig { default: false, rules: vec![] },
-        upstream: Default::default(),
+        ..Default::default()
     };
 
     let app = create_test_app(config);
@@ -357,11 +369,11 @@ async fn test_upstream_error_handling() {
 #[tokio::test]
 async fn test_malformed_upstream_url() {
     let config = Config {
-        server: ServerConfig { port: 3000 },
+        server: ServerConfig { port: 3000, ..Default::default() },
         logging: LoggingConfig { default: false, rules: vec![] },
         drop: DropConfig { default: false, rules: vec![] },
         response_logging: ResponseLoggingConfig { default: false, rules: vec![] },
-        upstream: Default::default(),
+        ..Default::default()
     };
 
     let app = create_test_app(config);
@@ -379,11 +391,11 @@ async fn test_malformed_upstream_url() {
 #[tokio::test]
 async fn test_empty_upstream_url() {
     let config = Config {
-        server: ServerConfig { port: 3000 },
+        server: ServerConfig { port: 3000, ..Default::default() },
         logging: LoggingConfig { default: false, rules: vec![] },
         drop: DropConfig { default: false, rules: vec![] },
         response_logging: ResponseLoggingConfig { default: false, rules: vec![] },
-        upstream: Default::default(),
+        ..Default::default()
     };
 
     let app = create_test_app(config);
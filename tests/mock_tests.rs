@@ -0,0 +1,237 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use logprox::config::{Config, ConfigHolder};
+use logprox::proxy_handler;
+use std::sync::Arc;
+use tower::util::ServiceExt;
+
+fn config_from_yaml(dir: &tempfile::TempDir, yaml: &str) -> Config {
+    let config_path = dir.path().join("config.yaml");
+    std::fs::write(&config_path, yaml).unwrap();
+    Config::from_file(config_path.to_str().unwrap()).unwrap()
+}
+
+fn create_proxy_app(config: Config) -> Router {
+    Router::new()
+        .fallback(proxy_handler)
+        .with_state(Arc::new(ConfigHolder::new(config)))
+}
+
+async fn body_string(resp: axum::response::Response) -> String {
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+#[tokio::test]
+async fn test_mock_rule_renders_template() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = config_from_yaml(
+        &dir,
+        r#"
+logging: { default: false, rules: [] }
+drop: { default: false, rules: [] }
+mock:
+  rules:
+    - name: "Stub user lookup"
+      match_conditions:
+        methods: ["POST"]
+        path:
+          patterns: ["/users/(?P<id>\\d+)$"]
+      response:
+        status_code: 201
+        headers:
+          content-type: "application/json"
+          x-echo: "{{header.x-client}}"
+        body: '{"id": "{{path.id}}", "name": "{{body.user.name}}", "first": "{{body.tags.0}}"}'
+"#,
+    );
+    let app = create_proxy_app(config);
+
+    let req = Request::builder()
+        .method("POST")
+        .uri("/https://api.example.com/users/42")
+        .header("x-client", "frontend")
+        .body(Body::from(r#"{"user": {"name": "Ada"}, "tags": ["a", "b"]}"#))
+        .unwrap();
+
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::CREATED);
+    assert_eq!(resp.headers().get("content-type").unwrap(), "application/json");
+    assert_eq!(resp.headers().get("x-echo").unwrap(), "frontend");
+    assert_eq!(
+        body_string(resp).await,
+        r#"{"id": "42", "name": "Ada", "first": "a"}"#
+    );
+}
+
+#[tokio::test]
+async fn test_mock_rule_body_file_and_delay() {
+    let dir = tempfile::tempdir().unwrap();
+    let body_path = dir.path().join("stub.txt");
+    std::fs::write(&body_path, "hello {{method}}").unwrap();
+    let config = config_from_yaml(
+        &dir,
+        &format!(
+            r#"
+logging: {{ default: false, rules: [] }}
+drop: {{ default: false, rules: [] }}
+mock:
+  rules:
+    - name: "File stub"
+      match_conditions:
+        path:
+          patterns: ["/stub$"]
+      response:
+        body_file: "{}"
+        delay: 50ms
+"#,
+            body_path.display()
+        ),
+    );
+    assert_eq!(
+        config.mock.rules[0].response.parse_delay(),
+        Some(std::time::Duration::from_millis(50))
+    );
+    let app = create_proxy_app(config);
+
+    let start = std::time::Instant::now();
+    let req = Request::builder().method("GET").uri("/stub").body(Body::empty()).unwrap();
+    let resp = app.oneshot(req).await.unwrap();
+    assert!(start.elapsed() >= std::time::Duration::from_millis(50));
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(body_string(resp).await, "hello GET");
+}
+
+#[tokio::test]
+async fn test_replay_ndjson_recording() {
+    let dir = tempfile::tempdir().unwrap();
+    let recording = dir.path().join("recording.ndjson");
+    std::fs::write(
+        &recording,
+        concat!(
+            r#"{"method": "GET", "url": "https://api.example.com/items?page=2", "status": 200, "headers": {"content-type": "application/json", "content-length": "999"}, "body": "[1, 2]"}"#,
+            "\n",
+            r#"{"method": "DELETE", "url": "https://api.example.com/items/1", "status": 204}"#,
+            "\n",
+        ),
+    )
+    .unwrap();
+    let config = config_from_yaml(
+        &dir,
+        &format!(
+            r#"
+logging: {{ default: false, rules: [] }}
+drop: {{ default: false, rules: [] }}
+mock:
+  replay:
+    file: "{}"
+"#,
+            recording.display()
+        ),
+    );
+    let app = create_proxy_app(config);
+
+    let req = Request::builder()
+        .method("GET")
+        .uri("/https://api.example.com/items?page=2")
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get("content-type").unwrap(), "application/json");
+    assert_eq!(body_string(resp).await, "[1, 2]");
+
+    let req = Request::builder()
+        .method("DELETE")
+        .uri("/https://api.example.com/items/1")
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.clone().oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+
+    // No recording and no fallthrough: never contacts the upstream.
+    let req = Request::builder()
+        .method("GET")
+        .uri("/https://api.example.com/missing")
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_replay_har_recording() {
+    let dir = tempfile::tempdir().unwrap();
+    let recording = dir.path().join("session.har");
+    std::fs::write(
+        &recording,
+        r#"{
+  "log": {
+    "entries": [
+      {
+        "request": {"method": "GET", "url": "https://api.example.com/logo"},
+        "response": {
+          "status": 200,
+          "headers": [{"name": "Content-Type", "value": "image/png"}, {"name": "Content-Encoding", "value": "gzip"}],
+          "content": {"text": "iVBORw==", "encoding": "base64"}
+        }
+      }
+    ]
+  }
+}"#,
+    )
+    .unwrap();
+    let config = config_from_yaml(
+        &dir,
+        &format!(
+            r#"
+logging: {{ default: false, rules: [] }}
+drop: {{ default: false, rules: [] }}
+mock:
+  replay:
+    file: "{}"
+"#,
+            recording.display()
+        ),
+    );
+    assert!(config.mock.recorded_response("get", "https://api.example.com/logo").is_some());
+    let app = create_proxy_app(config);
+
+    let req = Request::builder()
+        .method("GET")
+        .uri("/https://api.example.com/logo")
+        .body(Body::empty())
+        .unwrap();
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get("content-type").unwrap(), "image/png");
+    assert!(resp.headers().get("content-encoding").is_none());
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..], &[0x89, b'P', b'N', b'G']);
+}
+
+#[test]
+fn test_replay_invalid_recording_fails_load() {
+    let dir = tempfile::tempdir().unwrap();
+    let recording = dir.path().join("recording.ndjson");
+    std::fs::write(&recording, "not json\n").unwrap();
+    let config_path = dir.path().join("config.yaml");
+    std::fs::write(
+        &config_path,
+        format!(
+            r#"
+logging: {{ default: false, rules: [] }}
+drop: {{ default: false, rules: [] }}
+mock:
+  replay:
+    file: "{}"
+"#,
+            recording.display()
+        ),
+    )
+    .unwrap();
+
+    let err = Config::from_file(config_path.to_str().unwrap()).unwrap_err();
+    assert!(err.to_string().contains("line 1"));
+}
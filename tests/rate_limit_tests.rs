@@ -0,0 +1,234 @@
+use axum::body::Body;
+use axum::extract::ConnectInfo;
+use axum::http::{Request, StatusCode};
+use axum::Router;
+use logprox::config::{Config, ConfigHolder, MatchConditions, RateLimitAlgorithm, RateLimitKey, RateLimitRule};
+use logprox::proxy_handler;
+use logprox::rate_limiter::RateLimiter;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tower::util::ServiceExt;
+
+/// Rate-limited app whose requests are answered by a catch-all mock, so no upstream is needed.
+fn create_app(rate_limits_yaml: &str) -> Router {
+    let yaml = format!(
+        r#"
+logging: {{ default: false, rules: [] }}
+drop: {{ default: false, rules: [] }}
+mock:
+  rules:
+    - name: "Catch-all"
+      match_conditions: {{}}
+      response:
+        body: "ok"
+rate_limits:
+{}
+"#,
+        rate_limits_yaml
+    );
+    let config: Config = serde_norway::from_str(&yaml).unwrap();
+    Router::new()
+        .fallback(proxy_handler)
+        .with_state(Arc::new(ConfigHolder::new(config)))
+}
+
+fn rule(algorithm: RateLimitAlgorithm, limit: u64) -> RateLimitRule {
+    RateLimitRule {
+        name: "Test limit".to_string(),
+        source: None,
+        match_conditions: MatchConditions::default(),
+        key: RateLimitKey::ClientIp,
+        algorithm,
+        limit,
+        window: "60s".to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_header_keyed_limit_returns_429() {
+    let app = create_app(
+        r#"
+  rules:
+    - name: "Per API key"
+      key: "header:x-api-key"
+      limit: 2
+      window: 60s
+"#,
+    );
+    let request = |key: &str| {
+        Request::builder()
+            .uri("/https://api.example.com/")
+            .header("x-api-key", key)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    for _ in 0..2 {
+        let resp = app.clone().oneshot(request("alice")).await.unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    let resp = app.clone().oneshot(request("alice")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert_eq!(resp.headers().get("ratelimit-limit").unwrap(), "2");
+    assert_eq!(resp.headers().get("ratelimit-remaining").unwrap(), "0");
+    let retry_after: u64 = resp.headers().get("retry-after").unwrap().to_str().unwrap().parse().unwrap();
+    assert!((1..=30).contains(&retry_after));
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(json["rule"], "Per API key");
+
+    // A different key has its own bucket.
+    let resp = app.oneshot(request("bob")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_client_ip_limit_scoped_by_match_conditions() {
+    let app = create_app(
+        r#"
+  rules:
+    - name: "Per client on /login"
+      match_conditions:
+        path:
+          patterns: ["/login$"]
+      key: client_ip
+      algorithm: sliding_window
+      limit: 1
+      window: 60s
+"#,
+    );
+    let request = |path: &str, ip: [u8; 4]| {
+        let mut req = Request::builder().uri(path).body(Body::empty()).unwrap();
+        req.extensions_mut().insert(ConnectInfo(SocketAddr::from((ip, 5000))));
+        req
+    };
+
+    let resp = app.clone().oneshot(request("/login", [10, 0, 0, 1])).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = app.clone().oneshot(request("/login", [10, 0, 0, 1])).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+
+    // Other clients and unmatched paths are unaffected.
+    let resp = app.clone().oneshot(request("/login", [10, 0, 0, 2])).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = app.oneshot(request("/other", [10, 0, 0, 1])).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_upstream_host_key_counts_the_rewritten_host() {
+    let yaml = r#"
+logging: { default: false, rules: [] }
+drop: { default: false, rules: [] }
+mock:
+  rules:
+    - name: "Catch-all"
+      match_conditions: {}
+      response: { body: "ok" }
+rewrites:
+  rules:
+    - name: "Mirror"
+      match_conditions: { path: { patterns: ["^/https://mirror\\.example\\.com/"] } }
+      host: "api.example.com"
+rate_limits:
+  rules:
+    - { name: "Per host", key: upstream_host, limit: 1, window: 60s }
+"#;
+    let (config, issues) = Config::check_source(yaml);
+    assert!(issues.is_empty(), "{:?}", issues);
+    let app = Router::new().fallback(proxy_handler).with_state(Arc::new(ConfigHolder::new(config.unwrap())));
+    let request = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+    let resp = app.clone().oneshot(request("/https://api.example.com/a")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    // Rewritten to api.example.com, so it shares that host's bucket
+    let resp = app.clone().oneshot(request("/https://mirror.example.com/a")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    let resp = app.oneshot(request("/https://other.example.com/a")).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+}
+
+#[test]
+fn test_limiter_algorithms() {
+    for algorithm in [RateLimitAlgorithm::TokenBucket, RateLimitAlgorithm::SlidingWindow] {
+        let limiter = RateLimiter::default();
+        let rule = rule(algorithm, 3);
+        let decisions: Vec<_> = (0..4).map(|_| limiter.check(&rule, "k")).collect();
+        assert!(decisions[..3].iter().all(|d| d.allowed), "{:?}", algorithm);
+        assert_eq!(decisions[2].remaining, 0);
+        assert!(!decisions[3].allowed);
+        assert!(decisions[3].retry_after_secs >= 1);
+    }
+}
+
+#[test]
+fn test_zero_limit_denies_with_finite_retry() {
+    for algorithm in [RateLimitAlgorithm::TokenBucket, RateLimitAlgorithm::SlidingWindow] {
+        let decision = RateLimiter::default().check(&rule(algorithm, 0), "k");
+        assert!(!decision.allowed, "{:?}", algorithm);
+        assert_eq!(decision.retry_after_secs, 60, "{:?}", algorithm);
+    }
+
+    let yaml = r#"
+logging: { default: false, rules: [] }
+drop: { default: false, rules: [] }
+rate_limits:
+  rules:
+    - { name: "Closed", key: client_ip, limit: 0, window: 60s }
+    - { name: "Instant", key: client_ip, limit: 10, window: 0s }
+"#;
+    let (_, issues) = Config::check_source(yaml);
+    let found: Vec<(&str, bool)> = issues.iter().map(|issue| (issue.path.as_str(), issue.is_error())).collect();
+    assert_eq!(found, vec![("rate_limits.rules[0].limit", true), ("rate_limits.rules[1].window", true)]);
+}
+
+#[test]
+fn test_pruning_keeps_each_rule_to_its_own_window() {
+    let limiter = RateLimiter::default();
+    let hourly = RateLimitRule { name: "Hourly".to_string(), window: "3600s".to_string(), ..rule(RateLimitAlgorithm::SlidingWindow, 1) };
+    let per_second = RateLimitRule { name: "Per second".to_string(), window: "1s".to_string(), ..rule(RateLimitAlgorithm::TokenBucket, 1) };
+    let start = 1_000_000.0;
+    assert!(limiter.check_at(&hourly, "k", start).allowed);
+    assert!(!limiter.check_at(&hourly, "k", start).allowed);
+
+    // Enough short-window keys to prune, then a new one once they are all idle
+    for i in 0..=10_000 {
+        limiter.check_at(&per_second, &format!("10.0.{}.{}", i / 256, i % 256), start);
+    }
+    assert!(limiter.check_at(&per_second, "10.1.0.0", start + 10.0).allowed);
+    assert!(!limiter.check_at(&hourly, "k", start + 10.0).allowed);
+}
+
+#[test]
+fn test_keys_beyond_the_cap_share_a_counter() {
+    let limiter = RateLimiter::default();
+    let rule = rule(RateLimitAlgorithm::TokenBucket, 1);
+    for i in 0..100_000 {
+        assert!(limiter.check_at(&rule, &format!("key-{}", i), 0.0).allowed);
+    }
+    assert!(limiter.check_at(&rule, "new-1", 0.0).allowed);
+    assert!(!limiter.check_at(&rule, "new-2", 0.0).allowed);
+    // Tracked keys keep their own counters
+    assert!(!limiter.check_at(&rule, "key-0", 0.0).allowed);
+}
+
+#[test]
+fn test_limiter_snapshot_round_trip() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("limits.json");
+    let path = path.to_str().unwrap();
+    let rule = rule(RateLimitAlgorithm::TokenBucket, 1);
+
+    let limiter = RateLimiter::default();
+    assert!(limiter.check(&rule, "10.0.0.1").allowed);
+    limiter.save_snapshot(path).unwrap();
+
+    let restored = RateLimiter::default();
+    restored.load_snapshot(path).unwrap();
+    assert!(!restored.check(&rule, "10.0.0.1").allowed);
+    assert!(restored.check(&rule, "10.0.0.2").allowed);
+
+    // A missing snapshot is not an error.
+    RateLimiter::default().load_snapshot(dir.path().join("missing.json").to_str().unwrap()).unwrap();
+}
@@ -0,0 +1,109 @@
+use logprox::config::{Config, ConfigHolder};
+use logprox::reload;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn config_yaml(body: &str) -> String {
+    format!(
+        r#"
+logging: {{ default: false, rules: [] }}
+drop: {{ default: false, rules: [] }}
+mock:
+  rules:
+    - name: "Stub"
+      match_conditions: {{}}
+      response:
+        body: "{}"
+"#,
+        body
+    )
+}
+
+fn mock_body(holder: &ConfigHolder) -> Option<String> {
+    holder.get().mock.rules[0].response.body.clone()
+}
+
+fn setup(dir: &tempfile::TempDir) -> (String, Arc<ConfigHolder>) {
+    let path = dir.path().join("config.yaml");
+    std::fs::write(&path, config_yaml("v1")).unwrap();
+    let path = path.to_str().unwrap().to_string();
+    let holder = Arc::new(ConfigHolder::new(Config::from_file(&path).unwrap()));
+    (path, holder)
+}
+
+/// Polls until the stub body equals `expected`, for up to five seconds.
+async fn wait_for_body(holder: &ConfigHolder, expected: &str) -> bool {
+    for _ in 0..100 {
+        if mock_body(holder).as_deref() == Some(expected) {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    false
+}
+
+#[test]
+fn test_failed_reload_keeps_previous_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let (path, holder) = setup(&dir);
+
+    std::fs::write(&path, config_yaml("v2")).unwrap();
+    reload::reload_and_log(&holder, &path, "test").unwrap();
+    assert_eq!(mock_body(&holder).as_deref(), Some("v2"));
+
+    std::fs::write(&path, "logging: [not, a, map").unwrap();
+    assert!(reload::reload_and_log(&holder, &path, "test").is_err());
+    assert_eq!(mock_body(&holder).as_deref(), Some("v2"));
+}
+
+#[test]
+fn test_snapshot_is_unaffected_by_reload() {
+    let dir = tempfile::tempdir().unwrap();
+    let (path, holder) = setup(&dir);
+    let snapshot = holder.get();
+
+    std::fs::write(&path, config_yaml("v2")).unwrap();
+    holder.reload_from(&path).unwrap();
+
+    assert_eq!(snapshot.mock.rules[0].response.body.as_deref(), Some("v1"));
+    assert_eq!(mock_body(&holder).as_deref(), Some("v2"));
+}
+
+#[tokio::test]
+async fn test_file_watch_reloads_on_change() {
+    let dir = tempfile::tempdir().unwrap();
+    let (path, holder) = setup(&dir);
+    let _watcher = reload::watch_config_file(holder.clone(), &path, Duration::from_millis(50)).unwrap();
+
+    std::fs::write(&path, config_yaml("v2")).unwrap();
+    assert!(wait_for_body(&holder, "v2").await, "watcher did not reload");
+
+    // A broken file is rejected and the last good config stays active
+    std::fs::write(&path, config_yaml("v3").replace("rules:", "rules: [")).unwrap();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(mock_body(&holder).as_deref(), Some("v2"));
+
+    // Editors that save via rename are picked up too
+    let tmp = dir.path().join("config.yaml.tmp");
+    std::fs::write(&tmp, config_yaml("v4")).unwrap();
+    std::fs::rename(&tmp, &path).unwrap();
+    assert!(wait_for_body(&holder, "v4").await, "watcher missed a rename");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_sighup_reloads_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let (path, holder) = setup(&dir);
+    let task = reload::spawn_sighup_reloader(holder.clone(), path.clone()).unwrap();
+
+    std::fs::write(&path, config_yaml("v2")).unwrap();
+    let status = std::process::Command::new("kill")
+        .args(["-HUP", &std::process::id().to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(wait_for_body(&holder, "v2").await, "SIGHUP did not reload");
+
+    task.abort();
+}
@@ -0,0 +1,144 @@
+use axum::http::{HeaderMap, HeaderValue};
+use logprox::config::{Config, RequestContext, ResponseContext};
+use logprox::handlers::dry_run::{dry_run, DryRunInput, SyntheticRequest, SyntheticResponse};
+use std::time::Duration;
+
+const CONFIG: &str = r#"
+logging: { default: false, rules: [] }
+drop: { default: false, rules: [] }
+response_logging:
+  default: false
+  rules:
+    - name: "Slow checkout"
+      match_conditions:
+        min_latency: 2s
+        request:
+          methods: [POST]
+          path: { patterns: ["/checkout"] }
+      capture: { timing: true }
+    - name: "Errors"
+      match_conditions:
+        status_codes: [429]
+        status_ranges: ["5xx"]
+      capture: { status_code: true }
+    - name: "Client errors"
+      match_conditions:
+        status_ranges: ["400-403", "405-428"]
+      capture: { status_code: true }
+    - name: "Large or empty bodies"
+      match_conditions:
+        any:
+          - min_body_bytes: 1000
+          - max_body_bytes: 0
+      capture: { body: true }
+"#;
+
+fn config() -> Config {
+    let (config, issues) = Config::check_source(CONFIG);
+    assert!(issues.is_empty(), "{:?}", issues);
+    config.unwrap()
+}
+
+fn matched<'a>(config: &'a Config, ctx: &ResponseContext) -> Option<&'a str> {
+    config.should_log_response_ctx(ctx)?;
+    let index = config.rules().response_logging.iter().position(|rule| rule.matches_ctx(ctx))?;
+    Some(config.response_logging.rules[index].name.as_str())
+}
+
+#[test]
+fn test_status_classes_and_ranges() {
+    let config = config();
+    let headers = HeaderMap::new();
+    let name = |status| matched(&config, &ResponseContext::new(status, &headers, "ok"));
+    assert_eq!(name(503), Some("Errors"));
+    assert_eq!(name(429), Some("Errors"));
+    assert_eq!(name(401), Some("Client errors"));
+    assert_eq!(name(404), None);
+    assert_eq!(name(200), None);
+}
+
+#[test]
+fn test_latency_and_request_conditions() {
+    let config = config();
+    let headers = HeaderMap::new();
+    let checkout = RequestContext::new("POST", "/https://shop.example.com/checkout", &headers, "");
+    let browse = RequestContext::new("GET", "/https://shop.example.com/checkout", &headers, "");
+    let response = |request: &RequestContext, latency_ms| {
+        let ctx = ResponseContext::new(200, &headers, "ok").with_latency(Duration::from_millis(latency_ms)).with_request(request);
+        matched(&config, &ctx).map(str::to_string)
+    };
+    assert_eq!(response(&checkout, 2000).as_deref(), Some("Slow checkout"));
+    assert_eq!(response(&checkout, 1999), None);
+    assert_eq!(response(&browse, 5000), None);
+
+    // Without a latency or a request, those conditions never match
+    let unknown = ResponseContext::new(200, &headers, "ok").with_request(&checkout);
+    assert_eq!(matched(&config, &unknown), None);
+    let no_request = ResponseContext::new(200, &headers, "ok").with_latency(Duration::from_secs(3));
+    assert_eq!(matched(&config, &no_request), None);
+}
+
+#[test]
+fn test_body_size_uses_the_received_size() {
+    let config = config();
+    let mut headers = HeaderMap::new();
+    headers.insert("content-encoding", HeaderValue::from_static("gzip"));
+    let body = "x".repeat(1500);
+    // A compressed body is sized as received, not as decoded
+    let compressed = ResponseContext::new(200, &headers, &body).with_body_size(40);
+    assert_eq!(matched(&config, &compressed), None);
+    let large = ResponseContext::new(200, &headers, &body);
+    assert_eq!(matched(&config, &large), Some("Large or empty bodies"));
+    let empty = ResponseContext::new(204, &headers, "");
+    assert_eq!(matched(&config, &empty), Some("Large or empty bodies"));
+}
+
+#[test]
+fn test_dry_run_evaluates_latency_and_request() {
+    let config = config();
+    let input = |latency_ms| DryRunInput {
+        request: SyntheticRequest { method: "POST".into(), path: "/https://shop.example.com/checkout".into(), ..Default::default() },
+        response: Some(SyntheticResponse { body: "ok".into(), latency_ms, ..Default::default() }),
+    };
+    let report = dry_run(&config, &input(Some(2500))).unwrap();
+    assert_eq!(report.response_logging.unwrap().winner.unwrap().name, "Slow checkout");
+    assert!(report.response_log.is_some());
+    let report = dry_run(&config, &input(None)).unwrap();
+    assert!(report.response_logging.unwrap().winner.is_none());
+    assert!(report.response_log.is_none());
+}
+
+#[test]
+fn test_response_conditions_are_validated() {
+    let yaml = r#"
+logging: { default: false, rules: [] }
+drop: { default: false, rules: [] }
+response_logging:
+  default: false
+  rules:
+    - name: "Bad"
+      match_conditions:
+        status_ranges: ["4xx", "6xx0", "599-500"]
+        min_latency: soon
+        min_body_bytes: 10
+        max_body_bytes: 5
+        request: { path: { patterns: ["("] } }
+      capture: {}
+    - name: "Backwards"
+      match_conditions: { min_latency: 2s, max_latency: 500ms }
+      capture: {}
+"#;
+    let (_, issues) = Config::check_source(yaml);
+    let found: Vec<(&str, bool)> = issues.iter().map(|issue| (issue.path.as_str(), issue.is_error())).collect();
+    assert_eq!(
+        found,
+        vec![
+            ("response_logging.rules[0].match_conditions.status_ranges[1]", true),
+            ("response_logging.rules[0].match_conditions.status_ranges[2]", true),
+            ("response_logging.rules[0].match_conditions.min_latency", true),
+            ("response_logging.rules[0].match_conditions.min_body_bytes", false),
+            ("response_logging.rules[0].match_conditions.request.path.patterns[0]", true),
+            ("response_logging.rules[1].match_conditions.min_latency", false),
+        ]
+    );
+}
@@ -0,0 +1,119 @@
+use axum::body::Body;
+use axum::extract::OriginalUri;
+use axum::http::{HeaderMap, Request, StatusCode};
+use axum::routing::get;
+use axum::Router;
+use logprox::config::{Config, ConfigHolder, RequestContext};
+use logprox::proxy_handler;
+use std::sync::Arc;
+use tower::util::ServiceExt;
+
+/// An upstream answering every path with the path it was asked for.
+async fn spawn_upstream() -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new().route("/*path", get(|OriginalUri(uri): OriginalUri| async move { uri.path().to_string() }));
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    format!("http://{}", addr)
+}
+
+fn config(rules: &str, allow_private_networks: bool) -> Config {
+    let yaml = format!(
+        "logging: {{ default: false, rules: [] }}\ndrop: {{ default: false, rules: [] }}\nupstream: {{ allow_private_networks: {} }}\nrewrites:\n  rules:\n{}",
+        allow_private_networks, rules
+    );
+    let (config, issues) = Config::check_source(&yaml);
+    assert!(issues.is_empty(), "{:?}", issues);
+    config.unwrap()
+}
+
+fn rewritten(config: &Config, path: &str) -> Option<String> {
+    let headers = HeaderMap::new();
+    let ctx = RequestContext::new("GET", path, &headers, "");
+    config.rewrite_ctx(&ctx, &path[1..]).map(|(_, url)| url)
+}
+
+fn app(config: Config) -> Router {
+    Router::new().fallback(proxy_handler).with_state(Arc::new(ConfigHolder::new(config)))
+}
+
+#[test]
+fn test_rewrites_with_captures_and_host_swap() {
+    let config = config(
+        r#"
+    - name: "v1 to v2"
+      target: path
+      pattern: "^/v1/(?P<rest>.*)$"
+      replacement: "/v2/$${rest}"  # $$ escapes the variable placeholder
+    - name: "Legacy host"
+      match_conditions: { methods: ["GET"] }
+      pattern: "^http://legacy\\.example\\.com/"
+      replacement: "https://api.example.com/legacy/"
+    - name: "Staging"
+      pattern: "staging"
+      host: "staging.internal:8443"
+"#,
+        false,
+    );
+    assert_eq!(rewritten(&config, "/https://api.example.com/v1/users/7").as_deref(), Some("https://api.example.com/v2/users/7"));
+    assert_eq!(rewritten(&config, "/http://legacy.example.com/a/b").as_deref(), Some("https://api.example.com/legacy/a/b"));
+    assert_eq!(rewritten(&config, "/https://staging.example.com/x").as_deref(), Some("https://staging.internal:8443/x"));
+    assert_eq!(rewritten(&config, "/https://api.example.com/v3/users"), None);
+}
+
+#[tokio::test]
+async fn test_rewritten_request_is_proxied_to_new_path() {
+    let upstream = spawn_upstream().await;
+    let config = config("    - { name: \"v2\", target: path, pattern: \"^/v1/\", replacement: \"/v2/\" }\n", true);
+    let req = Request::builder().uri(format!("/{}/v1/items", upstream)).body(Body::empty()).unwrap();
+    let resp = app(config).oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    assert_eq!(&body[..], b"/v2/items");
+}
+
+#[tokio::test]
+async fn test_redirect_mode_returns_location_through_proxy() {
+    let config = config(
+        "    - { name: \"Moved\", target: path, pattern: \"^/old/\", replacement: \"/new/\", redirect: 308 }\n",
+        false,
+    );
+    let req = Request::builder().uri("/https://api.example.com/old/page?lang=en").body(Body::empty()).unwrap();
+    let resp = app(config).oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::PERMANENT_REDIRECT);
+    assert_eq!(resp.headers()["location"], "/https://api.example.com/new/page?lang=en");
+}
+
+#[tokio::test]
+async fn test_rewritten_url_is_checked_for_ssrf() {
+    let config = config("    - { name: \"Sneaky\", host: \"127.0.0.1:9\" }\n", false);
+    let req = Request::builder().uri("/https://api.example.com/data").body(Body::empty()).unwrap();
+    let resp = app(config).oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+}
+
+#[test]
+fn test_invalid_rewrite_rules_are_reported() {
+    let yaml = r#"
+logging: { default: false, rules: [] }
+drop: { default: false, rules: [] }
+rewrites:
+  rules:
+    - { name: "a", pattern: "(oops", replacement: "x" }
+    - { name: "b", replacement: "/v2/" }
+    - { name: "c", host: "bad host/path", redirect: 303 }
+    - { name: "d", pattern: "x" }
+"#;
+    let (_, issues) = Config::check_source(yaml);
+    let found: Vec<(String, bool)> = issues.iter().map(|issue| (issue.path.clone(), issue.is_error())).collect();
+    assert_eq!(
+        found,
+        vec![
+            ("rewrites.rules[0].pattern".to_string(), true),
+            ("rewrites.rules[1].replacement".to_string(), true),
+            ("rewrites.rules[2].host".to_string(), true),
+            ("rewrites.rules[2].redirect".to_string(), true),
+            ("rewrites.rules[3]".to_string(), false),
+        ]
+    );
+}
@@ -0,0 +1,263 @@
+use axum::body::Body;
+use axum::http::{HeaderMap, HeaderValue, Request, StatusCode};
+use axum::Router;
+use logprox::config::{Config, ConfigHolder, RequestContext, ResponseContext};
+use logprox::proxy_handler;
+use std::sync::{Arc, Mutex};
+use tower::util::ServiceExt;
+
+fn config(yaml: &str) -> Config {
+    let (config, issues) = Config::check_source(yaml);
+    assert!(issues.is_empty(), "{:?}", issues);
+    config.unwrap()
+}
+
+/// Whether the request's entry is logged, decided as the proxy decides it.
+fn logs_request(config: &Config, ctx: &RequestContext, request_id: &str) -> bool {
+    config.logging_match_ctx(ctx).map_or(true, |(_, sampler)| sampler.sample(request_id, ctx.headers))
+}
+
+/// Whether the response's entry is logged, decided as the proxy decides it.
+fn logs_response(config: &Config, ctx: &ResponseContext, request_id: &str) -> bool {
+    config.response_logging_match_ctx(ctx).map_or(true, |(_, sampler)| sampler.sample_response(request_id, ctx))
+}
+
+#[test]
+fn test_sample_every_logs_one_in_n() {
+    let config = config(
+        r#"
+logging:
+  default: false
+  rules:
+    - name: "Broad"
+      match_conditions: {}
+      capture: { method: true }
+      sample_every: 3
+drop: { default: false, rules: [] }
+"#,
+    );
+    let headers = HeaderMap::new();
+    let ctx = RequestContext::new("GET", "/https://api.example.com/", &headers, "");
+    let logged: Vec<bool> = (0..7).map(|_| logs_request(&config, &ctx, "id")).collect();
+    assert_eq!(logged, vec![true, false, false, true, false, false, true]);
+}
+
+const KEYED: &str = r#"
+logging:
+  default: false
+  rules:
+    - name: "By request"
+      match_conditions: { path: { patterns: ["/orders"] } }
+      capture: { method: true }
+      sample_rate: 0.25
+      sample_key: request_id
+    - name: "By trace"
+      match_conditions: {}
+      capture: { method: true }
+      sample_rate: 0.5
+      sample_key: "header:X-Trace-Id"
+drop: { default: false, rules: [] }
+response_logging:
+  default: false
+  rules:
+    - name: "Responses"
+      match_conditions: { request: { path: { patterns: ["/orders"] } } }
+      capture: { status_code: true }
+      sample_rate: 0.25
+      sample_key: request_id
+      always_log_errors: true
+"#;
+
+#[test]
+fn test_keyed_sampling_keeps_requests_and_responses_together() {
+    let config = config(KEYED);
+    let headers = HeaderMap::new();
+    let request = RequestContext::new("GET", "/https://api.example.com/orders", &headers, "");
+    let response = ResponseContext::new(200, &headers, "").with_request(&request);
+    let mut logged = 0;
+    for i in 0..400 {
+        let id = format!("req-{}", i);
+        let request_logged = logs_request(&config, &request, &id);
+        assert_eq!(request_logged, logs_request(&config, &request, &id));
+        assert_eq!(request_logged, logs_response(&config, &response, &id));
+        logged += usize::from(request_logged);
+    }
+    assert!((60..=140).contains(&logged), "{} of 400 logged", logged);
+
+    // Errors are logged even when sampled out
+    let error = ResponseContext::new(503, &headers, "").with_request(&request);
+    assert!((0..400).all(|i| logs_response(&config, &error, &format!("req-{}", i))));
+}
+
+#[test]
+fn test_header_keyed_sampling_is_deterministic() {
+    let config = config(KEYED);
+    let decisions = |trace: &str| -> Vec<bool> {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-trace-id", HeaderValue::from_str(trace).unwrap());
+        let ctx = RequestContext::new("GET", "/https://api.example.com/users", &headers, "");
+        (0..20).map(|i| logs_request(&config, &ctx, &format!("req-{}", i))).collect()
+    };
+    // The request ID varies; the trace ID alone decides
+    let traces: Vec<Vec<bool>> = (0..50).map(|i| decisions(&format!("trace-{}", i))).collect();
+    assert!(traces.iter().all(|d| d.iter().all(|&logged| logged == d[0])));
+    assert!(traces.iter().any(|d| d[0]) && traces.iter().any(|d| !d[0]));
+}
+
+#[tokio::test]
+async fn test_sampled_out_entries_are_counted_and_errors_logged() {
+    let config = config(
+        r#"
+logging:
+  default: false
+  rules:
+    - name: "Nothing but errors"
+      match_conditions: {}
+      capture: { method: true, path: true }
+      sample_rate: 0
+      always_log_errors: true
+drop:
+  default: false
+  rules:
+    - name: "Gone"
+      match_conditions: { path: { patterns: ["/gone"] } }
+      response: { status_code: 410 }
+response_logging:
+  default: false
+  rules:
+    - name: "Sampled"
+      match_conditions: {}
+      capture: { status_code: true }
+      sample_every: 2
+faults:
+  rules:
+    - name: "Broken"
+      match_conditions: { path: { patterns: ["/broken"] } }
+      error: { status_code: 503 }
+    - name: "Teapot"
+      match_conditions: { path: { patterns: ["/teapot"] } }
+      error: { status_code: 418 }
+"#,
+    );
+    let holder = Arc::new(ConfigHolder::new(config));
+    let app = Router::new().fallback(proxy_handler).with_state(holder.clone());
+    let send = |path: &'static str| {
+        let app = app.clone();
+        async move { app.oneshot(Request::builder().uri(path).body(Body::empty()).unwrap()).await.unwrap().status() }
+    };
+
+    assert_eq!(send("/https://api.example.com/gone").await, StatusCode::GONE);
+    assert_eq!(send("/https://api.example.com/gone").await, StatusCode::GONE);
+    assert_eq!(send("/https://api.example.com/teapot").await, StatusCode::IM_A_TEAPOT);
+    assert_eq!(send("/https://api.example.com/broken").await, StatusCode::SERVICE_UNAVAILABLE);
+
+    let snapshot = holder.metrics().snapshot();
+    // Drops are never request-logged; the 503 request entry was held back and logged
+    assert_eq!(snapshot.sampled_out.get("logging.Nothing but errors"), Some(&1));
+    assert_eq!(snapshot.sampled_out.get("response_logging.Sampled"), Some(&2));
+    assert_eq!(snapshot.sampled_out_total, 3);
+}
+
+/// Collects everything the proxy logs while installed as the default subscriber.
+#[derive(Clone, Default)]
+struct LogBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl std::io::Write for LogBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_held_request_entry_is_logged_on_upstream_error() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let upstream = Router::new()
+        .route("/ok", axum::routing::get(|| async { "fine" }))
+        .route("/fail", axum::routing::get(|| async { (StatusCode::INTERNAL_SERVER_ERROR, "broken") }));
+    tokio::spawn(async move { axum::serve(listener, upstream).await.unwrap() });
+
+    let config = config(
+        r#"
+logging:
+  default: false
+  rules:
+    - name: "Nothing but errors"
+      match_conditions: {}
+      capture: { method: true, path: true }
+      sample_rate: 0
+      always_log_errors: true
+drop: { default: false, rules: [] }
+upstream: { allow_private_networks: true }
+"#,
+    );
+    let holder = Arc::new(ConfigHolder::new(config));
+    let app = Router::new().fallback(proxy_handler).with_state(holder.clone());
+    let logs = LogBuffer::default();
+    let writer = logs.clone();
+    let _subscriber = tracing::subscriber::set_default(
+        tracing_subscriber::fmt().with_ansi(false).with_writer(move || writer.clone()).finish(),
+    );
+
+    for (path, status) in [("ok", StatusCode::OK), ("fail", StatusCode::INTERNAL_SERVER_ERROR)] {
+        let uri = format!("/http://{}/{}", addr, path);
+        let resp = app.clone().oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap()).await.unwrap();
+        assert_eq!(resp.status(), status);
+    }
+
+    let output = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    let entries: Vec<&str> = output.lines().filter(|line| line.contains("\"method\":\"GET\"")).collect();
+    assert_eq!(entries.len(), 1, "{}", output);
+    assert!(entries[0].contains("/fail"), "{}", entries[0]);
+    assert_eq!(holder.metrics().snapshot().sampled_out.get("logging.Nothing but errors"), Some(&1));
+}
+
+#[test]
+fn test_sampling_settings_are_validated() {
+    let yaml = r#"
+logging:
+  default: false
+  rules:
+    - name: "Bad"
+      match_conditions: {}
+      capture: {}
+      sample_rate: 1.5
+      sample_every: 0
+    - name: "Unsampled"
+      match_conditions: { methods: [GET] }
+      capture: {}
+      sample_key: request_id
+      always_log_errors: true
+drop: { default: false, rules: [] }
+response_logging:
+  default: false
+  rules:
+    - name: "Both"
+      match_conditions: {}
+      capture: {}
+      sample_rate: 0.1
+      sample_every: 10
+"#;
+    let (_, issues) = Config::check_source(yaml);
+    let found: Vec<(&str, bool)> = issues.iter().map(|issue| (issue.path.as_str(), issue.is_error())).collect();
+    assert_eq!(
+        found,
+        vec![
+            ("logging.rules[0].sample_rate", true),
+            ("logging.rules[0].sample_rate", false),
+            ("logging.rules[0].sample_every", true),
+            ("logging.rules[1]", false),
+            ("logging.rules[1].sample_key", false),
+            ("logging.rules[1].always_log_errors", false),
+            ("response_logging.rules[0].sample_rate", false),
+        ]
+    );
+
+    let (_, issues) = Config::check_source(&yaml.replace("sample_key: request_id", "sample_key: trace"));
+    assert!(issues.iter().any(|issue| issue.is_error() && issue.message.contains("invalid sample key 'trace'")), "{:?}", issues);
+}
@@ -0,0 +1,124 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use logprox::admin_router;
+use logprox::config::{schema, Config, ConfigHolder, ConfigSources};
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Arc;
+use tower::util::ServiceExt;
+
+fn write(dir: &Path, name: &str, contents: &str) -> String {
+    let path = dir.join(name);
+    std::fs::write(&path, contents).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+fn load(path: &str) -> (Option<Config>, Vec<logprox::config::ValidationIssue>) {
+    Config::check_sources(&ConfigSources::load_for_env(path, None))
+}
+
+#[test]
+fn test_json_and_toml_configs_load_by_extension() {
+    let dir = tempfile::tempdir().unwrap();
+    let json = write(
+        dir.path(),
+        "config.json",
+        r#"{
+  "server": { "port": 8081 },
+  "logging": { "default": false, "rules": [] },
+  "drop": { "default": false, "rules": [] },
+  "include": "rules.toml"
+}"#,
+    );
+    write(
+        dir.path(),
+        "rules.toml",
+        r#"
+[[drop.rules]]
+name = "Gone"
+match_conditions = { path = { patterns = ["^/old"] } }
+response = { status_code = 410 }
+"#,
+    );
+
+    let (config, issues) = load(&json);
+    assert!(issues.is_empty(), "{:?}", issues);
+    let config = config.unwrap();
+    assert_eq!(config.server.port, 8081);
+    assert_eq!(config.drop.rules[0].name, "Gone");
+    assert!(config.drop.rules[0].source.as_deref().unwrap().ends_with("rules.toml"));
+}
+
+#[test]
+fn test_syntax_errors_are_located_in_every_format() {
+    let dir = tempfile::tempdir().unwrap();
+    let json = write(dir.path(), "bad.json", "{\n  \"logging\": { \"default\": false, }\n}\n");
+    let toml = write(dir.path(), "bad.toml", "[logging]\ndefault = false\nrules = [}\n[drop]\n");
+
+    for (path, line) in [(json, 2), (toml, 3)] {
+        let (config, issues) = load(&path);
+        assert!(config.is_none());
+        assert_eq!(issues.len(), 1, "{:?}", issues);
+        assert_eq!(issues[0].file.as_deref(), Some(path.as_str()));
+        assert_eq!(issues[0].line, Some(line), "{}", issues[0]);
+    }
+
+    // JSON values are located like YAML ones.
+    let invalid = write(
+        dir.path(),
+        "invalid.json",
+        "{\n  \"logging\": { \"default\": false, \"rules\": [] },\n  \"drop\": { \"default\": \"maybe\", \"rules\": [] }\n}\n",
+    );
+    let (_, issues) = load(&invalid);
+    assert_eq!(issues[0].path, "drop.default");
+    assert_eq!(issues[0].line, Some(3));
+}
+
+#[test]
+fn test_schema_describes_config_sections() {
+    let schema = schema::schema();
+    let properties = schema["properties"].as_object().unwrap();
+    for section in ["server", "logging", "drop", "response_logging", "mock", "auth", "reload"] {
+        assert!(properties.contains_key(section), "missing {}", section);
+    }
+    let required: Vec<&str> = schema["required"].as_array().unwrap().iter().filter_map(Value::as_str).collect();
+    assert_eq!(required, vec!["logging", "drop"]);
+    // Fields that are not part of the file stay out of the schema.
+    assert!(!properties.contains_key("source_files"));
+
+    let reference = schema::reference();
+    assert!(reference.contains("| `drop.rules[].response.status_code` | integer | required | HTTP status code. |"));
+    assert!(reference.contains("| `rate_limits.rules[].algorithm` | `token_bucket` \\| `sliding_window` |"));
+}
+
+#[test]
+fn test_config_docs_field_reference_is_up_to_date() {
+    // Regenerate with `cargo run -- docs > config_docs.md`.
+    assert!(schema::DOCS.contains(schema::REFERENCE_BEGIN) && schema::DOCS.contains(schema::REFERENCE_END));
+    assert!(schema::docs() == schema::DOCS, "config_docs.md is out of date; run `logprox docs > config_docs.md`");
+}
+
+#[tokio::test]
+async fn test_schema_endpoint_and_toml_upload() {
+    let (config, _) = Config::check_source("logging: { default: false, rules: [] }\ndrop: { default: false, rules: [] }\n");
+    let holder = Arc::new(ConfigHolder::new(config.unwrap()));
+    let app = admin_router(holder.clone());
+
+    let resp = app.clone().oneshot(Request::builder().uri("/config/schema").body(Body::empty()).unwrap()).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    let served: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(served, schema::schema());
+
+    let toml = "[server]\nport = 9999\n\n[logging]\ndefault = true\nrules = []\n\n[drop]\ndefault = false\nrules = []\n";
+    let req = Request::builder()
+        .method("PUT")
+        .uri("/config")
+        .header("content-type", "application/toml")
+        .body(Body::from(toml))
+        .unwrap();
+    let resp = app.oneshot(req).await.unwrap();
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(holder.get().server.port, 9999);
+    assert!(holder.get().logging.default);
+}
@@ -0,0 +1,88 @@
+use axum::body::Body;
+use axum::http::Request;
+use logprox::admin_router;
+use logprox::config::{Config, ConfigHolder};
+use std::sync::Arc;
+use tower::util::ServiceExt;
+
+// Each test uses its own variable names, since tests run in parallel.
+
+#[test]
+fn test_placeholders_are_substituted_in_every_field() {
+    std::env::set_var("SUBST_PORT", "8081");
+    std::env::set_var("SUBST_HOST", "api.example.com");
+    std::env::set_var("SUBST_EMPTY", "");
+    let (config, issues) = Config::check_source(
+        r#"
+server: { port: "${SUBST_PORT}" }
+upstream: { allowed_hosts: ["${SUBST_HOST}"] }
+logging:
+  default: false
+  rules:
+    - name: "API"
+      match_conditions:
+        headers: { x-tenant: "^${SUBST_TENANT:-acme}$" }
+      capture: { headers: [], body: false, method: true, path: true, timing: true }
+      timeout: ${SUBST_TIMEOUT:-5s}
+drop:
+  default: false
+  rules:
+    - name: "Gone"
+      match_conditions: { path: { patterns: ["^/old"] } }
+      response: { status_code: 410, body: "${SUBST_EMPTY:-fallback} $${NOT_SUBSTITUTED}" }
+"#,
+    );
+    assert!(issues.is_empty(), "{:?}", issues);
+    let config = config.unwrap();
+    assert_eq!(config.server.port, 8081);
+    assert_eq!(config.upstream.allowed_hosts, vec!["api.example.com"]);
+    assert_eq!(config.logging.rules[0].match_conditions.headers["x-tenant"], "^acme$");
+    assert_eq!(config.logging.rules[0].timeout.as_deref(), Some("5s"));
+    assert_eq!(config.drop.rules[0].response.body.as_deref(), Some("fallback ${NOT_SUBSTITUTED}"));
+}
+
+#[test]
+fn test_undefined_variable_without_default_is_an_error() {
+    let (config, issues) = Config::check_source(
+        "logging: { default: false, rules: [] }\ndrop: { default: false, rules: [] }\nadmin: { listen: \"${SUBST_UNDEFINED_LISTEN}\" }\n",
+    );
+    assert!(config.is_none());
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].path, "admin.listen");
+    assert_eq!(issues[0].line, Some(3));
+    assert!(issues[0].message.contains("SUBST_UNDEFINED_LISTEN"), "{}", issues[0].message);
+}
+
+#[tokio::test]
+async fn test_secrets_from_files_are_masked_in_config_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let secret = dir.path().join("drop_body");
+    std::fs::write(&secret, "s3cret\n").unwrap();
+    let yaml = format!(
+        r#"
+logging: {{ default: false, rules: [] }}
+drop:
+  default: false
+  rules:
+    - name: "Secret"
+      match_conditions: {{}}
+      response: {{ status_code: 403, body: "${{file:{}}}" }}
+"#,
+        secret.display()
+    );
+    let (config, issues) = Config::check_source(&yaml);
+    assert!(issues.is_empty(), "{:?}", issues);
+    let config = config.unwrap();
+    assert_eq!(config.drop.rules[0].response.body.as_deref(), Some("s3cret"));
+    assert_eq!(config.masked_json()["drop"]["rules"][0]["response"]["body"], "********");
+
+    let app = admin_router(Arc::new(ConfigHolder::new(config)));
+    let resp = app.oneshot(Request::builder().uri("/config").body(Body::empty()).unwrap()).await.unwrap();
+    let body = axum::body::to_bytes(resp.into_body(), usize::MAX).await.unwrap();
+    assert!(!String::from_utf8_lossy(&body).contains("s3cret"));
+
+    let missing = yaml.replace("drop_body", "missing");
+    let (config, issues) = Config::check_source(&missing);
+    assert!(config.is_none());
+    assert_eq!(issues[0].path, "drop.rules[0].response.body");
+}
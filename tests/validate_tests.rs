@@ -0,0 +1,107 @@
+use logprox::config::{Config, Severity};
+use std::process::Command;
+
+const CONFIG: &str = r#"logging:
+  default: false
+  rules:
+    - name: "Everything"
+      match_conditions: {}
+      capture: { method: true }
+      timeout: 1m
+    - name: "API"
+      match_conditions:
+        path:
+          patterns: ["/api/(oops"]
+        methods: ["GET", "FETCH"]
+      capture: { method: true }
+drop:
+  default: false
+  rules:
+    - name: "Gone"
+      match_conditions:
+        headers:
+          x-bad: "[z-a]"
+      response:
+        status_code: 1000
+    - name: "Gone"
+      match_conditions: { path: { patterns: ["/old"] } }
+      response: { status_code: 410 }
+"#;
+
+fn write_config(dir: &tempfile::TempDir, yaml: &str) -> String {
+    let path = dir.path().join("config.yaml");
+    std::fs::write(&path, yaml).unwrap();
+    path.to_str().unwrap().to_string()
+}
+
+#[test]
+fn test_check_source_reports_every_issue_with_location() {
+    let (config, issues) = Config::check_source(CONFIG);
+    assert!(config.is_some());
+
+    let found: Vec<_> = issues.iter().map(|i| (i.severity, i.path.as_str(), i.line)).collect();
+    assert_eq!(
+        found,
+        vec![
+            (Severity::Error, "logging.rules[0].timeout", Some(7)),
+            (Severity::Warning, "logging.rules[1]", Some(8)),
+            (Severity::Error, "logging.rules[1].match_conditions.path.patterns[0]", Some(11)),
+            (Severity::Warning, "logging.rules[1].match_conditions.methods[1]", Some(12)),
+            (Severity::Error, "drop.rules[0].match_conditions.headers.x-bad", Some(20)),
+            (Severity::Error, "drop.rules[0].response.status_code", Some(22)),
+            (Severity::Warning, "drop.rules[1].name", Some(23)),
+        ]
+    );
+    assert!(issues[1].message.contains("earlier rule 'Everything'"));
+    assert!(issues[3].message.contains("Unknown HTTP method 'FETCH'"));
+    assert_eq!(issues[5].to_string(), "22:22: error: drop.rules[0].response.status_code: Invalid HTTP status code 1000: must be between 100 and 999");
+}
+
+#[test]
+fn test_check_source_locates_syntax_errors() {
+    let (config, issues) = Config::check_source("logging: { default: false, rules: [] }\ndrop: [oops\n");
+    assert!(config.is_none());
+    assert_eq!(issues.len(), 1);
+    assert_eq!(issues[0].severity, Severity::Error);
+    // The unterminated list is detected where the document ends.
+    assert_eq!(issues[0].line, Some(3));
+}
+
+#[test]
+fn test_from_file_lists_all_errors_and_accepts_warnings() {
+    let dir = tempfile::tempdir().unwrap();
+    let err = Config::from_file(&write_config(&dir, CONFIG)).unwrap_err().to_string();
+    assert!(err.starts_with("4 config errors:"), "{}", err);
+    assert!(err.contains("Invalid duration '1m'"));
+    assert!(err.contains("Invalid path pattern '/api/(oops'"));
+    assert!(err.contains("Invalid header pattern '[z-a]'"));
+    assert!(err.contains("Invalid HTTP status code 1000"));
+
+    let warnings_only = CONFIG.replace("1m", "60s").replace("/api/(oops", "/api/").replace("[z-a]", "a").replace("1000", "403");
+    let config = Config::from_file(&write_config(&dir, &warnings_only)).unwrap();
+    assert_eq!(config.drop.rules.len(), 2);
+}
+
+#[test]
+fn test_validate_command_exit_codes() {
+    let dir = tempfile::tempdir().unwrap();
+    let run = |path: &str, extra: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_logprox")).arg("validate").arg(path).args(extra).output().unwrap()
+    };
+
+    let output = run(&write_config(&dir, CONFIG), &[]);
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("config.yaml:22:22: error: drop.rules[0].response.status_code"), "{}", stdout);
+    assert!(stdout.contains("4 error(s), 3 warning(s)"));
+
+    let warnings_only = CONFIG.replace("1m", "60s").replace("/api/(oops", "/api/").replace("[z-a]", "a").replace("1000", "403");
+    let path = write_config(&dir, &warnings_only);
+    assert!(!run(&path, &[]).status.success());
+    assert!(run(&path, &["--allow-warnings"]).status.success());
+
+    let clean = write_config(&dir, "logging: { default: false, rules: [] }\ndrop: { default: false, rules: [] }\n");
+    let output = run(&clean, &["--json"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), "[]");
+}